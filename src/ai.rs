@@ -1,11 +1,32 @@
 use crate::board::Board;
-use crate::cache::Cache;
+use crate::book::OpeningBook;
+use crate::engine::StopHandle;
 use crate::player::Role;
+use crate::proof_number::{PnResult, DEFAULT_PN_NODES};
+use crate::time_manager::TimeManager;
+use crate::transposition::{Bound, TranspositionTable, DEFAULT_TT_MB};
+use crate::vcf::{VcfResult, DEFAULT_VCF_NODES};
 use log::info;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::instrument;
 
 pub const MAX: i32 = 100_000_000;
 pub const HIGH_VALUE: i32 = 5_500_000;
+/// Value of a five on the board, see `Board::evaluate`. The search lowers it by the
+/// ply the five was made at, so quicker wins and slower losses score better.
+pub const WIN_SCORE: i32 = 10_000_000;
+/// Values at least this far from zero are wins or losses at a known distance;
+/// heuristic evaluations are kept below it.
+const MATE_BOUND: i32 = WIN_SCORE - 1_000;
+/// Deepest iteration tried by a time-limited search.
+pub const MAX_SEARCH_DEPTH: i32 = 20;
+/// Share of the move budget the threat (VCT) stages may use before the main search.
+const VCT_TIME_SHARE: f64 = 0.3;
+/// Share of the move budget for the VCF checks run before anything else.
+const VCF_TIME_SHARE: f64 = 0.1;
 
 /// Structure to account for cache statistics
 #[derive(Debug, Default)]
@@ -15,24 +36,82 @@ pub struct CacheHits {
   pub hit: i32,
 }
 
-#[derive(Clone, Debug)]
-pub struct CacheEntry {
-  pub depth: i32,
-  pub value: i32,
-  pub role: Role,
-  pub move_xy: Option<(usize, usize)>,
-  pub path: Vec<(usize, usize)>,
-  pub only_three: bool,
-  pub only_four: bool,
+/// Keys mixed into the board hash so that the side to move and the search mode
+/// (full / threes only / fours only) get separate transposition table entries.
+const ROLE_KEYS: [u64; 2] = [0x9E37_79B9_7F4A_7C15, 0xC2B2_AE3D_27D4_EB4F];
+const ONLY_THREE_KEY: u64 = 0x1656_67B1_9E37_79F9;
+const ONLY_FOUR_KEY: u64 = 0x85EB_CA77_C2B2_AE63;
+
+fn tt_key(hash: u64, role: Role, only_three: bool, only_four: bool) -> u64 {
+  let mut key = hash ^ ROLE_KEYS[(role == Role::White) as usize];
+  if only_three {
+    key ^= ONLY_THREE_KEY;
+  }
+  if only_four {
+    key ^= ONLY_FOUR_KEY;
+  }
+  key
 }
+
+/// Score of a position where the search stops, `cdepth` plies below the root: a win or
+/// loss counts the plies it took, a heuristic value stays out of the win range.
+fn leaf_score(board: &mut Board, role: Role, cdepth: i32) -> i32 {
+  let score = board.evaluate(role);
+  if board.get_winner() != 0 {
+    score - score.signum() * cdepth
+  } else {
+    score.clamp(-MATE_BOUND + 1, MATE_BOUND - 1)
+  }
+}
+
+/// Win and loss values are stored relative to the node, so that an entry found at
+/// another ply still gives the right distance.
+fn value_to_tt(value: i32, cdepth: i32) -> i32 {
+  if value.abs() >= MATE_BOUND {
+    value + value.signum() * cdepth
+  } else {
+    value
+  }
+}
+
+fn value_from_tt(value: i32, cdepth: i32) -> i32 {
+  if value.abs() >= MATE_BOUND {
+    value - value.signum() * cdepth
+  } else {
+    value
+  }
+}
+
 #[derive(Debug)]
 pub struct AIEngine {
   pub depth: i32,
   pub cache_hits: CacheHits,
 
-  cache: Cache<u64, CacheEntry>,
+  /// Shared with the helper threads of a parallel search.
+  tt: Arc<TranspositionTable>,
 
   only_three_threshold: i32,
+
+  /// Search threads of the full-width stage; more than one runs Lazy SMP helpers.
+  pub threads: usize,
+  /// 0 for the main search; helpers alternate their depths by their id.
+  helper_id: usize,
+  /// Raised to make the search unwind, e.g. for helpers once the main thread is done.
+  pub(crate) stop: StopHandle,
+
+  /// Per-move time budget and game clock; unlimited by default.
+  pub time: TimeManager,
+  /// Deadline of the running search stage, if time-limited.
+  stage_deadline: Option<Instant>,
+  /// Set once the stage deadline passes; the running search then unwinds
+  /// without using or caching its partial results.
+  aborted: bool,
+
+  /// Opening book consulted before searching, unless `use_book` is off.
+  pub book: Option<Arc<OpeningBook>>,
+  pub use_book: bool,
+  /// Picks among the book moves; seeded so that games can be replayed.
+  book_rng: StdRng,
 }
 
 impl AIEngine {
@@ -41,11 +120,140 @@ impl AIEngine {
     Self {
       depth,
       cache_hits: CacheHits::default(),
-      cache: Cache::new(0),
+      tt: Arc::new(TranspositionTable::new(DEFAULT_TT_MB)),
       only_three_threshold: 6,
+      threads: 1,
+      helper_id: 0,
+      stop: StopHandle::default(),
+      time: TimeManager::default(),
+      stage_deadline: None,
+      aborted: false,
+      book: None,
+      use_book: true,
+      book_rng: StdRng::seed_from_u64(0),
     }
   }
 
+  /// Replace the transposition table with an empty one of `mb` megabytes.
+  pub fn set_hash_size(&mut self, mb: usize) {
+    self.tt = Arc::new(TranspositionTable::new(mb));
+  }
+
+  /// Forget everything learned in the previous game.
+  pub fn clear_hash(&mut self) {
+    self.tt.clear();
+  }
+
+  /// Play from `book`, choosing among its moves with a generator seeded by `seed`.
+  pub fn set_book(&mut self, book: Arc<OpeningBook>, seed: u64) {
+    self.book = Some(book);
+    self.book_rng = StdRng::seed_from_u64(seed);
+  }
+
+  pub fn set_threads(&mut self, threads: usize) {
+    self.threads = threads.max(1);
+  }
+
+  /// Lazy SMP helper: same settings and clock, the shared table and its own stop flag.
+  fn helper(&self, helper_id: usize, stop: StopHandle) -> AIEngine {
+    AIEngine {
+      depth: self.depth,
+      cache_hits: CacheHits::default(),
+      tt: Arc::clone(&self.tt),
+      only_three_threshold: self.only_three_threshold,
+      threads: 1,
+      helper_id,
+      stop,
+      time: self.time.clone(),
+      stage_deadline: None,
+      aborted: false,
+      book: None,
+      use_book: false,
+      book_rng: StdRng::seed_from_u64(0),
+    }
+  }
+
+  /// Start a search stage that may run until `fraction` of the move budget is used.
+  fn begin_stage(&mut self, fraction: f64) {
+    self.stage_deadline = self.time.deadline(fraction);
+    self.aborted = false;
+  }
+
+  /// Root search of one stage. A search cut short by the deadline yields no move.
+  #[allow(clippy::type_complexity)]
+  fn search_root(
+    &mut self,
+    only_three: bool,
+    only_four: bool,
+    board: &mut Board,
+    role: Role,
+    depth: i32,
+  ) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
+    let mut path_buf = vec![];
+    let result = self.analyze(only_three, only_four, board, role, depth, 0, &mut path_buf, -MAX, MAX);
+    if self.aborted {
+      return (-MAX, None, vec![]);
+    }
+    result
+  }
+
+  /// Full-width search. Without a time limit this is a single search to `self.depth`;
+  /// with one, it deepens iteratively until the deadline and keeps the result of the
+  /// last completed iteration.
+  #[allow(clippy::type_complexity)]
+  fn iterative_deepening(&mut self, board: &mut Board, role: Role) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
+    self.begin_stage(1.0);
+    // Helpers with odd ids search one ply deeper, so threads do not all walk in step
+    let skew = (self.helper_id % 2) as i32;
+    if !self.time.is_limited() {
+      return self.search_root(false, false, board, role, self.depth + skew);
+    }
+
+    // Move ordering alone is the answer if not even depth 1 completes
+    let fallback = board.get_valuable_moves(role, 0, false, false).first().copied();
+    let mut best = (-MAX, fallback, vec![]);
+    for d in (1 + skew)..=MAX_SEARCH_DEPTH {
+      let result = self.search_root(false, false, board, role, d);
+      if self.aborted {
+        info!("Iteration {} aborted after {:?}", d, self.time.elapsed());
+        break;
+      }
+      info!("Iteration {} done: {:?} {:?}", d, result.0, result.1);
+      let decided = result.0.abs() >= HIGH_VALUE;
+      if result.1.is_some() {
+        best = result;
+      }
+      // A proven result will not change; and the next iteration would not finish in time
+      // if this one already took half of the budget.
+      let half_used = self.time.deadline(0.5).is_some_and(|t| Instant::now() >= t);
+      if decided || half_used {
+        break;
+      }
+    }
+    best
+  }
+
+  /// Full-width stage, in parallel when `threads` > 1 (Lazy SMP): helper threads search
+  /// cloned boards and only fill the shared transposition table, while the move comes
+  /// from the main thread alone. Helpers are stopped as soon as the main search ends.
+  #[allow(clippy::type_complexity)]
+  fn parallel_search(&mut self, board: &mut Board, role: Role) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
+    if self.threads <= 1 {
+      return self.iterative_deepening(board, role);
+    }
+    let stop = StopHandle::default();
+    std::thread::scope(|scope| {
+      for helper_id in 1..self.threads {
+        let mut helper = self.helper(helper_id, stop.clone());
+        let mut helper_board = board.clone();
+        scope.spawn(move || helper.iterative_deepening(&mut helper_board, role));
+      }
+      let result = self.iterative_deepening(board, role);
+      stop.stop();
+      result
+    })
+  }
+
   #[instrument]
   #[allow(clippy::too_many_arguments)]
   pub fn analyze(
@@ -62,29 +270,43 @@ impl AIEngine {
   ) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
     self.cache_hits.search += 1;
 
+    // 0) Out of time or stopped: unwind, the caller discards the result
+    if !self.aborted && (self.stop.is_stopped() || self.stage_deadline.is_some_and(|deadline| Instant::now() >= deadline)) {
+      self.aborted = true;
+    }
+    if self.aborted {
+      return (0, None, path.clone());
+    }
+
     // 1) Base exit conditions
     if cdepth >= depth || board.is_game_over() {
-      let score = board.evaluate(role);
-      return (score, None, path.clone());
+      return (leaf_score(board, role, cdepth), None, path.clone());
     }
 
-    // 2) Cache check
-    let hash_val = board.hash();
-    if let Some(prev) = self.cache.get(&hash_val) {
-      if prev.role == role {
-        let depth_left = depth - cdepth;
-        if (prev.value.abs() >= HIGH_VALUE || prev.depth >= depth_left)
-          && prev.only_three == only_three
-          && prev.only_four == only_four
-        {
-          self.cache_hits.hit += 1;
-          let new_path = {
-            let mut p = path.clone();
-            p.extend_from_slice(&prev.path);
-            p
-          };
-          return (prev.value, prev.move_xy, new_path);
-        }
+    // Renju: White's four that can only be blocked on a forbidden point is a win for White
+    if role == Role::Black && board.is_forced_onto_forbidden() {
+      return (-WIN_SCORE + cdepth, None, path.clone());
+    }
+
+    // 2) Transposition table: a deep enough (or decisive) entry whose bound settles
+    //    the window ends the search; otherwise its move is tried first.
+    let depth_left = depth - cdepth;
+    let alpha_orig = alpha;
+    let key = tt_key(board.hash(), role, only_three, only_four);
+    let mut tt_move = None;
+    if let Some(entry) = self.tt.probe(key) {
+      tt_move = entry.best_move;
+      let value = value_from_tt(entry.value, cdepth);
+      let usable = match entry.bound {
+        Bound::Exact => true,
+        Bound::Lower => value >= beta,
+        Bound::Upper => value <= alpha,
+      };
+      if usable && (value.abs() >= HIGH_VALUE || entry.depth >= depth_left) {
+        self.cache_hits.hit += 1;
+        let mut new_path = path.clone();
+        new_path.extend(entry.best_move);
+        return (value, entry.best_move, new_path);
       }
     }
 
@@ -92,13 +314,14 @@ impl AIEngine {
     let mut value = -MAX;
     let mut best_move: Option<(usize, usize)> = None;
     let mut best_path = path.clone();
-    let mut best_depth = best_path.len() as i32;
 
     // 4) Generate "valuable" moves
-    let points = board.get_valuable_moves(role, cdepth, only_three || cdepth > self.only_three_threshold, only_four);
+    let mut points = board.get_valuable_moves(role, cdepth, only_three || cdepth > self.only_three_threshold, only_four);
     if points.is_empty() {
-      let score = board.evaluate(role);
-      return (score, None, path.clone());
+      return (leaf_score(board, role, cdepth), None, path.clone());
+    }
+    if let Some(pos) = tt_move.and_then(|m| points.iter().position(|&p| p == m)) {
+      points[..=pos].rotate_right(1);
     }
 
     // 5) Depth loop
@@ -106,7 +329,10 @@ impl AIEngine {
       // 6) Iterate over all "valuable" moves
       for p in &points {
         let (px, py) = *p;
-        board.put(px, py, role);
+        if !board.put(px, py, role) {
+          // Rejected by the rules (e.g. outside the opening square): nothing to undo
+          continue;
+        }
 
         // Add move to path
         path.push((px, py));
@@ -127,17 +353,19 @@ impl AIEngine {
         board.undo();
         path.pop();
 
+        if self.aborted {
+          return (0, None, path.clone());
+        }
+
         // Return to own role
         eval_score = -eval_score;
 
-        // 8) Compare with maximum
-        if eval_score >= HIGH_VALUE || d == depth {
-          if eval_score > value || (eval_score <= -HIGH_VALUE && value <= -HIGH_VALUE && eval_path.len() as i32 > best_depth) {
-            value = eval_score;
-            best_path = eval_path.clone();
-            best_depth = best_path.len() as i32;
-            best_move = Some((px, py));
-          }
+        // 8) Compare with maximum. Wins and losses carry their distance, so this also
+        //    prefers the quickest win and, when lost, the longest defence.
+        if (eval_score >= HIGH_VALUE || d == depth) && eval_score > value {
+          value = eval_score;
+          best_path = eval_path;
+          best_move = Some((px, py));
         }
 
         // 9) Alpha-beta
@@ -151,37 +379,88 @@ impl AIEngine {
       }
     }
 
-    // 10) Save to cache (if needed)
-    let depth_left = depth - cdepth;
-    let do_put = (cdepth < self.only_three_threshold as i32) || only_three || only_four;
+    // 10) Save to the transposition table (if needed)
+    let do_put = (cdepth < self.only_three_threshold) || only_three || only_four;
     if do_put {
-      let sliced_path = {
-        let mut p = Vec::new();
-        if best_path.len() as i32 >= cdepth {
-          let idx = cdepth as usize;
-          p.extend_from_slice(&best_path[idx..]);
-        }
-        p
+      let bound = if value <= alpha_orig {
+        Bound::Upper
+      } else if value >= beta {
+        Bound::Lower
+      } else {
+        Bound::Exact
       };
-
-      self.cache.put(
-        hash_val,
-        CacheEntry {
-          depth: depth_left,
-          value,
-          role,
-          move_xy: best_move,
-          path: sliced_path,
-          only_three,
-          only_four,
-        },
-      );
+      self.tt.store(key, value_to_tt(value, cdepth), depth_left, bound, best_move);
       self.cache_hits.total += 1;
     }
     (value, best_move, best_path)
   }
 
+  /// Choose a move for `role`, within the time budget if one is set. A book move is
+  /// played without searching; a position whose book moves are all illegal here is
+  /// searched.
   pub fn make_move(&mut self, board: &mut Board, role: Role) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
+    if let Some(book) = self.book.as_ref().filter(|_| self.use_book) {
+      if let Some(mv) = book.choose(board, role, &mut self.book_rng) {
+        info!("Book move {:?}", mv);
+        return (0, Some(mv), vec![mv]);
+      }
+    }
+    self.tt.new_search();
+    self.time.start();
+    let result = self.search_move(board, role);
+    self.time.stop();
+    self.stage_deadline = None;
+    result
+  }
+
+  /// Play our VCF if there is one. If the opponent has one instead, play the first point
+  /// of their sequence that refutes it. `None` when neither side has a proven VCF.
+  #[allow(clippy::type_complexity)]
+  fn check_vcf(&mut self, board: &mut Board, role: Role) -> Option<(i32, Option<(usize, usize)>, Vec<(usize, usize)>)> {
+    let deadline = self.time.deadline(VCF_TIME_SHARE);
+    let stop = Some(&self.stop);
+    if let VcfResult::Win(sequence) = board.find_vcf(role, DEFAULT_VCF_NODES, deadline, stop) {
+      info!("VCF found: {:?}", sequence);
+      return Some((HIGH_VALUE, sequence.first().copied(), sequence));
+    }
+
+    let opponent = role.opponent();
+    let VcfResult::Win(threat) = board.find_vcf(opponent, DEFAULT_VCF_NODES, deadline, stop) else {
+      return None;
+    };
+    info!("Opponent VCF: {:?}", threat);
+    // Every point of the sequence is a candidate: taking a four's square, its block or
+    // the final five point may break the chain
+    let mut candidates = threat.clone();
+    candidates.dedup();
+    for (x, y) in candidates {
+      if board.board[x + 1][y + 1] != 0 || !board.put(x, y, role) {
+        continue;
+      }
+      let refuted = board.find_vcf(opponent, DEFAULT_VCF_NODES, deadline, stop) == VcfResult::NoWin;
+      let value = if refuted { board.evaluate(role) } else { 0 };
+      board.undo();
+      if refuted {
+        info!("VCF defence at ({}, {})", x, y);
+        return Some((value, Some((x, y)), vec![(x, y)]));
+      }
+    }
+    None
+  }
+
+  /// VCT stage: proof-number search over threat moves. Only a proven win is returned;
+  /// an unproven position is left to the full-width search.
+  #[allow(clippy::type_complexity)]
+  fn prove_vct(&mut self, board: &mut Board, role: Role) -> Option<(i32, Option<(usize, usize)>, Vec<(usize, usize)>)> {
+    let deadline = self.time.deadline(VCT_TIME_SHARE);
+    match board.prove_vct(role, DEFAULT_PN_NODES, deadline, Some(&self.stop)) {
+      PnResult::ProvenWin(line) => Some((HIGH_VALUE, line.first().copied(), line)),
+      PnResult::ProvenLoss | PnResult::Unknown => None,
+    }
+  }
+
+  #[allow(clippy::type_complexity)]
+  fn search_move(&mut self, board: &mut Board, role: Role) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
     let vct_depth = self.depth + self.depth * 2;
 
     // Если на доске совсем нет ходов, значит это первый ход в партии
@@ -190,6 +469,11 @@ impl AIEngine {
       return (0, Some((center, center)), vec![]);
     }
 
+    // 0) Forced wins by continuous fours, ours first, then the opponent's
+    if let Some(result) = self.check_vcf(board, role) {
+      return result;
+    }
+
     // CRITICAL: Check for opponent's immediate threats that must be defended
     let threats = board.find_critical_threats(role);
 
@@ -204,13 +488,10 @@ impl AIEngine {
         );
 
         // But first check if we have a winning move
-        let mut path_buf = vec![];
-        let (win_value, win_move, win_path) = self.analyze(true, false, board, role, vct_depth, 0, &mut path_buf, -MAX, MAX);
-
-        if win_value >= HIGH_VALUE && win_move.is_some() {
+        if let Some(win) = self.prove_vct(board, role) {
           // We have a winning move - take it!
           info!("We have winning move despite threat!");
-          return (win_value, win_move, win_path);
+          return win;
         }
 
         // Otherwise, defend the critical position
@@ -222,13 +503,10 @@ impl AIEngine {
           highest_threat.0, highest_threat.1, highest_threat.2
         );
 
-        let mut path_buf = vec![];
-        let (win_value, win_move, win_path) = self.analyze(true, false, board, role, vct_depth, 0, &mut path_buf, -MAX, MAX);
-
-        // Only attack if we have a clear winning sequence
-        if win_value >= HIGH_VALUE && win_move.is_some() {
+        // Only attack if we have a proven winning sequence
+        if let Some(win) = self.prove_vct(board, role) {
           info!("We have winning move, attacking instead of defending semi-open four");
-          return (win_value, win_move, win_path);
+          return win;
         }
 
         // Otherwise defend
@@ -237,34 +515,15 @@ impl AIEngine {
       }
     }
 
-    // 1) First try to analyze with (onlyThree=true, onlyFour=false)
-    //    similar to "let [value, move, path] = this.analyze(true, false, ...)"
-    let mut path_buf = vec![];
-    let (mut value, mut mv, mut path) = self.analyze(true, false, board, role, vct_depth, 0, &mut path_buf, -MAX, MAX);
-    // If the score >= SCORES.FIVE => direct return
-    if value >= HIGH_VALUE {
-      info!("AI 1 analyze {:?} {:?} {:?}", value, mv, path);
-      return (value, mv, path);
+    // 1) VCT stage: a proven win by continuous threats is played right away
+    if let Some(win) = self.prove_vct(board, role) {
+      info!("AI 1 VCT proof {:?}", win);
+      return win;
     }
 
-    // 2) Otherwise (onlyThree=false, onlyFour=false)
-    let mut path_buf2 = vec![];
-    let (value2, mv2, path2) = self.analyze(false, false, board, role, self.depth, 0, &mut path_buf2, -MAX, MAX);
-    info!("AI 2 analyze {:?} {:?} {:?}", value2, mv2, path2);
-
-    // IMPROVED: Don't blindly choose Full Depth if it's worse than VCT
-    // If Full Depth gives negative evaluation but VCT gave positive, prefer VCT
-    if value2 < 0 && value >= 0 && mv.is_some() {
-      info!(
-        "Full Depth result is negative ({}), using VCT result ({}) instead",
-        value2, value
-      );
-      // Keep VCT result
-    } else {
-      value = value2;
-      mv = mv2;
-      path = path2;
-    }
+    // 2) Otherwise full-width search
+    let (value, mv, path) = self.parallel_search(board, role);
+    info!("AI 2 analyze {:?} {:?} {:?}", value, mv, path);
 
     if mv.is_none() {
       info!("AI 3 analyze return {:?} {:?} {:?}", value, mv, path);
@@ -273,41 +532,22 @@ impl AIEngine {
 
     // 3) Make a move on the board to check further
     let (mx, my) = mv.unwrap();
-    board.put(mx, my, role);
+    if !board.put(mx, my, role) {
+      info!("AI 3 analyze return {:?} {:?} {:?}", value, mv, path);
+      return (value, mv, path);
+    }
 
     // 4) Look at "value2, move2, path2" with (onlyThree=true, board.reverse(), vctDepth)
     let rev_board = board.reverse();
-    let mut path_buf3 = vec![];
-    let (value_rev, move_rev, path_rev) = self.analyze(
-      true,
-      false,
-      &mut rev_board.clone(),
-      role,
-      vct_depth,
-      0,
-      &mut path_buf3,
-      -MAX,
-      MAX,
-    );
+    let (value_rev, move_rev, path_rev) = self.search_root(true, false, &mut rev_board.clone(), role, vct_depth);
 
     board.undo(); // Undo
 
-    if value < HIGH_VALUE && value_rev == HIGH_VALUE && path_rev.len() > path.len() {
-      // Additional check:
-      let mut path_buf4 = vec![];
-      let (_, _, path_rev2) = self.analyze(
-        true,
-        false,
-        &mut rev_board.clone(),
-        role,
-        vct_depth,
-        0,
-        &mut path_buf4,
-        -MAX,
-        MAX,
-      );
-
-      if path_rev.len() <= path_rev2.len() {
+    // The opponent has a forced win after our move while we have none: take their key
+    // point instead. The reversed board ignores Renju restrictions, so re-check it.
+    if value < HIGH_VALUE && value_rev >= MATE_BOUND {
+      let rev_forbidden = move_rev.is_some_and(|(rx, ry)| role == Role::Black && board.is_forbidden(rx, ry));
+      if !rev_forbidden {
         info!("AI 4 analyze return {:?} {:?} {:?}", value, move_rev, path_rev);
         return (value, move_rev, path_rev);
       }
@@ -316,3 +556,123 @@ impl AIEngine {
     (value, mv, path)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::board::Rule;
+  use crate::renju_opening::OpeningRule;
+  use std::time::Duration;
+
+  #[test]
+  fn test_time_limited_search_returns_in_time() {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(8, 8, Role::White);
+    board.put(6, 8, Role::Black);
+    board.put(8, 6, Role::White);
+
+    let mut ai = AIEngine::new(3);
+    ai.time = TimeManager::new(Some(Duration::from_millis(300)), None);
+    let started = Instant::now();
+    let (_, mv, _) = ai.make_move(&mut board, Role::Black);
+    let elapsed = started.elapsed();
+
+    assert!(mv.is_some(), "a move is returned even if the search is cut short");
+    assert!(elapsed < Duration::from_secs(5), "search took {:?}", elapsed);
+    assert_eq!(board.history.len(), 4, "the board is restored after searching");
+  }
+
+  #[test]
+  fn test_time_limited_search_finds_win() {
+    let mut board = Board::new(15);
+    for x in 3..7 {
+      board.put(x, 7, Role::White);
+    }
+    board.put(2, 7, Role::Black);
+    board.put(0, 0, Role::Black);
+    board.put(14, 14, Role::Black);
+
+    let mut ai = AIEngine::new(3);
+    ai.time = TimeManager::new(Some(Duration::from_millis(500)), None);
+    let (_, mv, _) = ai.make_move(&mut board, Role::White);
+    assert_eq!(mv, Some((7, 7)));
+  }
+
+  /// White four on row 7, closed at (2,7): (7,7) wins for White and is forced for Black.
+  fn forced_position() -> Board {
+    let mut board = Board::new(15);
+    for x in 3..7 {
+      board.put(x, 7, Role::White);
+    }
+    board.put(2, 7, Role::Black);
+    board.put(8, 8, Role::Black);
+    board.put(9, 6, Role::Black);
+    board
+  }
+
+  #[test]
+  fn test_win_scores_count_plies() {
+    let mut ai = AIEngine::new(3);
+    let mut board = forced_position();
+    // White completes the five at once
+    let (value, mv, _) = ai.search_root(false, false, &mut board, Role::White, 3);
+    assert_eq!((value, mv), (WIN_SCORE - 1, Some((7, 7))));
+    // The second search is answered from the table with the same distance
+    let (again, _, _) = ai.search_root(false, false, &mut board, Role::White, 3);
+    assert_eq!(again, value);
+    assert_eq!(value_from_tt(value_to_tt(-WIN_SCORE + 4, 2), 3), -WIN_SCORE + 5);
+  }
+
+  #[test]
+  fn test_parallel_search_matches_single_thread() {
+    for role in [Role::Black, Role::White] {
+      let mut single = AIEngine::new(2);
+      let expected = single.parallel_search(&mut forced_position(), role).1;
+
+      let mut parallel = AIEngine::new(2);
+      parallel.set_threads(4);
+      let mut board = forced_position();
+      let (_, mv, _) = parallel.parallel_search(&mut board, role);
+      assert_eq!(mv, expected);
+      assert_eq!(mv, Some((7, 7)));
+      assert_eq!(board.history.len(), 7, "main board is restored");
+    }
+  }
+
+  #[test]
+  fn test_parallel_time_limited_search_returns_in_time() {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(8, 8, Role::White);
+
+    let mut ai = AIEngine::new(3);
+    ai.set_threads(3);
+    ai.time = TimeManager::new(Some(Duration::from_millis(300)), None);
+    let started = Instant::now();
+    let (_, mv, _) = ai.make_move(&mut board, Role::Black);
+    assert!(
+      started.elapsed() < Duration::from_millis(1500),
+      "took {:?}",
+      started.elapsed()
+    );
+    let (x, y) = mv.expect("a move");
+    assert!(board.put(x, y, Role::Black));
+  }
+
+  #[test]
+  fn test_search_keeps_to_the_opening_square() {
+    // Taraguchi-10 move 4 must stay within 3 cells of the centre
+    let mut board = Board::with_rule(15, Rule::Renju);
+    board.opening_rule = Some(OpeningRule::Taraguchi10);
+    for &(x, y, role) in &[(7, 7, Role::Black), (7, 8, Role::White), (9, 9, Role::Black)] {
+      assert!(board.put(x, y, role));
+    }
+    let before = board.board.clone();
+    let mut ai = AIEngine::new(2);
+    let (_, mv, _) = ai.search_root(false, false, &mut board, Role::White, 2);
+    assert_eq!(board.board, before, "the searched position is restored");
+    let (x, y) = mv.expect("a move");
+    assert!(board.put(x, y, Role::White), "({}, {}) is outside the square", x, y);
+  }
+}
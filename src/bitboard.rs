@@ -0,0 +1,111 @@
+use crate::patterns::WINDOW_RADIUS;
+
+/// Largest board the line masks hold: one `u64` bit per cell of a line.
+pub const MAX_SIZE: usize = 64;
+
+/// Bits of one window: the activation point and `WINDOW_RADIUS` cells on each side.
+const WINDOW_MASK: u128 = (1 << (2 * WINDOW_RADIUS + 1)) - 1;
+
+/// The stones of both roles as bitmasks of every line in the four directions of
+/// `Board` (horizontal, vertical, "\" and "/"), so that the cells around a point
+/// along a line are read with a few shifts instead of one lookup per cell.
+///
+/// Cell `i` of a line is the `i`-th one met when walking the line in its direction,
+/// which makes the window offsets of `window_index` bit offsets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineMasks {
+  size: usize,
+  /// `lines[dir][line][role]`, role as in `Board`'s `role_index` (0=Black, 1=White).
+  lines: [Vec<[u64; 2]>; 4],
+}
+
+impl LineMasks {
+  pub fn new(size: usize) -> Self {
+    assert!(size <= MAX_SIZE, "a {0}x{0} board is larger than {1}x{1}", size, MAX_SIZE);
+    let diagonals = (2 * size).saturating_sub(1);
+    Self {
+      size,
+      lines: [
+        vec![[0; 2]; size],
+        vec![[0; 2]; size],
+        vec![[0; 2]; diagonals],
+        vec![[0; 2]; diagonals],
+      ],
+    }
+  }
+
+  /// `(line, cell, line length)` of (x, y) in direction `dir`.
+  fn locate(&self, dir: usize, x: usize, y: usize) -> (usize, usize, usize) {
+    let last = self.size - 1;
+    match dir {
+      // [1, 0]: the cells of a row follow x
+      0 => (y, x, self.size),
+      // [0, 1]
+      1 => (x, y, self.size),
+      // [1, 1]: x - y is constant, the line starts on the top or left edge
+      2 => (x + last - y, x.min(y), self.size - x.abs_diff(y)),
+      // [-1, 1]: x + y is constant, y grows along the line
+      _ => (x + y, y - (x + y).saturating_sub(last), self.size - (x + y).abs_diff(last)),
+    }
+  }
+
+  /// Flip the stone of `role` at (x, y), placing or removing it.
+  pub fn toggle(&mut self, role: usize, x: usize, y: usize) {
+    for dir in 0..self.lines.len() {
+      let (line, cell, _) = self.locate(dir, x, y);
+      self.lines[dir][line][role] ^= 1 << cell;
+    }
+  }
+
+  /// Cells around (x, y) in direction `dir` as `(own, blocked)` bitmasks for `role`,
+  /// `blocked` being the opponent's stones and the cells past the board edge. Bit
+  /// `WINDOW_RADIUS + offset` is the cell `offset` steps away, (x, y) itself is bit
+  /// `WINDOW_RADIUS`.
+  pub fn window(&self, role: usize, dir: usize, x: usize, y: usize) -> (u32, u32) {
+    let (line, cell, len) = self.locate(dir, x, y);
+    let masks = self.lines[dir][line];
+    // Shifted up by the radius, so that the window of the first cell starts at bit 0
+    let padded = |mask: u128| mask << WINDOW_RADIUS;
+    let walls = !padded((1 << len) - 1);
+    let own = (padded(masks[role] as u128) >> cell) & WINDOW_MASK;
+    let blocked = ((padded(masks[1 - role] as u128) | walls) >> cell) & WINDOW_MASK;
+    (own as u32, blocked as u32)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_every_direction_reads_its_neighbours() {
+    let size = 7;
+    let mut masks = LineMasks::new(size);
+    let (x, y) = (2, 4);
+    for (dir, [dx, dy]) in [[1, 0], [0, 1], [1, 1], [-1, 1]].into_iter().enumerate() {
+      let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+      masks.toggle(0, nx, ny);
+      let (own, blocked) = masks.window(0, dir, x, y);
+      assert_eq!(own, 1 << (WINDOW_RADIUS + 1), "direction {}", dir);
+      // Black's stone is White's opponent
+      assert_eq!(masks.window(1, dir, x, y).1 & own, own, "direction {}", dir);
+      assert_eq!(blocked & (1 << WINDOW_RADIUS), 0);
+      masks.toggle(0, nx, ny);
+      assert_eq!(masks.window(0, dir, x, y).0, 0);
+    }
+  }
+
+  #[test]
+  fn test_cells_off_the_board_are_blocked() {
+    let masks = LineMasks::new(15);
+    // Corner: everything before (0, 0) is off the board along the row and the column
+    for dir in 0..2 {
+      assert_eq!(masks.window(0, dir, 0, 0).1, (1 << WINDOW_RADIUS) - 1);
+    }
+    // "/" through (0, 0) is the single cell itself
+    assert_eq!(masks.window(0, 3, 0, 0).1 as u128, WINDOW_MASK & !(1 << WINDOW_RADIUS));
+    // Three cells from the right edge
+    let (_, blocked) = masks.window(0, 0, 11, 7);
+    assert_eq!(blocked, (WINDOW_MASK as u32) & !((1 << (WINDOW_RADIUS + 4)) - 1));
+  }
+}
@@ -1,11 +1,15 @@
 use log::info;
 use tracing::instrument;
 
+use crate::bitboard::LineMasks;
 use crate::cache::Cache;
-use crate::patterns::GOMOKU_PATTERNS;
+#[cfg(test)]
+use crate::patterns::is_five_pattern;
+use crate::patterns::{window_index_from_masks, PatternTable};
 use crate::player::Role;
+use crate::renju::ForbiddenChecker;
+use crate::renju_opening::OpeningRule;
 use crate::zobrist_cache::ZobristCache;
-use std::collections::HashMap;
 
 const DIRECTIONS: usize = 4;
 
@@ -16,6 +20,72 @@ const ALL_DIRECTIONS: [[i32; 2]; 4] = [
   [-1, 1], // Diagonal "/"
 ];
 
+/// One of the 8 symmetries of a square board: rotations and reflections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symmetry {
+  Identity,
+  /// x -> size-1-x
+  FlipX,
+  /// y -> size-1-y
+  FlipY,
+  Rotate180,
+  /// Reflection in the main diagonal: x <-> y
+  Transpose,
+  Rotate90,
+  Rotate270,
+  /// Reflection in the anti-diagonal
+  AntiTranspose,
+}
+
+impl Symmetry {
+  pub const ALL: [Symmetry; 8] = [
+    Symmetry::Identity,
+    Symmetry::FlipX,
+    Symmetry::FlipY,
+    Symmetry::Rotate180,
+    Symmetry::Transpose,
+    Symmetry::Rotate90,
+    Symmetry::Rotate270,
+    Symmetry::AntiTranspose,
+  ];
+
+  /// Map (x, y) through this symmetry of a board of `size`.
+  pub fn apply(self, x: usize, y: usize, size: usize) -> (usize, usize) {
+    let n = size - 1;
+    match self {
+      Symmetry::Identity => (x, y),
+      Symmetry::FlipX => (n - x, y),
+      Symmetry::FlipY => (x, n - y),
+      Symmetry::Rotate180 => (n - x, n - y),
+      Symmetry::Transpose => (y, x),
+      Symmetry::Rotate90 => (n - y, x),
+      Symmetry::Rotate270 => (y, n - x),
+      Symmetry::AntiTranspose => (n - y, n - x),
+    }
+  }
+
+  /// The symmetry that undoes this one: the two quarter turns undo each other, every
+  /// other symmetry undoes itself.
+  pub fn inverse(self) -> Symmetry {
+    match self {
+      Symmetry::Rotate90 => Symmetry::Rotate270,
+      Symmetry::Rotate270 => Symmetry::Rotate90,
+      s => s,
+    }
+  }
+
+  /// A move list mapped through this symmetry, in the same order.
+  pub fn apply_moves(self, moves: &[(usize, usize, Role)], size: usize) -> Vec<(usize, usize, Role)> {
+    moves
+      .iter()
+      .map(|&(x, y, role)| {
+        let (tx, ty) = self.apply(x, y, size);
+        (tx, ty, role)
+      })
+      .collect()
+  }
+}
+
 /// For convenience, we will make enum templates or IDs (we could store the cost directly).
 /// But we will use the existing self.patterns.
 #[repr(i32)]
@@ -96,19 +166,62 @@ struct ValuableMovesCacheEntry {
   only_four: bool,
 }
 
+/// Rule set the board is played under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+  /// Any run of five or more stones wins, no restrictions.
+  Freestyle,
+  /// Standard gomoku: exactly five wins, six or more does not (for both sides).
+  Standard,
+  /// Renju: Black wins only with exactly five and may not play
+  /// double-three, double-four or overline. White's overline wins.
+  Renju,
+}
+
+impl Rule {
+  /// Whether a run longer than five does NOT count as a win for `role_val` (1 / -1).
+  pub fn exact_five(&self, role_val: i32) -> bool {
+    match self {
+      Rule::Freestyle => false,
+      Rule::Standard => true,
+      Rule::Renju => role_val == Role::Black.to_int(),
+    }
+  }
+
+  /// Whether a stone run of length `len` wins for `role_val` under this rule.
+  pub fn is_win(&self, len: usize, role_val: i32) -> bool {
+    if self.exact_five(role_val) {
+      len == 5
+    } else {
+      len >= 5
+    }
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct Board {
   pub size: usize,
+  pub rule: Rule,
   pub board: Vec<Vec<i32>>,               // 0=empty, +1=white, -1=black
   pub history: Vec<(usize, usize, Role)>, // History of moves
+  /// The same stones as `board`, as bitmasks of every line, kept by `put` / `undo`.
+  lines: LineMasks,
+  /// Renju opening rule restricting where the first moves may go.
+  pub opening_rule: Option<OpeningRule>,
   zorbist_cache: ZobristCache,
-  winner_cache: Cache<u64, i32>,
-  gameover_cache: Cache<u64, bool>,
+  /// Role value of the first player who made five (0 while nobody has), kept by
+  /// `put` / `undo`.
+  winner: i32,
+  /// Length of `history` right after the winning move.
+  win_ply: usize,
   evaluate_cache: Cache<u64, (Role, i32)>,
   valuable_moves_cache: Cache<u64, ValuableMovesCacheEntry>,
 
-  role_scores: HashMap<Role, Vec<Vec<i32>>>,
-  patterns: &'static [(i32, &'static [i32], i32)],
+  /// `role_scores[role_index(role)][x][y]`
+  role_scores: [Vec<Vec<i32>>; 2],
+  /// Sum of `role_scores` per role (see `role_index`), kept by `set_score`.
+  score_totals: [i32; 2],
+  patterns: &'static PatternTable,
   shape_cache: ShapeCache,
 }
 
@@ -116,6 +229,7 @@ pub struct Board {
 //  - 1, if there is a "stone of the current role" (role_val)
 //  - 2, if there is an "opponent or wall/out-of-bounds"
 //  - 0, if the cell is empty
+#[cfg(test)]
 fn cell_pattern_value(board_val: i32, role_val: i32) -> i32 {
   if board_val == role_val {
     1
@@ -128,7 +242,7 @@ fn cell_pattern_value(board_val: i32, role_val: i32) -> i32 {
 }
 
 impl Board {
-  // Create a new board with the given size
+  // Create a new freestyle board with the given size
   pub fn new(size: usize) -> Self {
     let size_with_wall = size + 2;
     // Create a 2D vector of size_with_wall x size_with_wall, filled with values of 2
@@ -139,27 +253,37 @@ impl Board {
       }
     }
 
-    let mut role_scores = HashMap::new();
-    for &r in &[Role::White, Role::Black] {
+    let role_scores = [(); 2].map(|_| {
       let mut scores = vec![vec![0; size]; size];
       let center = size / 2;
       scores[center][center] = 1000; // Add more points to the center of the board
-      role_scores.insert(r, scores);
-    }
+      scores
+    });
 
-    Self {
+    let mut board = Self {
       size,
+      rule: Rule::Freestyle,
       board: b,
-      history: Vec::new(),                    // Initialize an empty history
+      lines: LineMasks::new(size),
+      history: Vec::new(), // Initialize an empty history
+      opening_rule: None,
       zorbist_cache: ZobristCache::new(size), // Initialize Zobrist cache for the board size
-      winner_cache: Cache::new(0),            // Initialize winner cache
-      gameover_cache: Cache::new(0),          // Initialize gameover cache
-      valuable_moves_cache: Cache::new(0),    // Initialize valuable moves cache
+      winner: 0,
+      win_ply: 0,
+      valuable_moves_cache: Cache::new(0), // Initialize valuable moves cache
       role_scores,
-      patterns: GOMOKU_PATTERNS,
+      score_totals: [0; 2],
+      patterns: PatternTable::gomoku(),
       evaluate_cache: Cache::new(0),
       shape_cache: ShapeCache::new(size),
-    }
+    };
+    board.score_totals = board.summed_scores();
+    board
+  }
+
+  // Create a new board with the given size and rule set
+  pub fn with_rule(size: usize, rule: Rule) -> Self {
+    Self { rule, ..Self::new(size) }
   }
 
   // Place a stone on the board
@@ -172,21 +296,39 @@ impl Board {
       // Check if the position is already occupied
       return false;
     }
+    if self
+      .opening_rule
+      .is_some_and(|rule| !rule.allows(self.history.len() + 1, x, y, self.size))
+    {
+      // Opening moves must stay within their central square
+      return false;
+    }
+    if role == Role::Black && self.is_forbidden(x, y) {
+      // Renju: Black may not play double-three, double-four or overline
+      return false;
+    }
+    // Only the stone just placed can complete a five
+    if self.winner == 0 && self.check_five(x, y, role) {
+      self.winner = role.to_int();
+      self.win_ply = self.history.len() + 1;
+    }
     self.board[x + 1][y + 1] = role.to_int(); // Place the stone
+    self.lines.toggle(role_index(role), x, y);
     self.history.push((x, y, role)); // Record the move in history with adjusted index
 
     // Update Zobrist hash
     self.zorbist_cache.toggle_piece(x, y, role.to_int());
 
     // Reset scores for the current cell
-    self.role_scores.get_mut(&Role::Black).unwrap()[x][y] = 0;
-    self.role_scores.get_mut(&Role::White).unwrap()[x][y] = 0;
+    self.set_score(Role::Black, x, y, 0);
+    self.set_score(Role::White, x, y, 0);
 
     // Mark shape_cache.roleScores as "dirty"
     self.shape_cache.mark_neighbors_dirty(role, x, y, self.size);
     self.shape_cache.mark_neighbors_dirty(role.opponent(), x, y, self.size);
 
     self.recalc_scores(x, y);
+    debug_assert_eq!(self.score_totals, self.summed_scores(), "score totals out of sync after put");
 
     true
   }
@@ -218,8 +360,8 @@ impl Board {
   /// Example of a fully updated cacl_score_for_point that uses shape_cache.
   pub fn cacl_score_for_point(&mut self, x: usize, y: usize) {
     // Reset score=0 for (x,y) for both roles — then we will sum up
-    *self.value_mut(Role::Black, x, y) = 0;
-    *self.value_mut(Role::White, x, y) = 0;
+    self.set_score(Role::Black, x, y, 0);
+    self.set_score(Role::White, x, y, 0);
 
     // For each role — sum up 4 directions:
     for &role in &[Role::Black, Role::White] {
@@ -261,7 +403,7 @@ impl Board {
       }
 
       // Write total_score
-      *self.value_mut(role, x, y) = total_score;
+      self.set_score(role, x, y, total_score);
     }
   }
 
@@ -288,8 +430,28 @@ impl Board {
   /// Returns `(ShapeId, total_cost)`, where `ShapeId` is the ID of the pattern with the maximum cost,
   /// and `total_cost` is the sum of all matched patterns.
   /// Thus, if the point (x,y) creates multiple threats, they will be summed.
+  ///
+  /// The line around (x,y) is read from the line bitmasks and encoded as an index into
+  /// the precomputed `PatternTable`.
   #[instrument]
   fn find_best_pattern_in_dir(&self, role: Role, x: usize, y: usize, dir: usize) -> (ShapeId, i32) {
+    // Every pattern is activated on an empty cell
+    if self.board[x + 1][y + 1] != 0 {
+      return (ShapeId::None, 0);
+    }
+    let role_val = role.to_int();
+    let (own, blocked) = self.lines.window(role_index(role), dir, x, y);
+    let window = window_index_from_masks(own, blocked);
+    // Apply a small heuristic to skip very cheap patterns if the game is already advanced
+    let skip_cheap = self.history.len() > 2;
+    let (best, cost) = self.patterns.lookup(window, self.rule.exact_five(role_val), skip_cheap);
+    (best.map_or(ShapeId::None, ShapeId::Pattern), cost)
+  }
+
+  /// `find_best_pattern_in_dir` by matching every pattern against the board, the
+  /// reference the pattern table is checked against.
+  #[cfg(test)]
+  fn scan_best_pattern_in_dir(&self, role: Role, x: usize, y: usize, dir: usize) -> (ShapeId, i32) {
     let role_val = role.to_int();
     let (dx, dy) = match dir {
       0 => (1, 0),  // horizontal direction
@@ -303,7 +465,7 @@ impl Board {
     let mut best_shape = ShapeId::None;
     let mut sum_cost = 0; // sum of costs of all matched patterns
 
-    for (i_pattern, &(act_idx, ref pattern_vec, cost)) in self.patterns.iter().enumerate() {
+    for (i_pattern, &(act_idx, ref pattern_vec, cost)) in self.patterns.patterns.iter().enumerate() {
       // Let's apply a small heuristic to skip
       // very cheap patterns if the game is already advanced
       if self.history.len() > 2 && cost < 200 {
@@ -312,6 +474,10 @@ impl Board {
 
       // Check for pattern match
       if self.check_pattern(role_val, x, y, dx, dy, act_idx, pattern_vec) {
+        // Under exact-five rules a "five" that joins more stones is an overline, not a win
+        if is_five_pattern(pattern_vec) && self.rule.exact_five(role_val) && self.is_overline(role_val, x, y, dx, dy, act_idx) {
+          continue;
+        }
         // If matched, add cost to sum_cost
         sum_cost += cost;
         // Compare if this is the most expensive pattern
@@ -328,6 +494,7 @@ impl Board {
 
   /// Check if pattern_vec matches when "activating" (x,y),
   /// in the direction (dx,dy), if act_idx is the "activation point".
+  #[cfg(test)]
   fn check_pattern(
     &self,
     role_val: i32,
@@ -361,9 +528,25 @@ impl Board {
     true
   }
 
+  /// Whether a five pattern activated at (x, y) has another `role_val` stone right
+  /// before or after its 5-cell window, i.e. would actually make an overline.
+  #[cfg(test)]
+  fn is_overline(&self, role_val: i32, x: usize, y: usize, dx: i32, dy: i32, act_idx: i32) -> bool {
+    [-act_idx - 1, 5 - act_idx].iter().any(|&offset| {
+      // +1 for the wall border: the neighbours of the window are at most one cell outside
+      let bx = x as i32 + 1 + offset * dx;
+      let by = y as i32 + 1 + offset * dy;
+      bx >= 0
+        && by >= 0
+        && bx < self.board.len() as i32
+        && by < self.board.len() as i32
+        && self.board[bx as usize][by as usize] == role_val
+    })
+  }
+
   /// Get role score at position (x, y) for logging purposes
   pub fn get_role_score(&self, role: Role, x: usize, y: usize) -> i32 {
-    self.role_scores.get(&role).map(|scores| scores[x][y]).unwrap_or(0)
+    self.role_scores[role_index(role)][x][y]
   }
 
   /// Find all critical threats from opponent that must be defended
@@ -379,8 +562,14 @@ impl Board {
     for x in 0..self.size {
       for y in 0..self.size {
         if self.board[x + 1][y + 1] == 0 {
+          // Under Renju we cannot block on a point that is forbidden for us
+          if role == Role::Black && self.is_forbidden(x, y) {
+            continue;
+          }
           // Simulate opponent move
-          self.put(x, y, opponent);
+          if !self.put(x, y, opponent) {
+            continue;
+          }
 
           // Check if this creates a winning position
           if self.check_five(x, y, opponent) {
@@ -413,59 +602,63 @@ impl Board {
   }
 
   /// Check if there's a five in a row at position (x, y) for the given role
+  /// (respecting the exact-five requirement of the current rule).
   fn check_five(&self, x: usize, y: usize, role: Role) -> bool {
     let role_val = role.to_int();
-    let bx = x + 1;
-    let by = y + 1;
+    ALL_DIRECTIONS
+      .iter()
+      .any(|&[dx, dy]| self.rule.is_win(self.line_run(x, y, dx, dy, role_val), role_val))
+  }
 
-    // Check all 4 directions
-    for &[dx, dy] in &ALL_DIRECTIONS {
-      let mut count = 1; // count the stone we just placed
-
-      // Count in positive direction
-      for step in 1..5 {
-        let nx = bx as i32 + step * dx;
-        let ny = by as i32 + step * dy;
-        if nx < 0 || nx >= (self.size + 2) as i32 || ny < 0 || ny >= (self.size + 2) as i32 {
-          break;
-        }
-        if self.board[nx as usize][ny as usize] != role_val {
-          break;
-        }
+  /// Length of the run of `role_val` stones through (x, y) in direction (dx, dy),
+  /// counting (x, y) itself as a `role_val` stone whatever it contains now.
+  pub fn line_run(&self, x: usize, y: usize, dx: i32, dy: i32, role_val: i32) -> usize {
+    let bx = x as i32 + 1;
+    let by = y as i32 + 1;
+    let mut count = 1;
+    for sign in [1, -1] {
+      let mut nx = bx + sign * dx;
+      let mut ny = by + sign * dy;
+      // The wall (value 2) around the board stops the walk
+      while self.board[nx as usize][ny as usize] == role_val {
         count += 1;
+        nx += sign * dx;
+        ny += sign * dy;
       }
+    }
+    count
+  }
 
-      // Count in negative direction
-      for step in 1..5 {
-        let nx = bx as i32 - step * dx;
-        let ny = by as i32 - step * dy;
-        if nx < 0 || nx >= (self.size + 2) as i32 || ny < 0 || ny >= (self.size + 2) as i32 {
-          break;
-        }
-        if self.board[nx as usize][ny as usize] != role_val {
-          break;
-        }
-        count += 1;
-      }
+  /// Whether playing `role` at the empty cell (x, y) would make a winning five.
+  pub fn makes_five(&self, x: usize, y: usize, role: Role) -> bool {
+    self.board[x + 1][y + 1] == 0 && self.check_five(x, y, role)
+  }
 
-      if count >= 5 {
-        return true;
+  /// All empty cells where `role` would complete a winning five.
+  pub fn five_points(&self, role: Role) -> Vec<(usize, usize)> {
+    let mut points = Vec::new();
+    for (x, column) in self.role_scores[role_index(role)].iter().enumerate() {
+      for (y, &score) in column.iter().enumerate() {
+        // Every five shape is worth at least FIVE in the pattern table,
+        // so the score matrix is a cheap pre-filter for the exact check.
+        if score >= 4_000_000 && self.makes_five(x, y, role) {
+          points.push((x, y));
+        }
       }
     }
+    points
+  }
 
-    false
+  /// Utility: set `role_scores[role][x][y]`, keeping `score_totals` in step.
+  fn set_score(&mut self, role: Role, x: usize, y: usize, value: i32) {
+    let cell = &mut self.role_scores[role_index(role)][x][y];
+    self.score_totals[role_index(role)] += value - *cell;
+    *cell = value;
   }
 
-  /// Utility: get a reference to `role_scores[role][x][y]`.
-  fn value_mut(&mut self, role: Role, x: usize, y: usize) -> &mut i32 {
-    self
-      .role_scores
-      .get_mut(&role)
-      .unwrap()
-      .get_mut(x)
-      .unwrap()
-      .get_mut(y)
-      .unwrap()
+  /// `score_totals` recomputed from every cell, to cross-check the running totals.
+  fn summed_scores(&self) -> [i32; 2] {
+    self.role_scores.each_ref().map(|scores| scores.iter().flatten().sum())
   }
 
   // Undo the last move
@@ -474,7 +667,11 @@ impl Board {
       // Remove the last move from history
       None => false, // No move to undo
       Some((x, y, _role)) => {
+        if self.history.len() + 1 == self.win_ply {
+          self.winner = 0;
+        }
         self.board[x + 1][y + 1] = 0; // Clear the position on the board with adjusted index
+        self.lines.toggle(role_index(_role), x, y);
         self.zorbist_cache.toggle_piece(x, y, _role.to_int());
 
         // +++ IMPORTANT +++
@@ -483,73 +680,20 @@ impl Board {
         self.shape_cache.mark_neighbors_dirty(_role.opponent(), x, y, self.size);
 
         self.recalc_scores(x, y);
+        debug_assert_eq!(self.score_totals, self.summed_scores(), "score totals out of sync after undo");
         true
       }
     }
   }
 
-  // Check if the game is over
-  pub fn is_game_over(&mut self) -> bool {
-    let hash = self.hash();
-    if let Some(&val) = self.gameover_cache.get(&hash) {
-      if val {
-        return true;
-      }
-    }
-
-    if self.get_winner() != 0 {
-      self.gameover_cache.put(hash, true);
-      return true;
-    }
-
-    for i in 1..=self.size {
-      for j in 1..=self.size {
-        if self.board[i][j] == 0 {
-          self.gameover_cache.put(hash, false);
-          return false;
-        }
-      }
-    }
-
-    self.gameover_cache.put(hash, true);
-    true
+  // Check if the game is over: somebody won or the board is full
+  pub fn is_game_over(&self) -> bool {
+    self.winner != 0 || self.history.len() == self.size * self.size
   }
 
-  // Get the winner of the game
-  pub fn get_winner(&mut self) -> i32 {
-    let hash = self.hash();
-    if let Some(&val) = self.winner_cache.get(&hash) {
-      if val != 0 {
-        return val;
-      }
-    }
-
-    let directions = [(1, 0), (0, 1), (1, 1), (1, -1)];
-    for i in 1..=self.size {
-      for j in 1..=self.size {
-        let cell = self.board[i][j];
-        if cell == 0 {
-          continue;
-        }
-        for &(dx, dy) in &directions {
-          let mut count = 0;
-          while i as isize + dx * count >= 1
-            && i as isize + dx * count <= self.size as isize
-            && j as isize + dy * count >= 1
-            && j as isize + dy * count <= self.size as isize
-            && self.board[(i as isize + dx * count) as usize][(j as isize + dy * count) as usize] == cell
-          {
-            count += 1;
-          }
-          if count >= 5 {
-            self.winner_cache.put(hash, cell);
-            return cell;
-          }
-        }
-      }
-    }
-    self.winner_cache.put(hash, 0);
-    0
+  // Get the winner of the game: -1 for Black, 1 for White, 0 for nobody yet
+  pub fn get_winner(&self) -> i32 {
+    self.winner
   }
 
   #[instrument]
@@ -594,13 +738,17 @@ impl Board {
     let threshold_three = 250_000;
 
     // Get score matrices for the current role and the opponent
-    let my_matrix = &self.role_scores[&role];
-    let opp_matrix = &self.role_scores[&role.opponent()];
+    let my_matrix = &self.role_scores[role_index(role)];
+    let opp_matrix = &self.role_scores[role_index(role.opponent())];
 
     for x in 0..self.size {
       for y in 0..self.size {
-        // Check if the cell is free
-        if self.board[x + 1][y + 1] == 0 {
+        // Check if the cell is free and the opening rule lets the next move go there
+        if self.board[x + 1][y + 1] == 0
+          && self
+            .opening_rule
+            .is_none_or(|rule| rule.allows(self.history.len() + 1, x, y, self.size))
+        {
           // Evaluate the "priority" of this cell
           let my_score = my_matrix[x][y];
           let opp_score = opp_matrix[x][y];
@@ -636,7 +784,13 @@ impl Board {
       });
     }
 
-    // 4) Sort candidates in descending order (i.e., the most priority ones are at the beginning)
+    // 4) Under Renju, Black must never be offered a forbidden point
+    if self.rule == Rule::Renju && role == Role::Black {
+      let mut checker = ForbiddenChecker::new(self);
+      candidates.retain(|&(x, y, _)| !checker.is_forbidden(x, y));
+    }
+
+    // 5) Sort candidates in descending order (i.e., the most priority ones are at the beginning)
     candidates.sort_by_key(|&(_, _, sc)| sc);
     candidates.reverse();
 
@@ -672,15 +826,9 @@ impl Board {
   }
 
   fn evaluate_internal(&self, role: Role) -> i32 {
-    let mut black_score = 0;
-    let mut white_score = 0;
-    // Count points for black and white stones
-    for x in 0..self.size {
-      for y in 0..self.size {
-        black_score += self.role_scores[&Role::Black][x][y];
-        white_score += self.role_scores[&Role::White][x][y];
-      }
-    }
+    // Points for black and white, summed as the cells are rescored
+    let black_score = self.score_totals[role_index(Role::Black)];
+    let white_score = self.score_totals[role_index(Role::White)];
     // Return the difference in points depending on the role
     if role == Role::Black {
       black_score - white_score
@@ -694,8 +842,61 @@ impl Board {
     self.zorbist_cache.get_hash()
   }
 
+  /// Hash the position would have after mapping it through `symmetry`, with this
+  /// board's Zobrist keys.
+  pub fn symmetric_hash(&self, symmetry: Symmetry) -> u64 {
+    self.history.iter().fold(0, |hash, &(x, y, role)| {
+      let (tx, ty) = symmetry.apply(x, y, self.size);
+      hash ^ self.zorbist_cache.key(tx, ty, role.to_int())
+    })
+  }
+
+  /// Hash shared by all 8 symmetric versions of the position (the smallest of their
+  /// hashes), and the symmetry that maps this board onto the version that has it.
+  pub fn canonical_hash(&self) -> (u64, Symmetry) {
+    Symmetry::ALL
+      .iter()
+      .map(|&symmetry| (self.symmetric_hash(symmetry), symmetry))
+      .min()
+      .expect("there are 8 symmetries")
+  }
+
+  /// The position mapped through `symmetry`, replayed move by move under the same
+  /// rules and with the same Zobrist keys, so hashes of the two boards compare.
+  #[cfg(test)]
+  pub fn transformed(&self, symmetry: Symmetry) -> Board {
+    let mut board = self.clone();
+    while board.undo() {}
+    for (x, y, role) in symmetry.apply_moves(&self.history, self.size) {
+      // Rules and opening squares are symmetric, so every move stays legal
+      let placed = board.put(x, y, role);
+      debug_assert!(placed, "symmetric move ({}, {}) rejected", x, y);
+    }
+    board
+  }
+
+  /// A 15x15 board under `rule` with the `black` stones placed, then the `white` ones.
+  /// Panics if any of them is rejected.
+  #[cfg(test)]
+  pub fn with_stones(rule: Rule, black: &[(usize, usize)], white: &[(usize, usize)]) -> Board {
+    let mut board = Board::with_rule(15, rule);
+    for (stones, role) in [(black, Role::Black), (white, Role::White)] {
+      for &(x, y) in stones {
+        assert!(board.put(x, y, role), "cannot place {:?} at ({}, {})", role, x, y);
+      }
+    }
+    board
+  }
+
+  /// Colour-swapped copy of the position. Renju restrictions are colour-specific,
+  /// so a Renju position is copied under the freestyle rule.
   pub fn reverse(&self) -> Board {
-    let mut new_board = Board::new(self.size);
+    let rule = if self.rule == Rule::Renju {
+      Rule::Freestyle
+    } else {
+      self.rule
+    };
+    let mut new_board = Board::with_rule(self.size, rule);
     for &(x, y, role) in &self.history {
       new_board.put(x, y, role.opponent());
     }
@@ -835,6 +1036,48 @@ mod tests_pattern {
     }
     assert_eq!(cost2, 10);
   }
+  #[test]
+  fn test_pattern_table_matches_scan() {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(7);
+    for rule in [Rule::Freestyle, Rule::Standard, Rule::Renju] {
+      for _ in 0..20 {
+        let mut b = Board::with_rule(15, rule);
+        // Dense random positions, bypassing `put` so that fives and overlines show up
+        let stones = rng.gen_range(0..120);
+        for _ in 0..stones {
+          let (x, y) = (rng.gen_range(0..15), rng.gen_range(0..15));
+          let role = if rng.gen_bool(0.5) { Role::Black } else { Role::White };
+          if b.board[x + 1][y + 1] != 0 {
+            continue;
+          }
+          b.board[x + 1][y + 1] = role.to_int();
+          b.lines.toggle(role_index(role), x, y);
+          b.history.push((x, y, role));
+        }
+        for x in 0..15 {
+          for y in 0..15 {
+            for role in [Role::Black, Role::White] {
+              for dir in 0..DIRECTIONS {
+                assert_eq!(
+                  b.find_best_pattern_in_dir(role, x, y, dir),
+                  b.scan_best_pattern_in_dir(role, x, y, dir),
+                  "{:?} {:?} at ({}, {}) dir {}",
+                  rule,
+                  role,
+                  x,
+                  y,
+                  dir
+                );
+              }
+            }
+          }
+        }
+      }
+    }
+  }
 }
 
 #[cfg(test)]
@@ -863,8 +1106,8 @@ mod tests_scoring {
     // (simulate cacl_score_for_point)
     brd.cacl_score_for_point(1, 4);
 
-    let wsc = brd.role_scores[&Role::White][1][4];
-    let bsc = brd.role_scores[&Role::Black][1][4];
+    let wsc = brd.role_scores[role_index(Role::White)][1][4];
+    let bsc = brd.role_scores[role_index(Role::Black)][1][4];
 
     println!("wsc: {:?}, bsc: {:?}", wsc, bsc);
 
@@ -880,6 +1123,45 @@ mod tests_scoring {
       "Black also sees the same location as a finishing move => big score"
     );
   }
+
+  #[test]
+  fn test_score_totals_follow_put_and_undo() {
+    let mut brd = Board::new(15);
+    let start = brd.evaluate_internal(Role::Black);
+    let moves = [(7, 7), (8, 8), (7, 8), (6, 6), (7, 9), (7, 6), (5, 5)];
+    for (i, &(x, y)) in moves.iter().enumerate() {
+      brd.put(x, y, if i % 2 == 0 { Role::Black } else { Role::White });
+      assert_eq!(brd.score_totals, brd.summed_scores());
+    }
+    let black = brd.evaluate_internal(Role::Black);
+    assert_eq!(brd.evaluate_internal(Role::White), -black);
+    while brd.undo() {
+      assert_eq!(brd.score_totals, brd.summed_scores());
+    }
+    assert_eq!(brd.evaluate_internal(Role::Black), start);
+  }
+
+  #[test]
+  fn test_line_masks_follow_put_and_undo() {
+    let mut brd = Board::new(15);
+    let moves = [(7, 7), (8, 8), (7, 8), (6, 6), (7, 9), (14, 0), (0, 14)];
+    let empty = brd.lines.clone();
+    for (i, &(x, y)) in moves.iter().enumerate() {
+      brd.put(x, y, if i % 2 == 0 { Role::Black } else { Role::White });
+    }
+    for x in 0..15 {
+      for y in 0..15 {
+        for dir in 0..DIRECTIONS {
+          assert_eq!(
+            brd.find_best_pattern_in_dir(Role::Black, x, y, dir),
+            brd.scan_best_pattern_in_dir(Role::Black, x, y, dir)
+          );
+        }
+      }
+    }
+    while brd.undo() {}
+    assert_eq!(brd.lines, empty);
+  }
 }
 
 #[cfg(test)]
@@ -889,7 +1171,7 @@ mod tests_winner {
 
   #[test]
   fn test_no_winner_initial() {
-    let mut b = Board::new(5);
+    let b = Board::new(5);
     let w = b.get_winner();
     assert_eq!(w, 0);
     assert!(!b.is_game_over());
@@ -914,6 +1196,39 @@ mod tests_winner {
     assert!(b.is_game_over());
   }
 
+  #[test]
+  fn test_standard_rule_overline_does_not_win() {
+    let mut b = Board::with_rule(10, Rule::Standard);
+    for x in [1, 2, 3, 5, 6] {
+      b.put(x, 4, Role::White);
+    }
+    // (4,4) would join six stones: not a win, and not rated as a five
+    let (_, cost) = b.find_best_pattern_in_dir(Role::White, 4, 4, 0);
+    assert!(cost < 4_000_000, "overline must not be scored as FIVE, got {}", cost);
+    b.put(4, 4, Role::White);
+    assert_eq!(b.get_winner(), 0);
+    assert!(!b.is_game_over());
+
+    // Exactly five still wins
+    let mut b = Board::with_rule(10, Rule::Standard);
+    for y in 0..4 {
+      b.put(7, y, Role::Black);
+    }
+    let (_, cost) = b.find_best_pattern_in_dir(Role::Black, 7, 4, 1);
+    assert!(cost >= 4_000_000);
+    b.put(7, 4, Role::Black);
+    assert_eq!(b.get_winner(), -1);
+  }
+
+  #[test]
+  fn test_freestyle_overline_wins() {
+    let mut b = Board::new(10);
+    for x in 1..7 {
+      b.put(x, 4, Role::Black);
+    }
+    assert_eq!(b.get_winner(), -1);
+  }
+
   #[test]
   fn test_winner_white_diagonal() {
     let mut b = Board::new(5);
@@ -925,6 +1240,37 @@ mod tests_winner {
     assert_eq!(w, 1, "White=+1");
     assert!(b.is_game_over());
   }
+
+  #[test]
+  fn test_undo_clears_winner() {
+    let mut b = Board::new(10);
+    for y in 0..5 {
+      b.put(2, y, Role::Black);
+    }
+    b.put(5, 5, Role::White);
+    assert_eq!(b.get_winner(), -1);
+    // Taking back a later move keeps the win, taking back the winning one clears it
+    b.undo();
+    assert_eq!(b.get_winner(), -1);
+    b.undo();
+    assert_eq!(b.get_winner(), 0);
+    assert!(!b.is_game_over());
+    b.put(2, 4, Role::Black);
+    assert_eq!(b.get_winner(), -1);
+  }
+
+  #[test]
+  fn test_full_board_is_game_over() {
+    let mut b = Board::new(3);
+    for (i, (x, y)) in (0..3).flat_map(|x| (0..3).map(move |y| (x, y))).enumerate() {
+      assert!(!b.is_game_over());
+      b.put(x, y, if i % 2 == 0 { Role::Black } else { Role::White });
+    }
+    assert_eq!(b.get_winner(), 0);
+    assert!(b.is_game_over());
+    b.undo();
+    assert!(!b.is_game_over());
+  }
 }
 
 #[cfg(test)]
@@ -949,7 +1295,7 @@ mod tests_moves {
     let mut b = Board::new(5);
     // Suppose some position where (2,2) gives OpenFour>=1_000_000
     // Simplify => manually set scores:
-    b.role_scores.get_mut(&Role::Black).unwrap()[2][2] = 1_500_000;
+    b.role_scores[role_index(Role::Black)][2][2] = 1_500_000;
     // "only_four=true"
     let moves = b.get_moves(Role::Black, 2, false, true);
     // should contain (2,2), as score>=1_000_000 => "FOUR"
@@ -969,3 +1315,72 @@ mod tests_moves {
     assert!(!mv1.is_empty());
   }
 }
+
+#[cfg(test)]
+mod tests_symmetry {
+  use super::*;
+  use crate::player::Role;
+
+  fn sample_board() -> Board {
+    let mut b = Board::new(15);
+    for (i, &(x, y)) in [(7, 7), (8, 7), (6, 8), (9, 9), (3, 12), (10, 4)].iter().enumerate() {
+      assert!(b.put(x, y, if i % 2 == 0 { Role::Black } else { Role::White }));
+    }
+    b
+  }
+
+  #[test]
+  fn test_symmetries_round_trip() {
+    for size in [14, 15] {
+      for symmetry in Symmetry::ALL {
+        let mut seen = vec![vec![false; size]; size];
+        for x in 0..size {
+          for y in 0..size {
+            let (tx, ty) = symmetry.apply(x, y, size);
+            assert!(!seen[tx][ty], "{:?} maps two cells onto ({}, {})", symmetry, tx, ty);
+            seen[tx][ty] = true;
+            assert_eq!(symmetry.inverse().apply(tx, ty, size), (x, y), "{:?}", symmetry);
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_transformed_board_round_trip() {
+    let b = sample_board();
+    for symmetry in Symmetry::ALL {
+      let t = b.transformed(symmetry);
+      assert_eq!(t.history, symmetry.apply_moves(&b.history, b.size));
+      assert_eq!(t.hash(), b.symmetric_hash(symmetry), "{:?}", symmetry);
+      let back = t.transformed(symmetry.inverse());
+      assert_eq!(back.history, b.history, "{:?}", symmetry);
+      assert_eq!(back.board, b.board);
+      assert_eq!(back.hash(), b.hash());
+    }
+  }
+
+  #[test]
+  fn test_canonical_hash_is_shared() {
+    let b = sample_board();
+    let (key, symmetry) = b.canonical_hash();
+    assert_eq!(b.symmetric_hash(symmetry), key);
+    for other in Symmetry::ALL {
+      let (other_key, to_canonical) = b.transformed(other).canonical_hash();
+      assert_eq!(other_key, key, "{:?}", other);
+      // Moves map onto the canonical orientation from any version of the position
+      let (px, py) = other.apply(2, 5, 15);
+      let (mx, my) = to_canonical.apply(px, py, 15);
+      let (cx, cy) = symmetry.apply(2, 5, 15);
+      let mut canonical_board = b.transformed(symmetry);
+      let mut from_other = b.transformed(other).transformed(to_canonical);
+      canonical_board.put(cx, cy, Role::White);
+      from_other.put(mx, my, Role::White);
+      assert_eq!(from_other.board, canonical_board.board, "{:?}", other);
+    }
+    // A different position gets a different hash
+    let mut moved = b.clone();
+    moved.put(0, 0, Role::White);
+    assert_ne!(moved.canonical_hash().0, key);
+  }
+}
@@ -0,0 +1,416 @@
+use crate::bitboard::MAX_SIZE;
+use crate::board::{Board, Rule};
+use crate::engine::EngineConfig;
+use crate::match_runner::{MatchConfig, Termination};
+use crate::openings::random_openings;
+use crate::player::Role;
+use crate::psq::{PsqGame, PsqResult};
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Book file signature and format version. Version 2 keys positions by
+/// `Board::canonical_hash`, version 3 records the rule, version 4 widens move counts
+/// to 16 bits.
+const BOOK_MAGIC: &[u8; 4] = b"GBK4";
+/// Book read by default, and written by default when building one.
+pub const DEFAULT_BOOK_FILE: &str = "gomoku_book.bin";
+/// Default number of plies a book is built for.
+pub const DEFAULT_BOOK_PLIES: usize = 10;
+
+/// A book move with its weight (relative chance of being played) and results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BookMove {
+  pub mv: (usize, usize),
+  pub weight: u32,
+  pub games: u32,
+  /// Games won by the side playing the move.
+  pub wins: u32,
+  pub draws: u32,
+}
+
+/// Opening book: moves and statistics per position, keyed by `Board::canonical_hash`,
+/// which is the same for all 8 symmetric versions of a position and in every run.
+/// Moves are stored in the canonical orientation and mapped back on lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningBook {
+  pub size: usize,
+  /// Rule the games were played under; the book is only used for that rule.
+  pub rule: Rule,
+  entries: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+  pub fn new(size: usize, rule: Rule) -> Self {
+    Self {
+      size,
+      rule,
+      entries: HashMap::new(),
+    }
+  }
+
+  /// Positions in the book.
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Book moves for `role` in the position on `board`, in its coordinates. Moves that
+  /// `board` does not allow (forbidden points, cells outside the opening square) are
+  /// left out.
+  pub fn probe(&self, board: &Board, role: Role) -> Vec<BookMove> {
+    if board.size != self.size || board.rule != self.rule {
+      return Vec::new();
+    }
+    let (key, symmetry) = board.canonical_hash();
+    let back = symmetry.inverse();
+    self.entries.get(&key).map_or(Vec::new(), |moves| {
+      moves
+        .iter()
+        .map(|&m| BookMove {
+          mv: back.apply(m.mv.0, m.mv.1, self.size),
+          ..m
+        })
+        .filter(|m| board.clone().put(m.mv.0, m.mv.1, role))
+        .collect()
+    })
+  }
+
+  /// A book move for `role` on `board`, picked at random in proportion to the weights.
+  /// `None` out of book or when every legal move has weight 0.
+  pub fn choose(&self, board: &Board, role: Role, rng: &mut impl Rng) -> Option<(usize, usize)> {
+    let moves = self.probe(board, role);
+    let total: u64 = moves.iter().map(|m| m.weight as u64).sum();
+    if total == 0 {
+      return None;
+    }
+    let mut pick = rng.gen_range(0..total);
+    for m in moves {
+      if pick < m.weight as u64 {
+        return Some(m.mv);
+      }
+      pick -= m.weight as u64;
+    }
+    None
+  }
+
+  /// Learn the first `plies` moves of a game that ended with `winner` (`None` for a
+  /// draw). A move's weight is 2 per win and 1 per draw of the side that played it.
+  pub fn add_game(&mut self, moves: &[(usize, usize, Role)], winner: Option<Role>, plies: usize) {
+    let mut board = Board::with_rule(self.size, self.rule);
+    for &(x, y, role) in moves.iter().take(plies) {
+      let (key, symmetry) = board.canonical_hash();
+      if !board.put(x, y, role) {
+        // Not a game of this rule from here on
+        break;
+      }
+      let mv = symmetry.apply(x, y, self.size);
+      let entry = self.entries.entry(key).or_default();
+      let index = match entry.iter().position(|m| m.mv == mv) {
+        Some(index) => index,
+        None => {
+          entry.push(BookMove {
+            mv,
+            ..BookMove::default()
+          });
+          entry.len() - 1
+        }
+      };
+      let book_move = &mut entry[index];
+      book_move.games += 1;
+      match winner {
+        Some(w) if w == role => book_move.wins += 1,
+        None => book_move.draws += 1,
+        Some(_) => {}
+      }
+      book_move.weight = 2 * book_move.wins + book_move.draws;
+    }
+  }
+
+  /// Learn the games of a `.psq` collection, played under the book's rule. Games on
+  /// another board size are skipped, unfinished ones count as draws. Returns the number
+  /// of games learned.
+  pub fn add_psq_games(&mut self, games: &[PsqGame], plies: usize) -> Result<usize, String> {
+    let mut learned = 0;
+    let (size, rule) = (self.size, self.rule);
+    for game in games.iter().filter(|g| g.size == size) {
+      let board = game.to_board(rule)?;
+      let winner = match game.result {
+        Some(PsqResult::Winner(role)) => Some(role),
+        Some(PsqResult::Draw) | Some(PsqResult::Unfinished) => None,
+        None => match board.get_winner() {
+          w if w > 0 => Some(Role::White),
+          w if w < 0 => Some(Role::Black),
+          _ => None,
+        },
+      };
+      self.add_game(&board.history, winner, plies);
+      learned += 1;
+    }
+    Ok(learned)
+  }
+
+  /// Learn `games` games of `engine` against itself from random two-stone openings,
+  /// under the book's rule.
+  pub fn add_self_play(&mut self, engine: &EngineConfig, games: usize, per_move: Option<Duration>, plies: usize, seed: u64) {
+    let rule = self.rule;
+    let config = MatchConfig {
+      engine1: engine.clone(),
+      engine2: engine.clone(),
+      games,
+      concurrency: 1,
+      size: self.size,
+      rule,
+      per_move,
+      control: None,
+      openings: random_openings(games, 2, self.size, rule, seed),
+      sprt: None,
+    };
+    let (mut black, mut white) = (engine.create(), engine.create());
+    for index in 0..games {
+      // Every opening once: `play_game` pairs openings for colour swaps
+      let (termination, board) = config.play_game(index * 2, [black.as_mut(), white.as_mut()]);
+      if !matches!(termination, Termination::Forfeit(_) | Termination::InvalidOpening) {
+        self.add_game(&board.history, termination.winner(), plies);
+      }
+    }
+  }
+
+  /// Compact binary form: magic, board size, rule, entry count, then per position its key,
+  /// move count and moves (x, y, weight, games, wins, draws). Little endian. Sizes and
+  /// coordinates take a byte each, so boards larger than 255 cannot be written.
+  pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+    if self.size > u8::MAX as usize {
+      return Err(format!("a {0}x{0} board is too large for a book file", self.size));
+    }
+    let mut bytes = BOOK_MAGIC.to_vec();
+    bytes.push(self.size as u8);
+    bytes.push(rule_code(self.rule));
+    bytes.extend((self.entries.len() as u32).to_le_bytes());
+    let mut keys: Vec<&u64> = self.entries.keys().collect();
+    keys.sort();
+    for key in keys {
+      let moves = &self.entries[key];
+      bytes.extend(key.to_le_bytes());
+      // At most 255 * 255 moves, which fits in 16 bits
+      bytes.extend((moves.len() as u16).to_le_bytes());
+      for m in moves {
+        bytes.extend([m.mv.0 as u8, m.mv.1 as u8]);
+        for value in [m.weight, m.games, m.wins, m.draws] {
+          bytes.extend(value.to_le_bytes());
+        }
+      }
+    }
+    Ok(bytes)
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    let mut reader = ByteReader { bytes, pos: 0 };
+    if reader.take(4)? != BOOK_MAGIC {
+      return Err("not an opening book".to_string());
+    }
+    let size = reader.take(1)?[0] as usize;
+    if size > MAX_SIZE {
+      return Err(format!("unsupported board size {}", size));
+    }
+    let code = reader.take(1)?[0];
+    let rule = rule_from_code(code).ok_or_else(|| format!("unknown rule {}", code))?;
+    let mut book = Self::new(size, rule);
+    for _ in 0..reader.u32()? {
+      let key = u64::from_le_bytes(reader.take(8)?.try_into().expect("8 bytes"));
+      let count = u16::from_le_bytes(reader.take(2)?.try_into().expect("2 bytes"));
+      let mut moves = Vec::new();
+      for _ in 0..count {
+        let xy = reader.take(2)?;
+        let mv = (xy[0] as usize, xy[1] as usize);
+        if mv.0 >= book.size || mv.1 >= book.size {
+          return Err(format!("book move {},{} is off the board", mv.0, mv.1));
+        }
+        moves.push(BookMove {
+          mv,
+          weight: reader.u32()?,
+          games: reader.u32()?,
+          wins: reader.u32()?,
+          draws: reader.u32()?,
+        });
+      }
+      book.entries.insert(key, moves);
+    }
+    Ok(book)
+  }
+
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    let bytes = self.to_bytes().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    fs::write(path, bytes)
+  }
+
+  pub fn load(path: &str) -> io::Result<Self> {
+    let bytes = fs::read(path)?;
+    Self::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+  }
+}
+
+fn rule_code(rule: Rule) -> u8 {
+  match rule {
+    Rule::Freestyle => 0,
+    Rule::Standard => 1,
+    Rule::Renju => 2,
+  }
+}
+
+fn rule_from_code(code: u8) -> Option<Rule> {
+  match code {
+    0 => Some(Rule::Freestyle),
+    1 => Some(Rule::Standard),
+    2 => Some(Rule::Renju),
+    _ => None,
+  }
+}
+
+struct ByteReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl ByteReader<'_> {
+  fn take(&mut self, n: usize) -> Result<&[u8], String> {
+    let slice = self.bytes.get(self.pos..self.pos + n).ok_or("truncated book file")?;
+    self.pos += n;
+    Ok(slice)
+  }
+
+  fn u32(&mut self) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn game(moves: &[(usize, usize)]) -> Vec<(usize, usize, Role)> {
+    let roles = [Role::Black, Role::White].into_iter().cycle();
+    moves.iter().zip(roles).map(|(&(x, y), role)| (x, y, role)).collect()
+  }
+
+  #[test]
+  fn test_lookup_under_symmetry() {
+    let mut book = OpeningBook::new(15, Rule::Freestyle);
+    book.add_game(&game(&[(7, 7), (8, 6), (9, 5)]), Some(Role::Black), 3);
+    book.add_game(&game(&[(7, 7), (8, 6), (6, 8)]), None, 3);
+
+    // The same position mirrored left to right
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(6, 6, Role::White);
+    let mut moves = book.probe(&board, Role::Black);
+    moves.sort_by_key(|m| m.mv);
+    assert_eq!(moves.len(), 2);
+    assert_eq!((moves[0].mv, moves[0].weight, moves[0].draws), ((5, 5), 2, 0));
+    assert_eq!((moves[1].mv, moves[1].weight, moves[1].draws), ((8, 8), 1, 1));
+
+    // The first move was played twice and won once
+    let empty = book.probe(&Board::new(15), Role::Black);
+    assert_eq!((empty[0].mv, empty[0].games, empty[0].wins), ((7, 7), 2, 1));
+    assert!(book.probe(&Board::new(9), Role::Black).is_empty());
+  }
+
+  #[test]
+  fn test_choose_follows_weights() {
+    let mut book = OpeningBook::new(15, Rule::Freestyle);
+    book.add_game(&game(&[(7, 7), (7, 6)]), Some(Role::Black), 2);
+    book.add_game(&game(&[(7, 7), (8, 8)]), Some(Role::White), 2);
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    let mut rng = StdRng::seed_from_u64(1);
+    // (7, 6) lost its only game, so it has no weight
+    for _ in 0..20 {
+      let mv = book.choose(&board, Role::White, &mut rng).unwrap();
+      assert!(book.probe(&board, Role::White).iter().any(|m| m.mv == mv && m.weight > 0));
+    }
+    board.put(0, 0, Role::White);
+    assert_eq!(book.choose(&board, Role::White, &mut rng), None);
+  }
+
+  #[test]
+  fn test_binary_round_trip() {
+    let mut book = OpeningBook::new(15, Rule::Freestyle);
+    book.add_game(&game(&[(7, 7), (8, 6), (9, 5), (1, 2)]), Some(Role::White), 4);
+    let bytes = book.to_bytes().unwrap();
+    assert_eq!(OpeningBook::from_bytes(&bytes), Ok(book));
+    assert!(OpeningBook::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(OpeningBook::from_bytes(b"nope").is_err());
+    assert!(OpeningBook::new(300, Rule::Freestyle).to_bytes().is_err());
+  }
+
+  #[test]
+  fn test_binary_round_trip_with_many_replies() {
+    // Every reply to the first move on a 20x20 board: more than a byte can count
+    let mut book = OpeningBook::new(20, Rule::Freestyle);
+    for x in 0..20 {
+      for y in 0..20 {
+        if (x, y) != (10, 10) {
+          book.add_game(&game(&[(10, 10), (x, y)]), None, 2);
+        }
+      }
+    }
+    book.add_game(&game(&[(10, 10), (9, 9), (8, 8)]), Some(Role::Black), 3);
+    let mut board = Board::new(20);
+    board.put(10, 10, Role::Black);
+    assert_eq!(book.probe(&board, Role::White).len(), 399);
+    let bytes = book.to_bytes().unwrap();
+    assert_eq!(OpeningBook::from_bytes(&bytes), Ok(book));
+  }
+
+  #[test]
+  fn test_build_from_psq_and_self_play() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/black_wins.psq");
+    let psq = PsqGame::load(path).unwrap();
+    let mut book = OpeningBook::new(15, Rule::Freestyle);
+    assert_eq!(book.add_psq_games(&[psq], 4), Ok(1));
+    assert_eq!(book.len(), 4);
+
+    book.add_self_play(&EngineConfig::Random { seed: 3 }, 2, None, 4, 1);
+    assert!(book.len() > 4);
+  }
+
+  #[test]
+  fn test_moves_follow_the_rule() {
+    // Black's double three at (7, 7), learned from a freestyle game
+    let moves = game(&[(5, 7), (0, 0), (6, 7), (0, 14), (7, 5), (14, 0), (7, 6), (14, 14), (7, 7)]);
+    let mut freestyle = OpeningBook::new(15, Rule::Freestyle);
+    freestyle.add_game(&moves, Some(Role::Black), 9);
+    let mut renju = Board::with_rule(15, Rule::Renju);
+    for &(x, y, role) in &moves[..8] {
+      assert!(renju.put(x, y, role));
+    }
+    assert!(
+      freestyle.probe(&renju, Role::Black).is_empty(),
+      "a freestyle book is not used under Renju"
+    );
+
+    // A Renju book leaves out a forbidden move and falls back to search
+    let mut book = OpeningBook::new(15, Rule::Renju);
+    book.entries.insert(
+      renju.canonical_hash().0,
+      vec![BookMove {
+        mv: (7, 7),
+        weight: 1,
+        ..BookMove::default()
+      }],
+    );
+    assert!(book.probe(&renju, Role::Black).is_empty());
+    assert_eq!(book.choose(&renju, Role::Black, &mut StdRng::seed_from_u64(0)), None);
+    assert_eq!(
+      OpeningBook::from_bytes(&book.to_bytes().unwrap()).map(|b| b.rule),
+      Ok(Rule::Renju)
+    );
+  }
+}
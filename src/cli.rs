@@ -1,4 +1,7 @@
-use clap::{Parser, ValueEnum};
+use crate::engine::EngineConfig;
+use crate::time_manager::TimeControl;
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 /// Game mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -18,6 +21,48 @@ pub enum FirstPlayerArg {
   AI,
 }
 
+/// Rule set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RuleArg {
+  /// Five or more in a row wins
+  Freestyle,
+  /// Exactly five wins, overlines do not count
+  Standard,
+  /// Renju: forbidden moves for Black, Black needs exactly five
+  Renju,
+}
+
+/// Renju opening rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OpeningRuleArg {
+  /// RIF: three stones, swap, fourth move, two fifth-move alternatives
+  Rif,
+  /// Soosõrv-8: like RIF, with 1 to 8 declared alternatives and a second swap
+  #[value(name = "soosorv8")]
+  Soosorv8,
+  /// Taraguchi-10: swap after each of the first four moves, ten alternatives
+  #[value(name = "taraguchi10")]
+  Taraguchi10,
+}
+
+/// Machine protocol spoken over stdin/stdout instead of the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProtocolArg {
+  /// Gomocup / Piskvork brain protocol
+  Piskvork,
+}
+
+/// AI search engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EngineArg {
+  /// Threat search plus alpha-beta
+  AlphaBeta,
+  /// Monte Carlo Tree Search
+  Mcts,
+  /// Random moves near the stones
+  Random,
+}
+
 /// Gomoku
 #[derive(Parser, Debug)]
 #[command(name = "gomoku_rust", version = "0.1.0")]
@@ -27,13 +72,104 @@ pub struct CliArgs {
   pub mode: GameModeArg,
 
   /// Field size
-  #[arg(long, default_value_t = 15)]
+  #[arg(
+    long,
+    global = true,
+    default_value_t = 15,
+    value_parser = RangedU64ValueParser::<usize>::new().range(1..=crate::bitboard::MAX_SIZE as u64)
+  )]
   pub size: usize,
 
+  /// Rule set
+  #[arg(long, global = true, value_enum, default_value_t=RuleArg::Freestyle)]
+  pub rules: RuleArg,
+
   /// AI depth
   #[arg(long, default_value_t = 3)]
   pub depth: i32,
 
+  /// AI search engine
+  #[arg(long, value_enum, default_value_t=EngineArg::AlphaBeta)]
+  pub engine: EngineArg,
+
+  /// MCTS playouts per move
+  #[arg(long, default_value_t = crate::mcts::DEFAULT_PLAYOUTS)]
+  pub playouts: usize,
+
+  /// MCTS and random engine seed, also used to choose among opening book moves
+  #[arg(long, default_value_t = 0)]
+  pub seed: u64,
+
+  /// Engine of the black AI player, overriding the options above,
+  /// e.g. `alpha-beta:depth=4,threads=2`, `mcts:playouts=5000,seed=1` or `random`
+  #[arg(long)]
+  pub black_engine: Option<EngineConfig>,
+
+  /// Engine of the white AI player, in the same format as --black-engine
+  #[arg(long)]
+  pub white_engine: Option<EngineConfig>,
+
+  /// AI time limit per move, in milliseconds (enables iterative deepening)
+  #[arg(long, global = true)]
+  pub time_per_move: Option<u64>,
+
+  /// AI game clock as <base>+<increment> in seconds, e.g. 300+5
+  #[arg(long, global = true)]
+  pub time_control: Option<TimeControl>,
+
+  /// Opening suite: `renju` for the 26 Renju openings, or a file with one opening per
+  /// line as offsets from the centre, `dx1, dy1, dx2, dy2, ...`. A game starts from a
+  /// random one; a match plays each one with both colours
+  #[arg(long, global = true)]
+  pub openings: Option<String>,
+
+  /// AI transposition table size in megabytes
+  #[arg(long, default_value_t = crate::transposition::DEFAULT_TT_MB)]
+  pub hash_size: usize,
+
+  /// AI search threads (Lazy SMP when more than one)
+  #[arg(long, default_value_t = 1)]
+  pub threads: usize,
+
+  /// Open with Swap2: the first player places three stones, the second one picks a
+  /// colour or adds two stones and lets the first one pick
+  #[arg(long, conflicts_with = "openings")]
+  pub swap2: bool,
+
+  /// Open with a Renju opening rule; first moves are restricted to central squares
+  #[arg(long, value_enum, conflicts_with_all = ["openings", "swap2"])]
+  pub opening_rule: Option<OpeningRuleArg>,
+
+  /// Resume a game saved with the S key; its size, rules, players and engines replace
+  /// the ones given on the command line, and S saves back to the same file
+  #[arg(long, conflicts_with_all = ["openings", "swap2", "opening_rule"])]
+  pub load: Option<String>,
+
+  /// Start from the position of a Piskvork `.psq` game record
+  #[arg(long, conflicts_with_all = ["openings", "swap2", "opening_rule", "load"])]
+  pub import_psq: Option<String>,
+
+  /// Write the game as a Piskvork `.psq` file (with move times and result) when it ends
+  #[arg(long)]
+  pub export_psq: Option<String>,
+
+  /// Start from the main line of an SGF (GM[4]) game record, which must use the --rules rule set
+  /// if it names one (RU)
+  #[arg(long, conflicts_with_all = ["openings", "swap2", "opening_rule", "load", "import_psq"])]
+  pub import_sgf: Option<String>,
+
+  /// Write the game as SGF, with the engines' scores and lines as comments, when it ends
+  #[arg(long)]
+  pub export_sgf: Option<String>,
+
+  /// Opening book the alpha-beta engine plays from, if the file exists
+  #[arg(long, default_value = crate::book::DEFAULT_BOOK_FILE)]
+  pub book: String,
+
+  /// Search every move, never play from the opening book
+  #[arg(long)]
+  pub no_book: bool,
+
   /// First player in Human vs AI mode
   #[arg(long, value_enum, default_value_t=FirstPlayerArg::Human)]
   pub first_player: FirstPlayerArg,
@@ -41,4 +177,121 @@ pub struct CliArgs {
   /// Enable logging to file instead of TUI
   #[arg(long)]
   pub log: bool,
+
+  /// Run as an engine speaking the given protocol on stdin/stdout
+  #[arg(long, value_enum)]
+  pub protocol: Option<ProtocolArg>,
+
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  /// Play a headless match between two engines and report Elo and SPRT statistics
+  Match(MatchArgs),
+  /// Show the known continuations of a position in a Renlib (.lib) or RIF XML library
+  Library(LibraryArgs),
+  /// Build an opening book from .psq game files and/or engine self-play
+  Book(BookArgs),
+  /// Search a fixed set of random openings and report the nodes searched per second
+  Bench(BenchArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+  /// Positions to search
+  #[arg(long, default_value_t = 8)]
+  pub positions: usize,
+
+  /// Stones of each position
+  #[arg(long, default_value_t = 6)]
+  pub opening_moves: usize,
+
+  /// Search depth
+  #[arg(long, default_value_t = 2)]
+  pub depth: i32,
+
+  /// Seed of the positions
+  #[arg(long, default_value_t = 0)]
+  pub seed: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct BookArgs {
+  /// Book file to write; an existing book is extended
+  #[arg(long, default_value = crate::book::DEFAULT_BOOK_FILE)]
+  pub out: String,
+
+  /// Piskvork game files to learn from
+  #[arg(long, num_args = 1..)]
+  pub psq: Vec<String>,
+
+  /// Self-play games to learn from
+  #[arg(long, default_value_t = 0)]
+  pub self_play: usize,
+
+  /// Engine of the self-play games
+  #[arg(long, default_value = "alpha-beta:depth=2")]
+  pub engine: EngineConfig,
+
+  /// Moves of each game that go into the book
+  #[arg(long, default_value_t = crate::book::DEFAULT_BOOK_PLIES)]
+  pub plies: usize,
+
+  /// Seed of the self-play openings
+  #[arg(long, default_value_t = 0)]
+  pub seed: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct LibraryArgs {
+  /// Library file
+  pub file: String,
+
+  /// Moves leading to the position, e.g. `h8 i9 j10`
+  pub moves: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct MatchArgs {
+  /// First engine, e.g. `alpha-beta:depth=2`; results are reported from its side
+  #[arg(long)]
+  pub engine1: EngineConfig,
+
+  /// Second engine, in the same format
+  #[arg(long)]
+  pub engine2: EngineConfig,
+
+  /// Number of games
+  #[arg(long, default_value_t = 100)]
+  pub games: usize,
+
+  /// Games played in parallel
+  #[arg(long, default_value_t = 1)]
+  pub concurrency: usize,
+
+  /// Stones of each random opening, used without --openings (0 starts from the empty board)
+  #[arg(long, default_value_t = 3)]
+  pub opening_moves: usize,
+
+  /// Seed of the random openings
+  #[arg(long, default_value_t = 0)]
+  pub opening_seed: u64,
+
+  /// SPRT null hypothesis: Elo difference elo0 (needs --elo1)
+  #[arg(long, requires = "elo1")]
+  pub elo0: Option<f64>,
+
+  /// SPRT alternative hypothesis: Elo difference elo1 (needs --elo0)
+  #[arg(long, requires = "elo0")]
+  pub elo1: Option<f64>,
+
+  /// SPRT false positive rate
+  #[arg(long, default_value_t = 0.05)]
+  pub alpha: f64,
+
+  /// SPRT false negative rate
+  #[arg(long, default_value_t = 0.05)]
+  pub beta: f64,
 }
@@ -0,0 +1,449 @@
+use crate::ai::{AIEngine, CacheHits};
+use crate::board::{Board, Rule};
+use crate::book::OpeningBook;
+use crate::external::ExternalEngine;
+use crate::mcts::{MctsEngine, DEFAULT_PLAYOUTS};
+use crate::player::Role;
+use crate::time_manager::{TimeControl, TimeManager};
+use crate::transposition::DEFAULT_TT_MB;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Shared flag asking a running search to return as soon as possible.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+  pub fn stop(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_stopped(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  /// Clear the flag before a new search.
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+}
+
+/// Time limits of one `think` call. The caller owns the game clock and charges it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+  /// Hard limit for this move.
+  pub per_move: Option<Duration>,
+  /// Time left on the game clock, if playing with one.
+  pub remaining: Option<Duration>,
+  /// Added to the clock after every move.
+  pub increment: Duration,
+}
+
+impl SearchLimits {
+  pub fn new(per_move: Option<Duration>, control: Option<TimeControl>) -> Self {
+    Self {
+      per_move,
+      remaining: control.map(|c| c.base),
+      increment: control.map(|c| c.increment).unwrap_or_default(),
+    }
+  }
+
+  /// Time manager budgeting a single move under these limits.
+  pub fn time_manager(&self) -> TimeManager {
+    let control = self.remaining.map(|base| TimeControl {
+      base,
+      increment: self.increment,
+    });
+    TimeManager::new(self.per_move, control)
+  }
+
+  /// Charge the time a move took to the clock and add the increment.
+  pub fn charge(&mut self, used: Duration) {
+    if let Some(remaining) = self.remaining.as_mut() {
+      *remaining = remaining.saturating_sub(used) + self.increment;
+    }
+  }
+}
+
+/// Outcome of a search.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchResult {
+  /// Score from the point of view of the side to move.
+  pub value: i32,
+  pub best_move: Option<(usize, usize)>,
+  /// Expected continuation, starting with `best_move`.
+  pub line: Vec<(usize, usize)>,
+}
+
+impl From<(i32, Option<(usize, usize)>, Vec<(usize, usize)>)> for SearchResult {
+  fn from((value, best_move, line): (i32, Option<(usize, usize)>, Vec<(usize, usize)>)) -> Self {
+    Self { value, best_move, line }
+  }
+}
+
+/// A move-choosing engine. Each engine carries its own settings; `Game` and the
+/// protocol front ends only talk to this trait.
+pub trait Engine: Send {
+  /// Short description for logs, e.g. `alpha-beta depth 3`.
+  fn name(&self) -> String;
+
+  /// A new game starts: forget whatever was learned in the previous one.
+  fn new_game(&mut self, size: usize, rule: Rule);
+
+  /// Choose a move for `role` within `limits`.
+  fn think(&mut self, board: &Board, role: Role, limits: &SearchLimits) -> SearchResult;
+
+  /// Handle that stops a running `think` from another thread: `think` borrows the
+  /// engine mutably, so take the handle before starting it.
+  fn stop_handle(&self) -> StopHandle;
+
+  /// Hash table size in megabytes, for engines that have one.
+  fn set_hash_size(&mut self, _mb: usize) {}
+
+  /// Opening book to play from before searching, for engines that use one. `seed`
+  /// seeds the choice among book moves.
+  fn set_book(&mut self, _book: Arc<OpeningBook>, _seed: u64) {}
+
+  /// Cache statistics, for engines that keep them.
+  fn cache_hits(&self) -> Option<&CacheHits> {
+    None
+  }
+}
+
+/// First legal cell in move ordering; used when a stopped search has no move yet.
+fn fallback_move(board: &Board, role: Role) -> Option<(usize, usize)> {
+  board.get_moves(role, 0, false, false).first().copied()
+}
+
+impl Engine for AIEngine {
+  fn name(&self) -> String {
+    format!("alpha-beta depth {}", self.depth)
+  }
+
+  fn new_game(&mut self, _size: usize, _rule: Rule) {
+    self.clear_hash();
+  }
+
+  fn think(&mut self, board: &Board, role: Role, limits: &SearchLimits) -> SearchResult {
+    self.time = limits.time_manager();
+    self.stop.reset();
+    let mut board = board.clone();
+    let mut result = SearchResult::from(self.make_move(&mut board, role));
+    if result.best_move.is_none() {
+      result.best_move = fallback_move(&board, role);
+    }
+    result
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.stop.clone()
+  }
+
+  fn set_hash_size(&mut self, mb: usize) {
+    AIEngine::set_hash_size(self, mb);
+  }
+
+  fn set_book(&mut self, book: Arc<OpeningBook>, seed: u64) {
+    AIEngine::set_book(self, book, seed);
+  }
+
+  fn cache_hits(&self) -> Option<&CacheHits> {
+    Some(&self.cache_hits)
+  }
+}
+
+impl Engine for MctsEngine {
+  fn name(&self) -> String {
+    format!("mcts {} playouts", self.playouts)
+  }
+
+  fn new_game(&mut self, _size: usize, _rule: Rule) {}
+
+  fn think(&mut self, board: &Board, role: Role, limits: &SearchLimits) -> SearchResult {
+    self.time = limits.time_manager();
+    self.stop.reset();
+    let mut board = board.clone();
+    let mut result = SearchResult::from(self.make_move(&mut board, role));
+    if result.best_move.is_none() {
+      result.best_move = fallback_move(&board, role);
+    }
+    result
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.stop.clone()
+  }
+}
+
+/// Plays a uniformly random legal move next to the stones on the board. A baseline
+/// opponent for engine matches.
+#[derive(Debug)]
+pub struct RandomEngine {
+  rng: StdRng,
+  stop: StopHandle,
+}
+
+impl RandomEngine {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      rng: StdRng::seed_from_u64(seed),
+      stop: StopHandle::default(),
+    }
+  }
+}
+
+impl Engine for RandomEngine {
+  fn name(&self) -> String {
+    "random".to_string()
+  }
+
+  fn new_game(&mut self, _size: usize, _rule: Rule) {}
+
+  fn think(&mut self, board: &Board, role: Role, _limits: &SearchLimits) -> SearchResult {
+    let center = board.size / 2;
+    let near_stones: Vec<(usize, usize)> = board
+      .get_moves(role, 0, false, false)
+      .into_iter()
+      .filter(|&(x, y)| (board.history.is_empty() && (x, y) == (center, center)) || has_neighbour(board, x, y))
+      .collect();
+    let best_move = near_stones.choose(&mut self.rng).copied();
+    SearchResult {
+      value: 0,
+      best_move,
+      line: best_move.into_iter().collect(),
+    }
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.stop.clone()
+  }
+}
+
+/// Whether a stone lies within two cells of (x, y).
+fn has_neighbour(board: &Board, x: usize, y: usize) -> bool {
+  (x.saturating_sub(2)..=(x + 2).min(board.size - 1))
+    .any(|nx| (y.saturating_sub(2)..=(y + 2).min(board.size - 1)).any(|ny| board.board[nx + 1][ny + 1] != 0))
+}
+
+/// Engine of an AI player together with its own settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineConfig {
+  /// Threat search plus alpha-beta (`AIEngine`)
+  AlphaBeta { depth: i32, hash_mb: usize, threads: usize },
+  /// Monte Carlo Tree Search (`MctsEngine`)
+  Mcts { playouts: usize, seed: u64 },
+  /// Random moves near the stones (`RandomEngine`)
+  Random { seed: u64 },
+  /// Gomocup brain executable and its arguments (`ExternalEngine`)
+  External { command: Vec<String> },
+}
+
+impl Default for EngineConfig {
+  fn default() -> Self {
+    EngineConfig::AlphaBeta {
+      depth: 3,
+      hash_mb: DEFAULT_TT_MB,
+      threads: 1,
+    }
+  }
+}
+
+impl EngineConfig {
+  pub fn create(&self) -> Box<dyn Engine> {
+    match self {
+      EngineConfig::AlphaBeta { depth, hash_mb, threads } => {
+        let mut engine = AIEngine::new(*depth);
+        engine.set_hash_size(*hash_mb);
+        engine.set_threads(*threads);
+        Box::new(engine)
+      }
+      EngineConfig::Mcts { playouts, seed } => Box::new(MctsEngine::new(*playouts, *seed)),
+      EngineConfig::Random { seed } => Box::new(RandomEngine::new(*seed)),
+      EngineConfig::External { command } => Box::new(ExternalEngine::new(command.clone())),
+    }
+  }
+}
+
+impl fmt::Display for EngineConfig {
+  /// The same format `from_str` accepts.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EngineConfig::AlphaBeta { depth, hash_mb, threads } => {
+        write!(f, "alpha-beta:depth={},hash={},threads={}", depth, hash_mb, threads)
+      }
+      EngineConfig::Mcts { playouts, seed } => write!(f, "mcts:playouts={},seed={}", playouts, seed),
+      EngineConfig::Random { seed } => write!(f, "random:seed={}", seed),
+      EngineConfig::External { command } => write!(f, "external:{}", command.join(" ")),
+    }
+  }
+}
+
+impl FromStr for EngineConfig {
+  type Err = String;
+
+  /// Parse `<engine>[:key=value,...]`, e.g. `alpha-beta:depth=4,threads=2`,
+  /// `mcts:playouts=5000,seed=1` or `random`. Missing keys keep their defaults.
+  /// An external brain takes its command line instead: `external:./pbrain-foo --fast`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, options) = s.split_once(':').unwrap_or((s, ""));
+    if name.trim() == "external" {
+      let command: Vec<String> = options.split_whitespace().map(str::to_string).collect();
+      if command.is_empty() {
+        return Err("external engine needs a command, e.g. external:./pbrain-foo".to_string());
+      }
+      return Ok(EngineConfig::External { command });
+    }
+    let mut config = match name.trim() {
+      "alpha-beta" | "alphabeta" => EngineConfig::default(),
+      "mcts" => EngineConfig::Mcts {
+        playouts: DEFAULT_PLAYOUTS,
+        seed: 0,
+      },
+      "random" => EngineConfig::Random { seed: 0 },
+      other => {
+        return Err(format!(
+          "unknown engine '{}', expected alpha-beta, mcts, random or external",
+          other
+        ))
+      }
+    };
+
+    for option in options.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+      let (key, value) = option
+        .split_once('=')
+        .ok_or_else(|| format!("invalid engine option '{}', expected key=value", option))?;
+      let number = |value: &str| {
+        value
+          .trim()
+          .parse::<u64>()
+          .map_err(|_| format!("invalid value '{}' for engine option '{}'", value, key))
+      };
+      match (&mut config, key.trim()) {
+        (EngineConfig::AlphaBeta { depth, .. }, "depth") => *depth = number(value)? as i32,
+        (EngineConfig::AlphaBeta { hash_mb, .. }, "hash") => *hash_mb = number(value)? as usize,
+        (EngineConfig::AlphaBeta { threads, .. }, "threads") => *threads = number(value)? as usize,
+        (EngineConfig::Mcts { playouts, .. }, "playouts") => *playouts = number(value)? as usize,
+        (EngineConfig::Mcts { seed, .. } | EngineConfig::Random { seed }, "seed") => *seed = number(value)?,
+        (_, key) => return Err(format!("engine '{}' has no option '{}'", name, key)),
+      }
+    }
+    Ok(config)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Instant;
+
+  #[test]
+  fn test_parse_engine_config() {
+    assert_eq!("alpha-beta".parse::<EngineConfig>(), Ok(EngineConfig::default()));
+    assert_eq!(
+      "alpha-beta:depth=5,threads=4".parse::<EngineConfig>(),
+      Ok(EngineConfig::AlphaBeta {
+        depth: 5,
+        hash_mb: DEFAULT_TT_MB,
+        threads: 4
+      })
+    );
+    assert_eq!(
+      "mcts:seed=9".parse::<EngineConfig>(),
+      Ok(EngineConfig::Mcts {
+        playouts: DEFAULT_PLAYOUTS,
+        seed: 9
+      })
+    );
+    assert!("mcts:depth=3".parse::<EngineConfig>().is_err());
+    assert!("alpha-beta:depth".parse::<EngineConfig>().is_err());
+    assert!("minimax".parse::<EngineConfig>().is_err());
+    assert_eq!(
+      "external:./pbrain-foo --fast".parse::<EngineConfig>(),
+      Ok(EngineConfig::External {
+        command: vec!["./pbrain-foo".to_string(), "--fast".to_string()]
+      })
+    );
+    assert!("external".parse::<EngineConfig>().is_err());
+
+    for config in [EngineConfig::default(), EngineConfig::Random { seed: 4 }] {
+      assert_eq!(config.to_string().parse::<EngineConfig>(), Ok(config));
+    }
+  }
+
+  #[test]
+  fn test_random_engine_plays_near_stones() {
+    let mut board = Board::new(15);
+    let mut engine = EngineConfig::Random { seed: 3 }.create();
+    let first = engine.think(&board, Role::Black, &SearchLimits::default());
+    assert_eq!(first.best_move, Some((7, 7)));
+
+    board.put(7, 7, Role::Black);
+    for _ in 0..10 {
+      let (x, y) = engine.think(&board, Role::White, &SearchLimits::default()).best_move.unwrap();
+      assert!(x.abs_diff(7) <= 2 && y.abs_diff(7) <= 2);
+    }
+  }
+
+  #[test]
+  fn test_think_leaves_board_untouched() {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(8, 8, Role::White);
+    let config = EngineConfig::AlphaBeta {
+      depth: 1,
+      hash_mb: 1,
+      threads: 1,
+    };
+    for config in [config, EngineConfig::Mcts { playouts: 10, seed: 1 }] {
+      let mut engine = config.create();
+      engine.new_game(15, Rule::Freestyle);
+      let result = engine.think(&board, Role::Black, &SearchLimits::default());
+      let (x, y) = result.best_move.expect("a move");
+      assert_eq!(board.history.len(), 2);
+      assert_eq!(board.board[x + 1][y + 1], 0);
+    }
+  }
+
+  #[test]
+  fn test_stopped_search_still_returns_a_move() {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(8, 8, Role::White);
+    // Deep enough that only `stop` can end the search in time
+    let mut engine = AIEngine::new(20);
+    let stop = Engine::stop_handle(&engine);
+    let worker = std::thread::spawn(move || {
+      let result = engine.think(&board, Role::Black, &SearchLimits::default());
+      result.best_move
+    });
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!worker.is_finished(), "the search ended before it was stopped");
+
+    let stopped = Instant::now();
+    stop.stop();
+    while !worker.is_finished() && stopped.elapsed() < Duration::from_millis(500) {
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(worker.is_finished(), "the search did not stop");
+    assert!(worker.join().unwrap().is_some());
+  }
+
+  #[test]
+  fn test_limits_charge_the_clock() {
+    let control = TimeControl {
+      base: Duration::from_secs(10),
+      increment: Duration::from_secs(1),
+    };
+    let mut limits = SearchLimits::new(None, Some(control));
+    assert!(limits.time_manager().budget().is_some());
+    limits.charge(Duration::from_secs(3));
+    assert_eq!(limits.remaining, Some(Duration::from_secs(8)));
+    assert_eq!(SearchLimits::default().time_manager().budget(), None);
+  }
+}
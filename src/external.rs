@@ -0,0 +1,323 @@
+use crate::board::{Board, Rule};
+use crate::engine::{Engine, SearchLimits, SearchResult, StopHandle};
+use crate::player::Role;
+use log::{info, warn};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time a brain gets to answer `START`.
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+/// Tolerance on top of the announced move time before a brain is considered hung.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+/// Move time limit when the game has none.
+const UNLIMITED_TURN_TIMEOUT: Duration = Duration::from_secs(300);
+/// Time a brain gets to exit after `END` before it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Why an external brain failed to deliver a move. Each of these loses the game.
+#[derive(Debug)]
+pub enum BrainError {
+  /// The executable could not be started.
+  Spawn(std::io::Error),
+  /// The process exited or closed its pipes.
+  Crashed,
+  /// No answer within the time limit.
+  Timeout,
+  /// An answer that is not a move, e.g. `ERROR ...`.
+  Protocol(String),
+  /// A move outside the board or on an occupied / forbidden cell.
+  IllegalMove(usize, usize),
+}
+
+impl fmt::Display for BrainError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      BrainError::Spawn(e) => write!(f, "cannot start brain: {}", e),
+      BrainError::Crashed => write!(f, "brain crashed"),
+      BrainError::Timeout => write!(f, "brain timed out"),
+      BrainError::Protocol(line) => write!(f, "unexpected answer '{}'", line),
+      BrainError::IllegalMove(x, y) => write!(f, "illegal move {},{}", x, y),
+    }
+  }
+}
+
+/// A running brain process. Its output lines arrive through a channel filled by a
+/// reader thread, so that waiting for them can time out.
+struct Brain {
+  child: Child,
+  stdin: ChildStdin,
+  lines: Receiver<String>,
+}
+
+impl Brain {
+  fn spawn(command: &[String]) -> Result<Self, BrainError> {
+    let (program, args) = command
+      .split_first()
+      .ok_or_else(|| BrainError::Spawn(std::io::Error::other("empty command")))?;
+    let mut child = Command::new(program)
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(BrainError::Spawn)?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+      for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        if sender.send(line.trim().to_string()).is_err() {
+          break;
+        }
+      }
+    });
+    Ok(Self { child, stdin, lines })
+  }
+
+  fn send(&mut self, command: &str) -> Result<(), BrainError> {
+    info!("brain <- {}", command);
+    writeln!(self.stdin, "{}", command)
+      .and_then(|_| self.stdin.flush())
+      .map_err(|_| BrainError::Crashed)
+  }
+
+  /// Next answer line before `deadline`. Log lines (`MESSAGE`, `DEBUG`, ...) are skipped.
+  fn receive(&mut self, deadline: Instant) -> Result<String, BrainError> {
+    loop {
+      let timeout = deadline.saturating_duration_since(Instant::now());
+      match self.lines.recv_timeout(timeout) {
+        Ok(line) => {
+          info!("brain -> {}", line);
+          let keyword = line.split_whitespace().next().unwrap_or("").to_ascii_uppercase();
+          if !matches!(keyword.as_str(), "" | "MESSAGE" | "DEBUG" | "SUGGEST") {
+            return Ok(line);
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => return Err(BrainError::Timeout),
+        Err(RecvTimeoutError::Disconnected) => return Err(BrainError::Crashed),
+      }
+    }
+  }
+}
+
+impl Drop for Brain {
+  fn drop(&mut self) {
+    let _ = self.send("END");
+    let deadline = Instant::now() + EXIT_TIMEOUT;
+    while Instant::now() < deadline {
+      if let Ok(Some(_)) = self.child.try_wait() {
+        return;
+      }
+      thread::sleep(Duration::from_millis(10));
+    }
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+/// Third-party Gomocup brain run as a child process and driven with the Piskvork
+/// protocol over its stdin/stdout.
+///
+/// The process is started on the first move of every game. Moves played since the
+/// brain's last answer are relayed with `TURN`; any other change of the position
+/// (undo, a new game) is sent as a whole with `BOARD`. A crash, a timeout or an
+/// illegal answer ends the process and yields no move, which forfeits the game.
+pub struct ExternalEngine {
+  command: Vec<String>,
+  brain: Option<Brain>,
+  rule: Rule,
+  /// Moves the brain knows about, in order.
+  known: Vec<(usize, usize, Role)>,
+  stop: StopHandle,
+}
+
+impl ExternalEngine {
+  /// `command` is the executable followed by its arguments.
+  pub fn new(command: Vec<String>) -> Self {
+    Self {
+      command,
+      brain: None,
+      rule: Rule::Freestyle,
+      known: Vec::new(),
+      stop: StopHandle::default(),
+    }
+  }
+
+  /// Ask the brain for a move of `role` and check that it is legal.
+  fn request_move(&mut self, board: &Board, role: Role, limits: &SearchLimits) -> Result<(usize, usize), BrainError> {
+    if self.brain.is_none() {
+      self.start(board.size)?;
+    }
+    let brain = self.brain.as_mut().expect("brain was started");
+
+    if let Some(per_move) = limits.per_move {
+      brain.send(&format!("INFO timeout_turn {}", per_move.as_millis()))?;
+    }
+    if let Some(remaining) = limits.remaining {
+      brain.send(&format!("INFO timeout_match {}", remaining.as_millis().max(1)))?;
+      brain.send(&format!("INFO time_left {}", remaining.as_millis()))?;
+    }
+
+    // Relay only the new opponent move when the brain has seen everything before it
+    let history = &board.history;
+    if history.is_empty() {
+      brain.send("BEGIN")?;
+    } else if history.len() == self.known.len() + 1 && history.starts_with(&self.known) && history[history.len() - 1].2 != role {
+      let (x, y, _) = history[history.len() - 1];
+      brain.send(&format!("TURN {},{}", x, y))?;
+    } else {
+      brain.send("BOARD")?;
+      for &(x, y, stone) in history {
+        brain.send(&format!("{},{},{}", x, y, if stone == role { 1 } else { 2 }))?;
+      }
+      brain.send("DONE")?;
+    }
+
+    let limit = match (limits.per_move, limits.remaining) {
+      (Some(a), Some(b)) => a.min(b),
+      (a, b) => a.or(b).unwrap_or(UNLIMITED_TURN_TIMEOUT),
+    };
+    let answer = brain.receive(Instant::now() + limit + TIMEOUT_GRACE)?;
+    let (x, y) = parse_move(&answer).ok_or(BrainError::Protocol(answer))?;
+
+    let mut check = board.clone();
+    if !check.put(x, y, role) {
+      return Err(BrainError::IllegalMove(x, y));
+    }
+    self.known = check.history;
+    Ok((x, y))
+  }
+
+  /// Launch the process and announce the board size and rule.
+  fn start(&mut self, size: usize) -> Result<(), BrainError> {
+    let mut brain = Brain::spawn(&self.command)?;
+    brain.send(&format!("START {}", size))?;
+    match brain.receive(Instant::now() + START_TIMEOUT)? {
+      ok if ok.eq_ignore_ascii_case("OK") => {}
+      other => return Err(BrainError::Protocol(other)),
+    }
+    let rule = match self.rule {
+      Rule::Freestyle => 0,
+      Rule::Standard => 1,
+      Rule::Renju => 4,
+    };
+    brain.send(&format!("INFO rule {}", rule))?;
+    self.known.clear();
+    self.brain = Some(brain);
+    Ok(())
+  }
+}
+
+/// Parse an `x,y` answer.
+fn parse_move(line: &str) -> Option<(usize, usize)> {
+  let (x, y) = line.split_once(',')?;
+  Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+impl Engine for ExternalEngine {
+  fn name(&self) -> String {
+    format!("external {}", self.command.join(" "))
+  }
+
+  fn new_game(&mut self, _size: usize, rule: Rule) {
+    // A fresh process per game: nothing carries over from a crash or a long game
+    self.brain = None;
+    self.rule = rule;
+    self.known.clear();
+  }
+
+  /// The Piskvork protocol has no way to interrupt a thinking brain, so a stop
+  /// request is not forwarded; the brain still answers within its time limit.
+  fn think(&mut self, board: &Board, role: Role, limits: &SearchLimits) -> SearchResult {
+    match self.request_move(board, role, limits) {
+      Ok(mv) => SearchResult {
+        value: 0,
+        best_move: Some(mv),
+        line: vec![mv],
+      },
+      Err(e) => {
+        warn!("{}: {}", self.name(), e);
+        self.brain = None;
+        SearchResult::default()
+      }
+    }
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.stop.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `sh tests/data/fake_brain.sh <mode>`: see the script for the modes.
+  fn fake_brain(mode: &str) -> ExternalEngine {
+    let script = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/fake_brain.sh");
+    let mut engine = ExternalEngine::new(vec!["sh".to_string(), script.to_string(), mode.to_string()]);
+    engine.new_game(15, Rule::Freestyle);
+    engine
+  }
+
+  #[test]
+  fn test_relays_moves() {
+    let mut engine = fake_brain("play");
+    let mut board = Board::new(15);
+    let limits = SearchLimits::default();
+
+    // BEGIN, then TURN with our move, then BOARD after the position changed behind its back
+    assert_eq!(engine.request_move(&board, Role::Black, &limits).unwrap(), (7, 7));
+    board.put(7, 7, Role::Black);
+    board.put(3, 3, Role::White);
+    assert_eq!(engine.request_move(&board, Role::Black, &limits).unwrap(), (4, 3));
+    board.undo();
+    board.put(9, 9, Role::White);
+    assert_eq!(engine.request_move(&board, Role::Black, &limits).unwrap(), (0, 0));
+  }
+
+  #[test]
+  fn test_crash() {
+    let mut engine = fake_brain("crash");
+    let board = Board::new(15);
+    let result = engine.request_move(&board, Role::Black, &SearchLimits::default());
+    assert!(matches!(result, Err(BrainError::Crashed)), "{:?}", result);
+  }
+
+  #[test]
+  fn test_timeout() {
+    let mut engine = fake_brain("hang");
+    let board = Board::new(15);
+    let limits = SearchLimits::new(Some(Duration::from_millis(100)), None);
+    let started = Instant::now();
+    let result = engine.think(&board, Role::Black, &limits);
+    assert_eq!(result.best_move, None);
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+  }
+
+  #[test]
+  fn test_illegal_move() {
+    let mut engine = fake_brain("play");
+    let mut board = Board::new(15);
+    board.put(4, 3, Role::Black);
+    board.put(3, 3, Role::White);
+    // The brain answers BOARD with 0,0; make that cell taken
+    board.put(0, 0, Role::Black);
+    board.put(1, 1, Role::White);
+    let result = engine.request_move(&board, Role::Black, &SearchLimits::default());
+    assert!(matches!(result, Err(BrainError::IllegalMove(0, 0))), "{:?}", result);
+  }
+
+  #[test]
+  fn test_missing_executable() {
+    let mut engine = ExternalEngine::new(vec!["/nonexistent/brain".to_string()]);
+    let result = engine.think(&Board::new(15), Role::Black, &SearchLimits::default());
+    assert_eq!(result.best_move, None);
+  }
+}
@@ -1,11 +1,20 @@
-use crate::ai::AIEngine;
-use crate::board::Board;
+use crate::board::{Board, Rule};
+use crate::book::OpeningBook;
+use crate::engine::{Engine, SearchLimits, SearchResult};
 use crate::game_logger::GameLogger;
+use crate::game_record::{GameRecord, DEFAULT_SAVE_FILE};
+use crate::openings::Opening;
 use crate::player::{Player, PlayerType, Role};
+use crate::psq::PsqGame;
+use crate::renju_opening::{check_offers, OpeningRule, RenjuOpeningAi, MAX_SOOSORV_ALTERNATIVES};
+use crate::sgf::SgfNode;
+use crate::swap2::{Swap2Ai, Swap2Choice};
 use crate::terminal_ui::{GameAction, TerminalUI};
+use crate::time_manager::TimeControl;
 use log::{info, warn};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameMode {
@@ -21,8 +30,12 @@ pub struct Game {
   pub player1: Player,
   pub player2: Player,
 
-  pub ai1: AIEngine,
-  pub ai2: AIEngine,
+  pub ai1: Box<dyn Engine>,
+  pub ai2: Box<dyn Engine>,
+
+  /// Time limits and game clocks of the two players.
+  pub limits1: SearchLimits,
+  pub limits2: SearchLimits,
 
   // Current position "cursor" for human move
   pub cursor_x: usize,
@@ -35,13 +48,42 @@ pub struct Game {
 
   pub current_role: Role,
   pub round: i32,
+
+  /// Player who lost by not producing a legal move (e.g. a crashed external brain).
+  pub forfeited: Option<Role>,
+
+  /// Open with Swap2: player1 proposes, player2 picks a colour or adds two stones.
+  pub swap2: bool,
+  /// The Swap2 opening has been played (always so in a resumed game).
+  pub swap2_done: bool,
+
+  /// Renju opening rule played before the game proper; set with `set_opening_rule`.
+  pub opening_rule: Option<OpeningRule>,
+
+  /// File the save key (S) writes the game to.
+  pub save_path: String,
+
+  /// Time spent on each move of the board history, for PSQ export.
+  pub move_times: Vec<Duration>,
+  turn_started: Instant,
+
+  /// Engine analysis of each move of the board history, for SGF export.
+  pub analysis: Vec<Option<SearchResult>>,
+
+  /// Write the game as a Piskvork `.psq` file here when it ends.
+  pub psq_path: Option<String>,
+  /// Write the game as an SGF file here when it ends.
+  pub sgf_path: Option<String>,
 }
 
 impl Game {
-  pub fn new(size: usize, mode: GameMode, p1: Player, p2: Player) -> Self {
-    let board = Board::new(size);
-    let ai1 = AIEngine::new(p1.depth);
-    let ai2 = AIEngine::new(p2.depth);
+  pub fn new(size: usize, rule: Rule, mode: GameMode, p1: Player, p2: Player) -> Self {
+    let board = Board::with_rule(size, rule);
+    let mut ai1 = p1.engine.create();
+    let mut ai2 = p2.engine.create();
+    ai1.new_game(size, rule);
+    ai2.new_game(size, rule);
+    let current_role = p1.role;
 
     let ui = TerminalUI::new();
 
@@ -52,6 +94,8 @@ impl Game {
       player2: p2,
       ai1,
       ai2,
+      limits1: SearchLimits::default(),
+      limits2: SearchLimits::default(),
       cursor_x: size / 2,
       cursor_y: size / 2,
       last_stone_x: None,
@@ -59,17 +103,131 @@ impl Game {
 
       ui,
 
-      current_role: p1.role,
+      current_role,
       round: 1,
+      forfeited: None,
+      swap2: false,
+      swap2_done: false,
+      opening_rule: None,
+      save_path: DEFAULT_SAVE_FILE.to_string(),
+      move_times: Vec::new(),
+      turn_started: Instant::now(),
+      analysis: Vec::new(),
+      psq_path: None,
+      sgf_path: None,
+    }
+  }
+
+  /// A game between a `black` and a `white` player, the AIs playing random moves.
+  #[cfg(test)]
+  pub fn for_test(size: usize, rule: Rule, black: PlayerType, white: PlayerType) -> Self {
+    let mode = match (black, white) {
+      (PlayerType::AI, PlayerType::AI) => GameMode::AIvAI,
+      (PlayerType::Human, PlayerType::Human) => GameMode::HumanvHuman,
+      _ => GameMode::AIvHuman,
+    };
+    let player = |player_type, role| Player {
+      player_type,
+      role,
+      engine: crate::engine::EngineConfig::Random { seed: 0 },
+    };
+    Self::new(size, rule, mode, player(black, Role::Black), player(white, Role::White))
+  }
+
+  /// Start from `opening` instead of the empty board. Returns `false` if it does not fit.
+  pub fn set_opening(&mut self, opening: &Opening) -> bool {
+    let mut board = Board::with_rule(self.board.size, self.board.rule);
+    if !opening.place(&mut board) {
+      return false;
+    }
+    self.set_position(board);
+    true
+  }
+
+  /// Continue from `board`, with the side after its last move to play.
+  pub fn set_position(&mut self, board: Board) {
+    self.board = board;
+    if let Some(&(x, y, role)) = self.board.history.last() {
+      self.last_stone_x = Some(x);
+      self.last_stone_y = Some(y);
+      self.current_role = role.opponent();
+      self.round = self.board.history.len() as i32 + 1;
     }
   }
 
+  /// Open with a Renju opening rule; the board then checks where the first moves go.
+  pub fn set_opening_rule(&mut self, rule: OpeningRule) {
+    self.opening_rule = Some(rule);
+    self.board.opening_rule = Some(rule);
+  }
+
+  /// The opening rule, unless its five moves are already on the board (a resumed game).
+  fn pending_opening_rule(&self) -> Option<OpeningRule> {
+    self.opening_rule.filter(|_| self.board.history.len() < 5)
+  }
+
+  /// Let the AI players play from `book` before searching, choosing among its moves
+  /// with generators seeded by `seed`.
+  pub fn set_book(&mut self, book: Option<Arc<OpeningBook>>, seed: u64) {
+    if let Some(book) = book {
+      self.ai1.set_book(Arc::clone(&book), seed);
+      self.ai2.set_book(book, seed);
+    }
+  }
+
+  /// Give both AI players a per-move limit and/or a game clock.
+  pub fn set_time_limits(&mut self, per_move: Option<Duration>, control: Option<TimeControl>) {
+    self.limits1 = SearchLimits::new(per_move, control);
+    self.limits2 = SearchLimits::new(per_move, control);
+  }
+
+  /// Let the engine of the player to move think, charging its clock. If `interactive`,
+  /// the user can press Enter / Space to make it play its best move so far.
+  fn think(&mut self, interactive: bool) -> SearchResult {
+    let (engine, limits) = if self.current_role == self.player1.role {
+      (&mut self.ai1, &mut self.limits1)
+    } else {
+      (&mut self.ai2, &mut self.limits2)
+    };
+    let (board, role, ui) = (&self.board, self.current_role, &mut self.ui);
+    let started = Instant::now();
+    let result = if interactive {
+      let stop = engine.stop_handle();
+      let limits = &*limits;
+      thread::scope(|s| {
+        let worker = s.spawn(move || engine.think(board, role, limits));
+        while !worker.is_finished() {
+          if ui.poll_move_now(Duration::from_millis(50)) {
+            stop.stop();
+          }
+        }
+        worker.join().expect("engine thread panicked")
+      })
+    } else {
+      engine.think(board, role, limits)
+    };
+    limits.charge(started.elapsed());
+    result
+  }
+
   pub fn run(&mut self) {
     // Initial screen setup
     self.ui.init_screen().unwrap();
+    if self.swap2 && !self.swap2_done && !self.swap2_opening(true) {
+      self.ui.restore_terminal().unwrap();
+      return;
+    }
+    if let Some(rule) = self.pending_opening_rule() {
+      if !self.renju_opening(rule, true) {
+        self.ui.restore_terminal().unwrap();
+        return;
+      }
+    }
 
+    self.start_clock();
     let mut paused = false;
-    let mut game_is_over = false;
+    // A resumed game may already be over, e.g. by forfeit
+    let mut game_is_over = self.outcome().is_some();
 
     loop {
       // Determine the current player based on the current role
@@ -100,6 +258,7 @@ impl Game {
           GameAction::TogglePause => {
             paused = false; // unpause
           }
+          GameAction::Save => self.save(),
           GameAction::None => {
             // do nothing
           }
@@ -141,6 +300,7 @@ impl Game {
               if !self.board.undo() {
                 self.ui.show_message("No moves to undo.");
               }
+              self.clock_moves();
               continue;
             }
 
@@ -150,6 +310,12 @@ impl Game {
               continue;
             }
 
+            // Save the game (S)
+            GameAction::Save => {
+              self.save();
+              continue;
+            }
+
             // Move cursor left
             GameAction::MoveLeft => {
               if self.cursor_x > 0 {
@@ -193,7 +359,9 @@ impl Game {
       } // end match
 
       // After making a move, check if the game is over
-      if self.board.is_game_over() {
+      if self.forfeited.is_some() {
+        game_is_over = true;
+      } else if self.board.is_game_over() {
         let w = self.board.get_winner();
         self.print_winner(w);
         game_is_over = true;
@@ -202,40 +370,435 @@ impl Game {
 
     // At the end — restore the terminal to normal state
     self.ui.restore_terminal().unwrap();
+    self.export_records();
   }
 
   fn turn(&mut self, player_type: PlayerType) {
     match player_type {
       PlayerType::AI => self.ai_turn(),
-      PlayerType::Human => self.human_turn(),
+      PlayerType::Human => {
+        // A rejected human move (Renju forbidden point) keeps the turn
+        if !self.human_turn() {
+          return;
+        }
+      }
     }
 
     // Switch turn
     self.current_role = self.current_role.opponent();
     self.round += 1;
+    self.clock_moves();
+  }
+
+  /// Start timing moves; stones already on the board count as instant.
+  fn start_clock(&mut self) {
+    self.move_times.resize(self.board.history.len(), Duration::ZERO);
+    self.turn_started = Instant::now();
   }
 
-  fn human_turn(&mut self) {
-    self.board.put(self.cursor_x, self.cursor_y, self.current_role);
+  /// Record the time spent on the moves played since the last call, and forget the
+  /// times of undone moves.
+  fn clock_moves(&mut self) {
+    let elapsed = self.turn_started.elapsed();
+    self.move_times.resize(self.board.history.len(), elapsed);
+    self.turn_started = Instant::now();
+  }
+
+  /// Keep the search behind the move just played, for the SGF comments.
+  fn record_analysis(&mut self, result: SearchResult) {
+    self.analysis.resize(self.board.history.len() - 1, None);
+    self.analysis.push(Some(result));
+  }
+
+  /// Winner of the game: `Some(None)` for a draw, `None` while it goes on.
+  pub fn outcome(&self) -> Option<Option<Role>> {
+    if let Some(loser) = self.forfeited {
+      return Some(Some(loser.opponent()));
+    }
+    match self.board.get_winner() {
+      w if w > 0 => Some(Some(Role::White)),
+      w if w < 0 => Some(Some(Role::Black)),
+      _ => self.board.is_game_over().then_some(None),
+    }
+  }
+
+  /// Write the game to `psq_path` and `sgf_path`, if set.
+  fn export_records(&self) {
+    let report = |path: &str, written: std::io::Result<()>| match written {
+      Ok(()) => println!("Game written to {}", path),
+      Err(e) => eprintln!("Cannot write {}: {}", path, e),
+    };
+    if let Some(path) = &self.psq_path {
+      report(path, PsqGame::of(self).save(path));
+    }
+    if let Some(path) = &self.sgf_path {
+      let written = SgfNode::of(self)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+        .and_then(|sgf| sgf.save(path));
+      report(path, written);
+    }
+  }
+
+  fn human_turn(&mut self) -> bool {
+    if !self.board.put(self.cursor_x, self.cursor_y, self.current_role) {
+      self.ui.show_message("Forbidden move for Black (Renju)");
+      return false;
+    }
     self.last_stone_x = Some(self.cursor_x);
     self.last_stone_y = Some(self.cursor_y);
+    true
   }
 
   fn ai_turn(&mut self) {
-    let (value, move_xy, _path) = if self.current_role == self.player1.role {
-      self.ai1.make_move(&mut self.board, self.current_role)
+    self.ui.show_message("AI is thinking... (Enter / Space: move now)");
+    let result = self.think(true);
+    let msg = format!("AI ({:?}) chose move with score={}", self.current_role, result.value);
+    self.ui.show_message(&msg);
+    info!("AI moved to {:?}", result.best_move);
+    if let Some((x, y)) = result.best_move {
+      if self.board.put(x, y, self.current_role) {
+        self.last_stone_x = Some(x);
+        self.last_stone_y = Some(y);
+        self.record_analysis(result);
+        return;
+      }
+    }
+    self.forfeited = Some(self.current_role);
+    let msg = format!("{:?} forfeits: its engine gave no legal move", self.current_role);
+    self.ui.show_message(&msg);
+  }
+
+  /// Swap2 opening. Player1 places three stones (black, white, black); player2 takes
+  /// Black, takes White, or adds a white and a black stone and lets player1 choose.
+  /// AI players decide with `Swap2Ai`, humans are prompted. Sets the roles of both
+  /// players; White moves next. Returns `false` if a human quits.
+  fn swap2_opening(&mut self, tui: bool) -> bool {
+    let mut ai = Swap2Ai::new();
+    let (first, second) = (self.player1.player_type, self.player2.player_type);
+
+    // 1) Three stones from the first player
+    if first == PlayerType::AI {
+      for (&(x, y), role) in ai
+        .propose(self.board.size, self.board.rule)
+        .iter()
+        .zip([Role::Black, Role::White, Role::Black])
+      {
+        self.place_stone(x, y, role);
+      }
+      self.announce(tui, "Swap2: first player placed three stones");
     } else {
-      self.ai2.make_move(&mut self.board, self.current_role)
+      for (n, role) in [Role::Black, Role::White, Role::Black].into_iter().enumerate() {
+        let prompt = format!("Swap2, first player: place {:?} stone {} of 3", role, n + 1);
+        if !self.place_human_stone(role, &prompt) {
+          return false;
+        }
+      }
+    }
+
+    // 2) The second player's choice
+    let choice = if second == PlayerType::AI {
+      ai.second_choice(&self.board)
+    } else {
+      self.redraw();
+      self
+        .ui
+        .show_message("Swap2, second player: [b] play Black, [w] play White, [t] place two more stones");
+      match self.ui.read_choice(&['b', 'w', 't']) {
+        None => return false,
+        Some('b') => Swap2Choice::TakeBlack,
+        Some('w') => Swap2Choice::TakeWhite,
+        Some(_) => {
+          for role in [Role::White, Role::Black] {
+            let prompt = format!("Swap2, second player: place a {:?} stone", role);
+            if !self.place_human_stone(role, &prompt) {
+              return false;
+            }
+          }
+          let (&(wx, wy, _), &(bx, by, _)) = (&self.board.history[3], &self.board.history[4]);
+          Swap2Choice::PlaceTwo((wx, wy), (bx, by))
+        }
+      }
+    };
+
+    // 3) Colours
+    let first_role = match choice {
+      Swap2Choice::TakeBlack => Role::White,
+      Swap2Choice::TakeWhite => Role::Black,
+      Swap2Choice::PlaceTwo(white, black) => {
+        if second == PlayerType::AI {
+          self.place_stone(white.0, white.1, Role::White);
+          self.place_stone(black.0, black.1, Role::Black);
+        }
+        if first == PlayerType::AI {
+          ai.choose_colour(&self.board)
+        } else {
+          self.redraw();
+          self.ui.show_message("Swap2, first player: [b] play Black, [w] play White");
+          match self.ui.read_choice(&['b', 'w']) {
+            None => return false,
+            Some('b') => Role::Black,
+            Some(_) => Role::White,
+          }
+        }
+      }
+    };
+    self.player1.role = first_role;
+    self.player2.role = first_role.opponent();
+    self.current_role = Role::White;
+    self.round = self.board.history.len() as i32 + 1;
+    self.swap2_done = true;
+    let msg = format!(
+      "Swap2 done: first player plays {:?}, second player {:?}",
+      first_role,
+      first_role.opponent()
+    );
+    self.announce(tui, &msg);
+    true
+  }
+
+  /// Renju opening under `rule`. Player1 starts as the tentative Black. Moves 1-4 are
+  /// played (RIF and Soosõrv: moves 1-3 all by player1), the holder of the next colour
+  /// may swap where the rule allows, then Black offers fifth moves and White picks one.
+  /// AI players decide with `RenjuOpeningAi`, humans are prompted. Sets the roles of
+  /// both players; White moves next. Returns `false` if a human quits.
+  fn renju_opening(&mut self, rule: OpeningRule, tui: bool) -> bool {
+    let mut ai = RenjuOpeningAi::new();
+    let is_ai = [
+      self.player1.player_type == PlayerType::AI,
+      self.player2.player_type == PlayerType::AI,
+    ];
+    // Whether player1 currently holds Black
+    let mut first_black = true;
+    let holder = |first_black: bool, role: Role| if (role == Role::Black) == first_black { 0 } else { 1 };
+
+    if rule.first_player_places_three() && is_ai[0] && self.board.history.len() < 3 {
+      // A resumed game keeps the moves it already has
+      for (&(x, y), role) in ai
+        .propose(self.board.size, self.board.rule)
+        .iter()
+        .zip([Role::Black, Role::White, Role::Black])
+        .skip(self.board.history.len())
+      {
+        self.place_stone(x, y, role);
+      }
+    }
+    let mut alternatives = rule.fifth_alternatives();
+    for number in 1..=4 {
+      let role = if number % 2 == 1 { Role::Black } else { Role::White };
+      let player = if rule.first_player_places_three() && number <= 3 {
+        0
+      } else {
+        holder(first_black, role)
+      };
+      if self.board.history.len() < number {
+        let placed = if is_ai[player] {
+          let mv = if rule.swap_after(number) {
+            ai.balanced_move(&self.board, role)
+          } else {
+            ai.free_move(&self.board, role)
+          };
+          mv.is_some_and(|(x, y)| {
+            self.place_stone(x, y, role);
+            self.board.history.len() == number
+          })
+        } else {
+          let prompt = format!(
+            "{:?} opening, player {}: place move {} ({:?})",
+            rule,
+            player + 1,
+            number,
+            role
+          );
+          self.place_human_stone(role, &prompt)
+        };
+        if !placed {
+          return false;
+        }
+      }
+
+      if number == 4 && alternatives.is_none() {
+        // Soosõrv-8: White declares the number of fifth-move alternatives
+        let count = if is_ai[holder(first_black, Role::White)] {
+          ai.declare_alternatives(&self.board)
+        } else {
+          self.redraw();
+          self.ui.show_message(&format!(
+            "White: declare 1-{} fifth-move alternatives",
+            MAX_SOOSORV_ALTERNATIVES
+          ));
+          let keys: Vec<char> = (1..=MAX_SOOSORV_ALTERNATIVES as u32)
+            .filter_map(|d| char::from_digit(d, 10))
+            .collect();
+          match self.ui.read_choice(&keys) {
+            None => return false,
+            Some(c) => c.to_digit(10).unwrap_or(1) as usize,
+          }
+        };
+        self.announce(tui, &format!("White declares {} fifth-move alternatives", count));
+        alternatives = Some(count);
+      }
+
+      if rule.swap_after(number) {
+        let to_move = role.opponent();
+        let decider = holder(first_black, to_move);
+        let swap = if is_ai[decider] {
+          ai.wants_swap(&self.board, to_move)
+        } else {
+          self.redraw();
+          let msg = format!("Player {} ({:?}): [s] swap colours, [k] keep", decider + 1, to_move);
+          self.ui.show_message(&msg);
+          match self.ui.read_choice(&['s', 'k']) {
+            None => return false,
+            Some(c) => c == 's',
+          }
+        };
+        if swap {
+          first_black = !first_black;
+          self.announce(
+            tui,
+            &format!("Player {} swaps and now plays {:?}", decider + 1, to_move.opponent()),
+          );
+        }
+      }
+    }
+
+    // Fifth move: Black offers alternatives, White picks one
+    let count = alternatives.unwrap_or(1);
+    let offers = if is_ai[holder(first_black, Role::Black)] {
+      ai.offer_fifth(&self.board, count)
+    } else {
+      let mut offers: Vec<(usize, usize)> = Vec::new();
+      while offers.len() < count {
+        let prompt = format!(
+          "Black: offer fifth move {} of {} (offered: {:?})",
+          offers.len() + 1,
+          count,
+          offers
+        );
+        if !self.place_human_stone(Role::Black, &prompt) {
+          return false;
+        }
+        let (x, y, _) = self.board.history[self.board.history.len() - 1];
+        self.board.undo();
+        let mut candidate = offers.clone();
+        candidate.push((x, y));
+        match check_offers(&self.board, &candidate) {
+          Ok(()) => offers = candidate,
+          Err(e) => self.announce(tui, &e),
+        }
+      }
+      offers
+    };
+    if offers.is_empty() {
+      return false;
+    }
+    let (x, y) = if is_ai[holder(first_black, Role::White)] {
+      ai.choose_fifth(&self.board, &offers)
+    } else {
+      self.redraw();
+      let list: Vec<String> = offers
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| format!("[{}] {},{}", (i + 1) % 10, x, y))
+        .collect();
+      self
+        .ui
+        .show_message(&format!("White: choose Black's fifth move {}", list.join(" ")));
+      let keys: Vec<char> = (1..=offers.len() as u32)
+        .filter_map(|d| char::from_digit(d % 10, 10))
+        .collect();
+      match self.ui.read_choice(&keys) {
+        None => return false,
+        Some(c) => {
+          let digit = c.to_digit(10).unwrap_or(1) as usize;
+          offers[(digit + 9) % 10]
+        }
+      }
+    };
+    self.place_stone(x, y, Role::Black);
+
+    let first_role = if first_black { Role::Black } else { Role::White };
+    self.player1.role = first_role;
+    self.player2.role = first_role.opponent();
+    self.current_role = Role::White;
+    self.round = self.board.history.len() as i32 + 1;
+    let msg = format!(
+      "{:?} opening done: first player plays {:?}, second player {:?}",
+      rule,
+      first_role,
+      first_role.opponent()
+    );
+    self.announce(tui, &msg);
+    true
+  }
+
+  /// Write the game to `save_path` and report the outcome on the TUI. A game in the
+  /// middle of its Swap2 opening is not saved: the record cannot resume it there.
+  fn save(&mut self) {
+    if self.swap2 && !self.swap2_done {
+      self.ui.show_message("Cannot save during the Swap2 opening");
+      return;
+    }
+    let msg = match GameRecord::of(self).save(&self.save_path) {
+      Ok(()) => format!("Game saved to {}", self.save_path),
+      Err(e) => format!("Cannot save to {}: {}", self.save_path, e),
     };
-    let msg = format!("AI ({:?}) chose move with score={}", self.current_role, value);
     self.ui.show_message(&msg);
-    info!("AI moved to {:?}", move_xy);
-    if let Some((x, y)) = move_xy {
-      self.board.put(x, y, self.current_role);
+  }
+
+  fn place_stone(&mut self, x: usize, y: usize, role: Role) {
+    if self.board.put(x, y, role) {
       self.last_stone_x = Some(x);
       self.last_stone_y = Some(y);
+    }
+  }
+
+  /// Let a human place a stone of `role` with the cursor. Returns `false` on quit.
+  fn place_human_stone(&mut self, role: Role, prompt: &str) -> bool {
+    loop {
+      self.redraw();
+      self.ui.show_message(prompt);
+      match self.ui.read_input() {
+        GameAction::Quit => return false,
+        GameAction::MoveLeft => self.cursor_x = self.cursor_x.saturating_sub(1),
+        GameAction::MoveRight => self.cursor_x = (self.cursor_x + 1).min(self.board.size - 1),
+        GameAction::MoveUp => self.cursor_y = self.cursor_y.saturating_sub(1),
+        GameAction::MoveDown => self.cursor_y = (self.cursor_y + 1).min(self.board.size - 1),
+        GameAction::PlaceStone if self.board.put(self.cursor_x, self.cursor_y, role) => {
+          self.last_stone_x = Some(self.cursor_x);
+          self.last_stone_y = Some(self.cursor_y);
+          return true;
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn redraw(&mut self) {
+    self.ui.draw_board(
+      &self.board,
+      self.cursor_x,
+      self.cursor_y,
+      self.last_stone_x,
+      self.last_stone_y,
+      PlayerType::Human,
+    );
+  }
+
+  /// Show a message on the TUI, or print it in headless mode.
+  fn announce(&mut self, tui: bool, msg: &str) {
+    if tui {
+      self.ui.show_message(msg);
     } else {
-      self.ui.show_message("AI chose no move");
+      println!("{}", msg);
+    }
+  }
+
+  fn engine_name(&self, role: Role) -> String {
+    if self.player1.role == role {
+      self.ai1.name()
+    } else {
+      self.ai2.name()
     }
   }
 
@@ -255,8 +818,19 @@ impl Game {
     println!("Starting AI vs AI game with logging...");
     println!("Log file: gomoku_game.log");
     println!("Board size: {}", self.board.size);
-    println!("AI depth: {}", self.player1.depth);
+    println!("Black engine: {}", self.engine_name(Role::Black));
+    println!("White engine: {}", self.engine_name(Role::White));
+    if let Some(budget) = self.limits1.time_manager().budget() {
+      println!("AI time budget: {:?} per move", budget);
+    }
     println!();
+    if self.swap2 && !self.swap2_done {
+      self.swap2_opening(false);
+    }
+    if let Some(rule) = self.pending_opening_rule() {
+      self.renju_opening(rule, false);
+    }
+    self.start_clock();
 
     loop {
       let player = if self.current_role == self.player1.role {
@@ -273,12 +847,19 @@ impl Game {
       match player.player_type {
         PlayerType::AI => {
           self.ai_turn_with_logging(&mut logger);
+          self.clock_moves();
         }
         PlayerType::Human => {
           panic!("Log mode only supports AI vs AI");
         }
       }
 
+      if let Some(loser) = self.forfeited {
+        println!("\nGame Over!");
+        println!("Result: {:?} forfeits, {:?} WINS!", loser, loser.opponent());
+        break;
+      }
+
       // Check game over
       if self.board.is_game_over() {
         let winner = self.board.get_winner();
@@ -301,27 +882,28 @@ impl Game {
       self.current_role = self.current_role.opponent();
       self.round += 1;
 
-      // Small delay for readability
+      // Small delay for readability, not charged to the next move
       thread::sleep(Duration::from_millis(100));
+      self.turn_started = Instant::now();
     }
+    self.export_records();
   }
 
   fn ai_turn_with_logging(&mut self, logger: &mut GameLogger) {
-    let ai = if self.current_role == self.player1.role {
-      &mut self.ai1
-    } else {
-      &mut self.ai2
-    };
-
-    // Get candidates before make_move
+    // Get candidates before thinking
     let candidates = self.board.get_valuable_moves(self.current_role, 0, false, false);
     logger.log_candidates(&candidates, self.current_role).ok();
 
-    // IMPORTANT: Use make_move which includes threat detection logic
-    let (final_value, final_move, _final_path) = ai.make_move(&mut self.board, self.current_role);
+    let result = self.think(false);
+    let (final_value, final_move) = (result.value, result.best_move);
+    let ai = if self.current_role == self.player1.role {
+      &self.ai1
+    } else {
+      &self.ai2
+    };
 
     // Determine reason based on value
-    let reason = if final_value >= 10_000_000 {
+    let reason = if final_value >= crate::ai::WIN_SCORE - 1 {
       "Winning move (FIVE)"
     } else if final_value >= crate::ai::HIGH_VALUE {
       "VCT WIN"
@@ -336,19 +918,45 @@ impl Game {
     if let Some((x, y)) = final_move {
       logger.log_patterns(x, y, self.current_role, &self.board).ok();
       logger.log_final_decision(final_move, final_value, reason).ok();
-      logger
-        .log_cache_stats(ai.cache_hits.hit, ai.cache_hits.total, ai.cache_hits.search)
-        .ok();
+      if let Some(hits) = ai.cache_hits() {
+        logger.log_cache_stats(hits.hit, hits.total, hits.search).ok();
+      }
 
       println!("  -> Move: ({}, {}) Score: {} [{}]", x, y, final_value, reason);
 
       // Place the stone on the board
-      self.board.put(x, y, self.current_role);
-      self.last_stone_x = Some(x);
-      self.last_stone_y = Some(y);
+      if self.board.put(x, y, self.current_role) {
+        self.last_stone_x = Some(x);
+        self.last_stone_y = Some(y);
+        self.record_analysis(result);
+        return;
+      }
+      println!("  -> Illegal move");
     } else {
       logger.log_final_decision(None, final_value, "No valid moves found").ok();
       println!("  -> No valid moves");
     }
+    self.forfeited = Some(self.current_role);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_renju_opening_keeps_resumed_moves() {
+    let mut game = Game::for_test(15, Rule::Renju, PlayerType::AI, PlayerType::AI);
+    game.set_opening_rule(OpeningRule::Rif);
+    assert!(game.board.put(7, 7, Role::Black));
+    assert!(game.board.put(6, 8, Role::White));
+
+    assert!(game.renju_opening(OpeningRule::Rif, false));
+    let history = &game.board.history;
+    assert_eq!(history.len(), 5);
+    assert_eq!(history[..2], [(7, 7, Role::Black), (6, 8, Role::White)]);
+    for (i, &(_, _, role)) in history.iter().enumerate() {
+      assert_eq!(role, if i % 2 == 0 { Role::Black } else { Role::White }, "move {}", i + 1);
+    }
   }
 }
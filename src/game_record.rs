@@ -0,0 +1,494 @@
+use crate::bitboard::MAX_SIZE;
+use crate::board::Rule;
+use crate::engine::{EngineConfig, SearchLimits, SearchResult};
+use crate::game::{Game, GameMode};
+use crate::player::{Player, PlayerType, Role};
+use crate::renju_opening::OpeningRule;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// File a game is saved to when none was loaded.
+pub const DEFAULT_SAVE_FILE: &str = "gomoku_game.sav";
+
+/// Everything needed to resume a game: board, rules, both players, their clocks and
+/// the moves with their times and engine analysis.
+///
+/// Saved as plain text, one `key value` pair per line. `opening-rule`, `swap2`,
+/// `limits1`, `limits2` and `forfeited` are optional, and so are a move's `time` and
+/// its engine's `value` and `line`; times are in milliseconds:
+///
+/// ```text
+/// size 15
+/// rule renju
+/// mode ai-human
+/// opening-rule rif
+/// player1 human black alpha-beta:depth=4,hash=64,threads=1
+/// player2 ai white mcts:playouts=5000,seed=0
+/// limits1 per-move=2000 remaining=281500 increment=5000
+/// limits2 per-move=2000 remaining=297250 increment=5000
+/// to-move black
+/// move 7,7 black time=0
+/// move 7,6 white time=1850 value=-120 line=7,6;8,8;6,6
+/// ```
+///
+/// A game is only saved once its Swap2 opening is over, so `swap2 true` marks a game
+/// that opened with Swap2 and is resumed after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+  pub size: usize,
+  pub rule: Rule,
+  pub mode: GameMode,
+  pub opening_rule: Option<OpeningRule>,
+  pub player1: Player,
+  pub player2: Player,
+  /// Time limits and remaining clock of player1 and player2.
+  pub limits1: SearchLimits,
+  pub limits2: SearchLimits,
+  pub to_move: Role,
+  /// Player who lost by forfeit, if the game ended that way.
+  pub forfeited: Option<Role>,
+  /// The game opened with Swap2.
+  pub swap2: bool,
+  pub moves: Vec<(usize, usize, Role)>,
+  /// Time spent on the first moves, as in `Game::move_times`.
+  pub move_times: Vec<Duration>,
+  /// Engine analysis of the first moves, as in `Game::analysis`.
+  pub analysis: Vec<Option<SearchResult>>,
+}
+
+impl GameRecord {
+  /// Snapshot of `game`.
+  pub fn of(game: &Game) -> Self {
+    Self {
+      size: game.board.size,
+      rule: game.board.rule,
+      mode: game.mode,
+      opening_rule: game.opening_rule,
+      player1: game.player1.clone(),
+      player2: game.player2.clone(),
+      limits1: game.limits1,
+      limits2: game.limits2,
+      to_move: game.current_role,
+      forfeited: game.forfeited,
+      swap2: game.swap2,
+      moves: game.board.history.clone(),
+      move_times: game.move_times.clone(),
+      analysis: game.analysis.clone(),
+    }
+  }
+
+  /// A new game in the saved position. Fails if a move cannot be replayed.
+  pub fn into_game(self) -> Result<Game, String> {
+    let mut game = Game::new(self.size, self.rule, self.mode, self.player1, self.player2);
+    if let Some(rule) = self.opening_rule {
+      game.set_opening_rule(rule);
+    }
+    for &(x, y, role) in &self.moves {
+      if !game.board.put(x, y, role) {
+        return Err(format!("illegal move {},{} for {}", x, y, role_name(role)));
+      }
+      game.last_stone_x = Some(x);
+      game.last_stone_y = Some(y);
+    }
+    game.current_role = self.to_move;
+    game.round = self.moves.len() as i32 + 1;
+    game.limits1 = self.limits1;
+    game.limits2 = self.limits2;
+    game.forfeited = self.forfeited;
+    game.swap2 = self.swap2;
+    game.swap2_done = self.swap2;
+    game.move_times = self.move_times;
+    game.analysis = self.analysis;
+    Ok(game)
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = format!(
+      "size {}\nrule {}\nmode {}\n",
+      self.size,
+      rule_name(self.rule),
+      mode_name(self.mode)
+    );
+    if let Some(rule) = self.opening_rule {
+      text += &format!("opening-rule {}\n", opening_rule_name(rule));
+    }
+    for (key, player) in [("player1", &self.player1), ("player2", &self.player2)] {
+      let player_type = match player.player_type {
+        PlayerType::Human => "human",
+        PlayerType::AI => "ai",
+      };
+      text += &format!("{} {} {} {}\n", key, player_type, role_name(player.role), player.engine);
+    }
+    for (key, limits) in [("limits1", &self.limits1), ("limits2", &self.limits2)] {
+      if *limits != SearchLimits::default() {
+        text += &format!("{} {}\n", key, limits_text(limits));
+      }
+    }
+    if self.swap2 {
+      text += "swap2 true\n";
+    }
+    text += &format!("to-move {}\n", role_name(self.to_move));
+    if let Some(loser) = self.forfeited {
+      text += &format!("forfeited {}\n", role_name(loser));
+    }
+    for (index, &(x, y, role)) in self.moves.iter().enumerate() {
+      text += &format!("move {},{} {}", x, y, role_name(role));
+      if let Some(time) = self.move_times.get(index) {
+        text += &format!(" time={}", time.as_millis());
+      }
+      if let Some(Some(result)) = self.analysis.get(index) {
+        let line: Vec<String> = result.line.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        text += &format!(" value={} line={}", result.value, line.join(";"));
+      }
+      text += "\n";
+    }
+    text
+  }
+
+  /// Parse the format written by `to_text`. Blank lines and lines starting with `#`
+  /// are skipped.
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let (mut size, mut rule, mut mode, mut to_move) = (None, None, None, None);
+    let (mut player1, mut player2) = (None, None);
+    let (mut opening_rule, mut forfeited, mut swap2) = (None, None, false);
+    let (mut limits1, mut limits2) = (SearchLimits::default(), SearchLimits::default());
+    let (mut moves, mut move_times, mut analysis) = (Vec::new(), Vec::new(), Vec::new());
+    for (number, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let error = |what: &str| format!("line {}: {}", number + 1, what);
+      let (key, value) = line.split_once(' ').ok_or_else(|| error("expected `key value`"))?;
+      let value = value.trim();
+      match key {
+        "size" => {
+          let parsed = value.parse::<usize>().ok().filter(|&size| size <= MAX_SIZE);
+          size = Some(parsed.ok_or_else(|| error("invalid size"))?)
+        }
+        "rule" => rule = Some(parse_rule(value).ok_or_else(|| error("unknown rule"))?),
+        "mode" => mode = Some(parse_mode(value).ok_or_else(|| error("unknown mode"))?),
+        "opening-rule" => opening_rule = Some(parse_opening_rule(value).ok_or_else(|| error("unknown opening rule"))?),
+        "to-move" => to_move = Some(parse_role(value).ok_or_else(|| error("unknown colour"))?),
+        "forfeited" => forfeited = Some(parse_role(value).ok_or_else(|| error("unknown colour"))?),
+        "swap2" => swap2 = value.parse::<bool>().map_err(|_| error("expected true or false"))?,
+        "limits1" => limits1 = parse_limits(value).map_err(|e| error(&e))?,
+        "limits2" => limits2 = parse_limits(value).map_err(|e| error(&e))?,
+        "player1" | "player2" => {
+          let mut parts = value.splitn(3, ' ');
+          let player_type = match parts.next() {
+            Some("human") => PlayerType::Human,
+            Some("ai") => PlayerType::AI,
+            _ => return Err(error("expected human or ai")),
+          };
+          let role = parts.next().and_then(parse_role).ok_or_else(|| error("unknown colour"))?;
+          let engine = match parts.next() {
+            Some(engine) => engine.parse::<EngineConfig>().map_err(|e| error(&e))?,
+            None => EngineConfig::default(),
+          };
+          let player = Some(Player {
+            player_type,
+            role,
+            engine,
+          });
+          if key == "player1" {
+            player1 = player;
+          } else {
+            player2 = player;
+          }
+        }
+        "move" => {
+          let mut parts = value.split_whitespace();
+          let (x, y) = parts
+            .next()
+            .and_then(parse_point)
+            .ok_or_else(|| error("expected `move x,y colour`"))?;
+          let role = parts.next().and_then(parse_role).ok_or_else(|| error("unknown colour"))?;
+          let (mut value, mut line) = (None, None);
+          for part in parts {
+            let (key, field) = part.split_once('=').ok_or_else(|| error("expected `key=value`"))?;
+            match key {
+              "time" => {
+                let ms = field.parse::<u64>().map_err(|_| error("invalid time"))?;
+                move_times.resize(moves.len(), Duration::ZERO);
+                move_times.push(Duration::from_millis(ms));
+              }
+              "value" => value = Some(field.parse::<i32>().map_err(|_| error("invalid value"))?),
+              "line" => {
+                let points = field.split(';').filter(|p| !p.is_empty()).map(parse_point);
+                line = Some(points.collect::<Option<Vec<_>>>().ok_or_else(|| error("invalid line"))?);
+              }
+              _ => return Err(error(&format!("unknown move field '{}'", key))),
+            }
+          }
+          if let Some(value) = value {
+            analysis.resize(moves.len(), None);
+            analysis.push(Some(SearchResult {
+              value,
+              best_move: Some((x, y)),
+              line: line.unwrap_or_default(),
+            }));
+          }
+          moves.push((x, y, role));
+        }
+        _ => return Err(error(&format!("unknown key '{}'", key))),
+      }
+    }
+
+    let missing = |key: &str| format!("missing '{}'", key);
+    Ok(Self {
+      size: size.ok_or_else(|| missing("size"))?,
+      rule: rule.ok_or_else(|| missing("rule"))?,
+      mode: mode.ok_or_else(|| missing("mode"))?,
+      opening_rule,
+      player1: player1.ok_or_else(|| missing("player1"))?,
+      player2: player2.ok_or_else(|| missing("player2"))?,
+      limits1,
+      limits2,
+      to_move: to_move.ok_or_else(|| missing("to-move"))?,
+      forfeited,
+      swap2,
+      moves,
+      move_times,
+      analysis,
+    })
+  }
+
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  pub fn load(path: &str) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+  }
+}
+
+/// `7,6` -> (7, 6).
+fn parse_point(text: &str) -> Option<(usize, usize)> {
+  let (x, y) = text.split_once(',')?;
+  Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+fn role_name(role: Role) -> &'static str {
+  match role {
+    Role::Black => "black",
+    Role::White => "white",
+  }
+}
+
+fn parse_role(name: &str) -> Option<Role> {
+  match name {
+    "black" => Some(Role::Black),
+    "white" => Some(Role::White),
+    _ => None,
+  }
+}
+
+/// Name of `rule` in saved games and SGF records, as given to `--rules`.
+pub fn rule_name(rule: Rule) -> &'static str {
+  match rule {
+    Rule::Freestyle => "freestyle",
+    Rule::Standard => "standard",
+    Rule::Renju => "renju",
+  }
+}
+
+pub fn parse_rule(name: &str) -> Option<Rule> {
+  match name {
+    "freestyle" => Some(Rule::Freestyle),
+    "standard" => Some(Rule::Standard),
+    "renju" => Some(Rule::Renju),
+    _ => None,
+  }
+}
+
+fn opening_rule_name(rule: OpeningRule) -> &'static str {
+  match rule {
+    OpeningRule::Rif => "rif",
+    OpeningRule::Soosorv8 => "soosorv8",
+    OpeningRule::Taraguchi10 => "taraguchi10",
+  }
+}
+
+fn parse_opening_rule(name: &str) -> Option<OpeningRule> {
+  match name {
+    "rif" => Some(OpeningRule::Rif),
+    "soosorv8" => Some(OpeningRule::Soosorv8),
+    "taraguchi10" => Some(OpeningRule::Taraguchi10),
+    _ => None,
+  }
+}
+
+/// `per-move=<ms> remaining=<ms> increment=<ms>`, leaving out the limits that are not set.
+fn limits_text(limits: &SearchLimits) -> String {
+  let mut parts = Vec::new();
+  if let Some(per_move) = limits.per_move {
+    parts.push(format!("per-move={}", per_move.as_millis()));
+  }
+  if let Some(remaining) = limits.remaining {
+    parts.push(format!("remaining={}", remaining.as_millis()));
+  }
+  if !limits.increment.is_zero() {
+    parts.push(format!("increment={}", limits.increment.as_millis()));
+  }
+  parts.join(" ")
+}
+
+fn parse_limits(value: &str) -> Result<SearchLimits, String> {
+  let mut limits = SearchLimits::default();
+  for part in value.split_whitespace() {
+    let (key, ms) = part
+      .split_once('=')
+      .ok_or_else(|| format!("expected `key=milliseconds`, got '{}'", part))?;
+    let ms = ms
+      .parse::<u64>()
+      .map(Duration::from_millis)
+      .map_err(|_| format!("invalid time '{}'", part))?;
+    match key {
+      "per-move" => limits.per_move = Some(ms),
+      "remaining" => limits.remaining = Some(ms),
+      "increment" => limits.increment = ms,
+      _ => return Err(format!("unknown time limit '{}'", key)),
+    }
+  }
+  Ok(limits)
+}
+
+fn mode_name(mode: GameMode) -> &'static str {
+  match mode {
+    GameMode::HumanvHuman => "human-human",
+    GameMode::AIvHuman => "ai-human",
+    GameMode::AIvAI => "ai-ai",
+  }
+}
+
+fn parse_mode(name: &str) -> Option<GameMode> {
+  match name {
+    "human-human" => Some(GameMode::HumanvHuman),
+    "ai-human" => Some(GameMode::AIvHuman),
+    "ai-ai" => Some(GameMode::AIvAI),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record() -> GameRecord {
+    GameRecord {
+      size: 15,
+      rule: Rule::Renju,
+      mode: GameMode::AIvHuman,
+      opening_rule: None,
+      player1: Player {
+        player_type: PlayerType::Human,
+        role: Role::White,
+        engine: EngineConfig::default(),
+      },
+      player2: Player {
+        player_type: PlayerType::AI,
+        role: Role::Black,
+        engine: EngineConfig::External {
+          command: vec!["./pbrain".to_string(), "--fast".to_string()],
+        },
+      },
+      limits1: SearchLimits::default(),
+      limits2: SearchLimits::default(),
+      to_move: Role::White,
+      forfeited: None,
+      swap2: false,
+      moves: vec![(7, 7, Role::Black), (7, 6, Role::White), (8, 8, Role::Black)],
+      move_times: Vec::new(),
+      analysis: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let record = record();
+    assert_eq!(GameRecord::parse(&record.to_text()), Ok(record.clone()));
+
+    let game = record.clone().into_game().unwrap();
+    assert_eq!(game.board.history, record.moves);
+    assert_eq!(game.current_role, Role::White);
+    assert_eq!(game.round, 4);
+    assert_eq!(GameRecord::of(&game), record);
+  }
+
+  #[test]
+  fn test_round_trip_clocks_and_opening_rule() {
+    let mut record = record();
+    record.opening_rule = Some(OpeningRule::Rif);
+    record.limits1 = SearchLimits {
+      per_move: Some(Duration::from_millis(2000)),
+      remaining: Some(Duration::from_millis(281_500)),
+      increment: Duration::from_secs(5),
+    };
+    record.limits2 = SearchLimits {
+      remaining: Some(Duration::from_millis(12_250)),
+      ..SearchLimits::default()
+    };
+    record.forfeited = Some(Role::Black);
+    let text = record.to_text();
+    assert!(text.contains("limits1 per-move=2000 remaining=281500 increment=5000\n"));
+    assert!(text.contains("limits2 remaining=12250\n"));
+    assert_eq!(GameRecord::parse(&text), Ok(record.clone()));
+
+    let game = record.clone().into_game().unwrap();
+    assert_eq!(game.opening_rule, Some(OpeningRule::Rif));
+    assert_eq!(game.board.opening_rule, Some(OpeningRule::Rif));
+    assert_eq!(game.limits1, record.limits1);
+    assert_eq!(game.limits2, record.limits2);
+    assert_eq!(game.forfeited, Some(Role::Black));
+    assert_eq!(GameRecord::of(&game), record);
+
+    assert!(GameRecord::parse(&text.replace("per-move=2000", "per-move=2s")).is_err());
+    assert!(GameRecord::parse(&text.replace("opening-rule rif", "opening-rule yamaguchi")).is_err());
+  }
+
+  #[test]
+  fn test_round_trip_times_analysis_and_swap2() {
+    let mut record = record();
+    record.rule = Rule::Freestyle;
+    record.swap2 = true;
+    record.move_times = vec![Duration::ZERO, Duration::from_millis(1850), Duration::from_millis(40)];
+    record.analysis = vec![
+      None,
+      Some(SearchResult {
+        value: -120,
+        best_move: Some((7, 6)),
+        line: vec![(7, 6), (8, 8), (6, 6)],
+      }),
+    ];
+    let text = record.to_text();
+    assert!(text.contains("swap2 true\n"));
+    assert!(text.contains("move 7,6 white time=1850 value=-120 line=7,6;8,8;6,6\n"));
+    assert_eq!(GameRecord::parse(&text), Ok(record.clone()));
+
+    // The resumed game exports the same times and comments, and skips Swap2
+    let game = record.clone().into_game().unwrap();
+    assert_eq!(game.move_times, record.move_times);
+    assert_eq!(game.analysis, record.analysis);
+    assert!(game.swap2 && game.swap2_done);
+    assert_eq!(GameRecord::of(&game), record);
+
+    assert!(GameRecord::parse(&text.replace("time=1850", "time=2s")).is_err());
+    assert!(GameRecord::parse(&text.replace("line=7,6;", "line=7;")).is_err());
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    let text = record().to_text();
+    assert!(GameRecord::parse(&text.replace("size 15\n", "")).is_err());
+    assert!(GameRecord::parse(&text.replace("renju", "go")).is_err());
+    assert!(GameRecord::parse(&format!("{}move 1 black\n", text)).is_err());
+
+    // A saved move that cannot be replayed is rejected when resuming
+    let mut record = record();
+    record.moves.push((7, 7, Role::White));
+    assert!(record.into_game().is_err());
+  }
+}
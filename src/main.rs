@@ -1,19 +1,56 @@
 mod ai;
+mod bitboard;
 mod board;
+mod book;
 mod cache;
 mod cli;
+mod engine;
+mod external;
 mod game;
 mod game_logger;
+mod game_record;
+mod match_runner;
+mod mcts;
+mod openings;
 mod patterns;
+mod piskvork;
 mod player;
+mod proof_number;
+mod psq;
+mod renju;
+mod renju_opening;
+mod renlib;
+mod sgf;
+mod swap2;
 mod terminal_ui;
+mod time_manager;
+mod transposition;
+mod vcf;
 mod zobrist_cache;
 
-use crate::cli::{CliArgs, FirstPlayerArg, GameModeArg};
+use crate::ai::{AIEngine, CacheHits, MAX};
+use crate::board::{Board, Rule};
+use crate::book::OpeningBook;
+use crate::cli::{
+  BenchArgs, BookArgs, CliArgs, Command, EngineArg, FirstPlayerArg, GameModeArg, OpeningRuleArg, ProtocolArg, RuleArg,
+};
+use crate::engine::EngineConfig;
 use crate::game::{Game, GameMode};
+use crate::game_record::{rule_name, GameRecord};
+use crate::match_runner::{MatchConfig, Sprt};
+use crate::openings::{check_openings, load_openings, random_openings};
+use crate::piskvork::PiskvorkBrain;
 use crate::player::{Player, PlayerType, Role};
+use crate::psq::PsqGame;
+use crate::renju_opening::OpeningRule;
+use crate::renlib::{parse_rif_move, rif_move_name, OpeningLibrary, LIBRARY_SIZE};
+use crate::sgf::SgfNode;
 use clap::Parser;
 use log::info;
+use rand::seq::SliceRandom;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 // use simplelog::*;
 // use std::fs::File;
 
@@ -46,18 +83,148 @@ fn main() {
     GameModeArg::AiAi => GameMode::AIvAI,
   };
 
+  let time_per_move = args.time_per_move.map(Duration::from_millis);
+
+  let rule = match args.rules {
+    RuleArg::Freestyle => Rule::Freestyle,
+    RuleArg::Standard => Rule::Standard,
+    RuleArg::Renju => Rule::Renju,
+  };
+
+  let openings = match &args.openings {
+    Some(spec) => {
+      let suite = load_openings(spec).map_err(|e| e.to_string());
+      match suite.and_then(|suite| check_openings(&suite, args.size, rule).map(|_| suite)) {
+        Ok(suite) => suite,
+        Err(e) => {
+          eprintln!("openings: {}", e);
+          return;
+        }
+      }
+    }
+    None => vec![],
+  };
+
+  // Library browsing only prints what the library knows about one position
+  if let Some(Command::Library(l)) = &args.command {
+    if let Err(e) = browse_library(&l.file, &l.moves, rule) {
+      eprintln!("library: {}", e);
+    }
+    return;
+  }
+
+  if let Some(Command::Book(b)) = &args.command {
+    if let Err(e) = build_book(b, args.size, rule, time_per_move) {
+      eprintln!("book: {}", e);
+    }
+    return;
+  }
+  if let Some(Command::Bench(b)) = &args.command {
+    bench(b, args.size, rule);
+    return;
+  }
+  let book = load_book(&args, args.size, rule);
+
+  // A match runs headless and only prints results
+  if let Some(Command::Match(m)) = args.command {
+    let config = MatchConfig {
+      engine1: m.engine1,
+      engine2: m.engine2,
+      games: m.games,
+      concurrency: m.concurrency,
+      size: args.size,
+      rule,
+      per_move: time_per_move,
+      control: args.time_control,
+      // Each opening is played twice, once with either colour
+      openings: if !openings.is_empty() || m.opening_moves == 0 {
+        openings
+      } else {
+        random_openings(m.games.div_ceil(2), m.opening_moves, args.size, rule, m.opening_seed)
+      },
+      sprt: m.elo0.zip(m.elo1).map(|(elo0, elo1)| Sprt {
+        elo0,
+        elo1,
+        alpha: m.alpha,
+        beta: m.beta,
+      }),
+    };
+    let stats = config.run();
+    println!("{}", config.summary(&stats));
+    return;
+  }
+
+  // Engine settings shared by AI players unless overridden per colour
+  let engine = match args.engine {
+    EngineArg::AlphaBeta => EngineConfig::AlphaBeta {
+      depth: args.depth,
+      hash_mb: args.hash_size,
+      threads: args.threads,
+    },
+    EngineArg::Mcts => EngineConfig::Mcts {
+      playouts: args.playouts,
+      seed: args.seed,
+    },
+    EngineArg::Random => EngineConfig::Random { seed: args.seed },
+  };
+  let black_engine = args.black_engine.clone().unwrap_or_else(|| engine.clone());
+  let white_engine = args.white_engine.clone().unwrap_or_else(|| engine.clone());
+
+  // A protocol engine is driven by a tournament manager, not by the TUI
+  if args.protocol == Some(ProtocolArg::Piskvork) {
+    let stdin = std::io::stdin();
+    let mut engine = engine.create();
+    if let Some(book) = book {
+      engine.set_book(book, args.seed);
+    }
+    let mut brain = PiskvorkBrain::new(stdin.lock(), std::io::stdout(), engine, rule);
+    brain.set_time_limits(time_per_move, args.time_control);
+    if let Err(e) = brain.run() {
+      eprintln!("piskvork: {}", e);
+    }
+    return;
+  }
+
+  // A saved game brings its own board, players and engines
+  if let Some(path) = &args.load {
+    let mut game = match GameRecord::load(path)
+      .map_err(|e| e.to_string())
+      .and_then(GameRecord::into_game)
+    {
+      Ok(game) => game,
+      Err(e) => {
+        eprintln!("load: {}", e);
+        return;
+      }
+    };
+    // The saved clocks stand unless new limits are given
+    if time_per_move.is_some() || args.time_control.is_some() {
+      game.set_time_limits(time_per_move, args.time_control);
+    }
+    game.set_book(book, args.seed);
+    game.save_path = path.clone();
+    game.psq_path = args.export_psq.clone();
+    game.sgf_path = args.export_sgf.clone();
+    if args.log {
+      game.run_with_logging();
+    } else {
+      game.run();
+    }
+    return;
+  }
+
   // 3) Define players based on the game mode
   let (player1, player2) = match mode {
     GameMode::HumanvHuman => (
       Player {
         player_type: PlayerType::Human,
         role: Role::Black,
-        depth: 0,
+        engine: EngineConfig::default(),
       },
       Player {
         player_type: PlayerType::Human,
         role: Role::White,
-        depth: 0,
+        engine: EngineConfig::default(),
       },
     ),
     GameMode::AIvHuman => match args.first_player {
@@ -65,24 +232,24 @@ fn main() {
         Player {
           player_type: PlayerType::Human,
           role: Role::Black,
-          depth: 0,
+          engine: EngineConfig::default(),
         },
         Player {
           player_type: PlayerType::AI,
           role: Role::White,
-          depth: args.depth,
+          engine: white_engine,
         },
       ),
       FirstPlayerArg::AI => (
         Player {
           player_type: PlayerType::AI,
           role: Role::Black,
-          depth: args.depth,
+          engine: black_engine,
         },
         Player {
           player_type: PlayerType::Human,
           role: Role::White,
-          depth: 0,
+          engine: EngineConfig::default(),
         },
       ),
     },
@@ -90,18 +257,74 @@ fn main() {
       Player {
         player_type: PlayerType::AI,
         role: Role::Black,
-        depth: args.depth,
+        engine: black_engine,
       },
       Player {
         player_type: PlayerType::AI,
         role: Role::White,
-        depth: args.depth,
+        engine: white_engine,
       },
     ),
   };
 
   // 4) Create the game instance
-  let mut game = Game::new(args.size, mode, player1, player2);
+  let mut game = Game::new(args.size, rule, mode, player1, player2);
+  game.set_time_limits(time_per_move, args.time_control);
+  game.set_book(book, args.seed);
+  game.swap2 = args.swap2;
+  if let Some(opening_rule) = args.opening_rule {
+    game.set_opening_rule(match opening_rule {
+      OpeningRuleArg::Rif => OpeningRule::Rif,
+      OpeningRuleArg::Soosorv8 => OpeningRule::Soosorv8,
+      OpeningRuleArg::Taraguchi10 => OpeningRule::Taraguchi10,
+    });
+  }
+  if let Some(opening) = openings.choose(&mut rand::thread_rng()) {
+    info!("Starting from opening {}", opening.name);
+    game.set_opening(opening);
+  }
+  let imported = match (&args.import_psq, &args.import_sgf) {
+    (Some(path), _) => Some((
+      path,
+      PsqGame::load(path)
+        .map_err(|e| e.to_string())
+        .and_then(|psq| psq.to_board(rule)),
+    )),
+    (_, Some(path)) => Some((
+      path,
+      SgfNode::load(path)
+        .map_err(|e| e.to_string())
+        .and_then(|sgf| sgf.to_board(rule)),
+    )),
+    _ => None,
+  };
+  if let Some((path, imported)) = imported {
+    match imported {
+      Ok(board) if board.size != args.size => {
+        eprintln!(
+          "import: {} is a {}x{} game, use --size {}",
+          path, board.size, board.size, board.size
+        );
+        return;
+      }
+      Ok(board) if board.rule != rule => {
+        eprintln!(
+          "import: {} is a {} game, use --rules {}",
+          path,
+          rule_name(board.rule),
+          rule_name(board.rule)
+        );
+        return;
+      }
+      Ok(board) => game.set_position(board),
+      Err(e) => {
+        eprintln!("import: {}", e);
+        return;
+      }
+    }
+  }
+  game.psq_path = args.export_psq.clone();
+  game.sgf_path = args.export_sgf.clone();
 
   // 5) Run the game loop
   if args.log {
@@ -110,3 +333,124 @@ fn main() {
     game.run();
   }
 }
+
+/// Print the comment and the continuations of the position after `moves` in a library.
+fn browse_library(path: &str, moves: &[String], rule: Rule) -> Result<(), String> {
+  let library = OpeningLibrary::load(path).map_err(|e| e.to_string())?;
+  let mut board = Board::with_rule(LIBRARY_SIZE, rule);
+  let mut role = Role::Black;
+  for name in moves {
+    match parse_rif_move(name) {
+      Some((x, y)) if board.put(x, y, role) => role = role.opponent(),
+      _ => return Err(format!("illegal move '{}'", name)),
+    }
+  }
+  let Some((comment, continuations)) = library.lookup(&board) else {
+    println!("Position not in the library");
+    return Ok(());
+  };
+  if !comment.is_empty() {
+    println!("{}", comment);
+  }
+  for continuation in continuations {
+    let (x, y) = continuation.mv;
+    println!("  {:<4} {}", rif_move_name(x, y), continuation.comment);
+  }
+  Ok(())
+}
+
+/// The opening book of the AI players, unless disabled, missing or built for another
+/// board size or rule.
+fn load_book(args: &CliArgs, size: usize, rule: Rule) -> Option<Arc<OpeningBook>> {
+  if args.no_book || !Path::new(&args.book).exists() {
+    return None;
+  }
+  match OpeningBook::load(&args.book) {
+    Ok(book) if book.size != size || book.rule != rule => {
+      eprintln!(
+        "book: {} was built for {:?} on {}x{}, not used",
+        args.book, book.rule, book.size, book.size
+      );
+      None
+    }
+    Ok(book) => {
+      info!("Opening book {}: {} positions", args.book, book.len());
+      Some(Arc::new(book))
+    }
+    Err(e) => {
+      eprintln!("book: {}", e);
+      None
+    }
+  }
+}
+
+/// Run the full-width search on the same random openings, from an empty table each
+/// time, and print its speed to compare board and search changes.
+fn bench(args: &BenchArgs, size: usize, rule: Rule) {
+  let mut engine = AIEngine::new(args.depth);
+  let mut nodes = 0;
+  let mut elapsed = Duration::ZERO;
+  for opening in random_openings(args.positions, args.opening_moves, size, rule, args.seed) {
+    let mut board = Board::with_rule(size, rule);
+    opening.place(&mut board);
+    let role = if board.history.len().is_multiple_of(2) {
+      Role::Black
+    } else {
+      Role::White
+    };
+    engine.clear_hash();
+    engine.cache_hits = CacheHits::default();
+    let start = Instant::now();
+    engine.analyze(false, false, &mut board, role, args.depth, 0, &mut vec![], -MAX, MAX);
+    elapsed += start.elapsed();
+    nodes += engine.cache_hits.search as u64;
+  }
+  println!(
+    "{} positions, depth {}: {} nodes in {:.2?}, {:.0} nodes/s",
+    args.positions,
+    args.depth,
+    nodes,
+    elapsed,
+    nodes as f64 / elapsed.as_secs_f64()
+  );
+}
+
+/// Extend (or start) the book file with .psq games and self-play games.
+fn build_book(args: &BookArgs, size: usize, rule: Rule, per_move: Option<Duration>) -> Result<(), String> {
+  let mut book = if Path::new(&args.out).exists() {
+    let book = OpeningBook::load(&args.out).map_err(|e| e.to_string())?;
+    if book.rule != rule {
+      return Err(format!("{} was built for {:?}, not {:?}", args.out, book.rule, rule));
+    }
+    if book.size != size {
+      return Err(format!(
+        "{} was built for {}x{}, not {}x{}",
+        args.out, book.size, book.size, size, size
+      ));
+    }
+    book
+  } else {
+    OpeningBook::new(size, rule)
+  };
+  let games = args
+    .psq
+    .iter()
+    .map(|path| PsqGame::load(path).map_err(|e| e.to_string()))
+    .collect::<Result<Vec<_>, _>>()?;
+  let learned = book.add_psq_games(&games, args.plies)?;
+  if learned < games.len() {
+    println!(
+      "Skipped {} games not played on a {}x{} board",
+      games.len() - learned,
+      book.size,
+      book.size
+    );
+  }
+  book.add_self_play(&args.engine, args.self_play, per_move, args.plies, args.seed);
+  if book.is_empty() {
+    return Err("no games to learn from, give --psq files or --self-play games".to_string());
+  }
+  book.save(&args.out).map_err(|e| e.to_string())?;
+  println!("{}: {} positions", args.out, book.len());
+  Ok(())
+}
@@ -0,0 +1,398 @@
+use crate::board::{Board, Rule};
+use crate::engine::{Engine, EngineConfig, SearchLimits};
+use crate::openings::Opening;
+use crate::player::Role;
+use crate::time_manager::TimeControl;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Two-sided 95% quantile of the normal distribution, for the Elo error bars.
+const Z_95: f64 = 1.959964;
+
+/// Games won, drawn and lost, from the first engine's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStats {
+  pub wins: usize,
+  pub draws: usize,
+  pub losses: usize,
+}
+
+impl MatchStats {
+  pub fn games(&self) -> usize {
+    self.wins + self.draws + self.losses
+  }
+
+  /// Mean points per game: 1 for a win, 1/2 for a draw.
+  pub fn score(&self) -> f64 {
+    if self.games() == 0 {
+      return 0.5;
+    }
+    (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+  }
+
+  /// Variance of the points of a single game.
+  fn variance(&self) -> f64 {
+    let n = self.games() as f64;
+    let s = self.score();
+    (self.wins as f64 * (1.0 - s).powi(2) + self.draws as f64 * (0.5 - s).powi(2) + self.losses as f64 * s.powi(2)) / n
+  }
+
+  /// Elo difference and the half-width of its 95% confidence interval. A perfect score
+  /// has infinite error bars.
+  pub fn elo(&self) -> (f64, f64) {
+    let s = self.score();
+    if s <= 0.0 || s >= 1.0 {
+      return (elo_from_score(s), f64::INFINITY);
+    }
+    let margin = Z_95 * (self.variance() / self.games() as f64).sqrt();
+    let low = elo_from_score((s - margin).max(0.0));
+    let high = elo_from_score((s + margin).min(1.0));
+    (elo_from_score(s), (high - low) / 2.0)
+  }
+
+  /// Log-likelihood ratio of H1 (Elo difference `elo1`) against H0 (`elo0`), with the
+  /// usual normal approximation of the game results.
+  pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+    if self.games() == 0 {
+      return 0.0;
+    }
+    let (s0, s1) = (score_from_elo(elo0), score_from_elo(elo1));
+    // All results equal so far: take the variance of a drawless game between the hypotheses
+    let variance = match self.variance() {
+      v if v > 0.0 => v,
+      _ => (s0 + s1) / 2.0 * (1.0 - (s0 + s1) / 2.0),
+    };
+    self.games() as f64 * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+  }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+  -400.0 * (1.0 / score - 1.0).log10()
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Sequential probability ratio test of "engine 1 is `elo1` stronger" (H1) against
+/// "engine 1 is `elo0` stronger" (H0), with error rates `alpha` and `beta`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+  pub elo0: f64,
+  pub elo1: f64,
+  pub alpha: f64,
+  pub beta: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtVerdict {
+  AcceptH0,
+  AcceptH1,
+  Continue,
+}
+
+impl Sprt {
+  /// Lower and upper LLR bounds.
+  pub fn bounds(&self) -> (f64, f64) {
+    ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+  }
+
+  pub fn verdict(&self, stats: &MatchStats) -> SprtVerdict {
+    let llr = stats.llr(self.elo0, self.elo1);
+    let (lower, upper) = self.bounds();
+    if llr >= upper {
+      SprtVerdict::AcceptH1
+    } else if llr <= lower {
+      SprtVerdict::AcceptH0
+    } else {
+      SprtVerdict::Continue
+    }
+  }
+}
+
+/// How a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+  Five(Role),
+  /// Board full.
+  Draw,
+  /// The engine of this role gave no legal move.
+  Forfeit(Role),
+  /// The opening does not fit the board size or rule; the game was not played.
+  InvalidOpening,
+}
+
+impl Termination {
+  pub fn winner(&self) -> Option<Role> {
+    match *self {
+      Termination::Five(role) => Some(role),
+      Termination::Draw | Termination::InvalidOpening => None,
+      Termination::Forfeit(role) => Some(role.opponent()),
+    }
+  }
+}
+
+/// Settings of a headless match between two engines.
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+  pub engine1: EngineConfig,
+  pub engine2: EngineConfig,
+  pub games: usize,
+  /// Games played at the same time.
+  pub concurrency: usize,
+  pub size: usize,
+  pub rule: Rule,
+  pub per_move: Option<Duration>,
+  pub control: Option<TimeControl>,
+  /// Starting positions, each played twice with colours swapped. An empty list starts
+  /// every game from the empty board.
+  pub openings: Vec<Opening>,
+  /// Stop early once the test is decided.
+  pub sprt: Option<Sprt>,
+}
+
+impl MatchConfig {
+  /// Play the match, printing one line per finished game. Returns the result from the
+  /// first engine's point of view.
+  pub fn run(&self) -> MatchStats {
+    let next_game = AtomicUsize::new(0);
+    let decided = AtomicBool::new(false);
+    let stats = Mutex::new(MatchStats::default());
+
+    thread::scope(|s| {
+      for _ in 0..self.concurrency.max(1) {
+        s.spawn(|| {
+          // Engines live as long as the worker; `new_game` resets them between games
+          let mut engine1 = self.engine1.create();
+          let mut engine2 = self.engine2.create();
+          loop {
+            let index = next_game.fetch_add(1, Ordering::Relaxed);
+            if index >= self.games || decided.load(Ordering::Relaxed) {
+              break;
+            }
+            let (termination, board, engine1_role) = self.play_match_game(index, engine1.as_mut(), engine2.as_mut());
+            if termination == Termination::InvalidOpening {
+              let opening = &self.openings[(index / 2) % self.openings.len()];
+              println!("Game {} skipped: opening {} does not fit the board", index + 1, opening.name);
+              continue;
+            }
+
+            let mut stats = stats.lock().expect("no worker panics while holding the lock");
+            match termination.winner() {
+              None => stats.draws += 1,
+              Some(role) if role == engine1_role => stats.wins += 1,
+              Some(_) => stats.losses += 1,
+            }
+            println!(
+              "Game {} ({} as {:?}): {:?} after {} moves, {} - {} - {}",
+              index + 1,
+              engine1.name(),
+              engine1_role,
+              termination,
+              board.history.len(),
+              stats.wins,
+              stats.losses,
+              stats.draws
+            );
+            if self.sprt.is_some_and(|sprt| sprt.verdict(&stats) != SprtVerdict::Continue) {
+              decided.store(true, Ordering::Relaxed);
+            }
+          }
+        });
+      }
+    });
+    stats.into_inner().expect("no worker panics while holding the lock")
+  }
+
+  /// Play game number `index` of the match. The first engine has Black in even games
+  /// and White in odd ones, so each opening is played with both colours. Also returns
+  /// the first engine's colour.
+  fn play_match_game(&self, index: usize, engine1: &mut dyn Engine, engine2: &mut dyn Engine) -> (Termination, Board, Role) {
+    if index.is_multiple_of(2) {
+      let (termination, board) = self.play_game(index, [engine1, engine2]);
+      (termination, board, Role::Black)
+    } else {
+      let (termination, board) = self.play_game(index, [engine2, engine1]);
+      (termination, board, Role::White)
+    }
+  }
+
+  /// Play game number `index` between the black and the white engine, from its opening.
+  /// Returns how it ended and the final position.
+  pub fn play_game(&self, index: usize, mut engines: [&mut dyn Engine; 2]) -> (Termination, Board) {
+    let mut board = Board::with_rule(self.size, self.rule);
+    if !self.openings.is_empty() && !self.openings[(index / 2) % self.openings.len()].place(&mut board) {
+      return (Termination::InvalidOpening, board);
+    }
+    for engine in engines.iter_mut() {
+      engine.new_game(self.size, self.rule);
+    }
+    let mut limits = [
+      SearchLimits::new(self.per_move, self.control),
+      SearchLimits::new(self.per_move, self.control),
+    ];
+
+    let mut role = board.history.last().map_or(Role::Black, |&(_, _, last)| last.opponent());
+    loop {
+      let winner = board.get_winner();
+      if winner != 0 {
+        let role = if winner > 0 { Role::White } else { Role::Black };
+        return (Termination::Five(role), board);
+      }
+      if board.is_game_over() {
+        return (Termination::Draw, board);
+      }
+
+      let side = if role == Role::Black { 0 } else { 1 };
+      let (engine, limits) = (&mut engines[side], &mut limits[side]);
+      let started = Instant::now();
+      let result = engine.think(&board, role, limits);
+      let used = started.elapsed();
+      let out_of_time = limits.remaining.is_some_and(|remaining| used > remaining);
+      limits.charge(used);
+      match result.best_move {
+        Some((x, y)) if !out_of_time && board.put(x, y, role) => role = role.opponent(),
+        _ => return (Termination::Forfeit(role), board),
+      }
+    }
+  }
+
+  /// Final report: score, Elo difference and the SPRT verdict.
+  pub fn summary(&self, stats: &MatchStats) -> String {
+    let (elo, margin) = stats.elo();
+    let mut summary = format!(
+      "Score of {} vs {}: {} - {} - {} [{:.3}] {}\nElo difference: {:.1} +/- {:.1}",
+      self.engine1,
+      self.engine2,
+      stats.wins,
+      stats.losses,
+      stats.draws,
+      stats.score(),
+      stats.games(),
+      elo,
+      margin
+    );
+    if let Some(sprt) = self.sprt {
+      let (lower, upper) = sprt.bounds();
+      let verdict = match sprt.verdict(stats) {
+        SprtVerdict::AcceptH0 => "H0 accepted",
+        SprtVerdict::AcceptH1 => "H1 accepted",
+        SprtVerdict::Continue => "inconclusive",
+      };
+      summary += &format!(
+        "\nSPRT: llr {:.2} ({:.2}, {:.2}) [{}, {}]: {}",
+        stats.llr(sprt.elo0, sprt.elo1),
+        lower,
+        upper,
+        sprt.elo0,
+        sprt.elo1,
+        verdict
+      );
+    }
+    summary
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stats(wins: usize, draws: usize, losses: usize) -> MatchStats {
+    MatchStats { wins, draws, losses }
+  }
+
+  #[test]
+  fn test_elo() {
+    assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+    assert!((score_from_elo(elo_from_score(0.3)) - 0.3).abs() < 1e-9);
+
+    let (elo, margin) = stats(60, 0, 40).elo();
+    assert!((elo - 70.44).abs() < 0.01);
+    assert!(margin > 50.0 && margin < 80.0, "{}", margin);
+    // More games, narrower error bars
+    assert!(stats(600, 0, 400).elo().1 < margin);
+  }
+
+  #[test]
+  fn test_sprt() {
+    let sprt = Sprt {
+      elo0: 0.0,
+      elo1: 10.0,
+      alpha: 0.05,
+      beta: 0.05,
+    };
+    let (lower, upper) = sprt.bounds();
+    assert!((upper - 2.944).abs() < 0.001 && (lower + 2.944).abs() < 0.001);
+    assert_eq!(sprt.verdict(&stats(600, 200, 400)), SprtVerdict::AcceptH1);
+    assert_eq!(sprt.verdict(&stats(400, 200, 600)), SprtVerdict::AcceptH0);
+    assert_eq!(sprt.verdict(&stats(10, 5, 9)), SprtVerdict::Continue);
+    assert_eq!(sprt.verdict(&MatchStats::default()), SprtVerdict::Continue);
+    // A clean sweep decides the test, but not after a handful of games
+    let sweep = Sprt { elo1: 100.0, ..sprt };
+    assert_eq!(sweep.verdict(&stats(5, 0, 0)), SprtVerdict::Continue);
+    assert_eq!(sweep.verdict(&stats(15, 0, 0)), SprtVerdict::AcceptH1);
+    assert_eq!(stats(15, 0, 0).elo().1, f64::INFINITY);
+  }
+
+  #[test]
+  fn test_match_alternates_colours() {
+    let config = MatchConfig {
+      engine1: EngineConfig::Random { seed: 1 },
+      engine2: EngineConfig::External {
+        command: vec!["/nonexistent/brain".to_string()],
+      },
+      games: 6,
+      concurrency: 2,
+      size: 9,
+      rule: Rule::Freestyle,
+      per_move: None,
+      control: None,
+      openings: crate::openings::random_openings(3, 2, 9, Rule::Freestyle, 1),
+      sprt: None,
+    };
+    // The broken brain forfeits every game, as White in even games and Black in odd ones
+    let mut random = config.engine1.create();
+    let mut brain = config.engine2.create();
+    for index in 0..4 {
+      let (termination, _, random_role) = config.play_match_game(index, random.as_mut(), brain.as_mut());
+      let expected = if index % 2 == 0 { Role::Black } else { Role::White };
+      assert_eq!(random_role, expected, "game {}", index);
+      assert_eq!(termination, Termination::Forfeit(expected.opponent()), "game {}", index);
+    }
+    assert_eq!(config.run(), stats(6, 0, 0));
+  }
+
+  #[test]
+  fn test_invalid_opening_is_skipped() {
+    use crate::openings::Opening;
+
+    let config = MatchConfig {
+      engine1: EngineConfig::Random { seed: 1 },
+      engine2: EngineConfig::Random { seed: 2 },
+      games: 4,
+      concurrency: 1,
+      size: 9,
+      rule: Rule::Freestyle,
+      per_move: None,
+      control: None,
+      openings: vec![
+        Opening {
+          name: "wide".to_string(),
+          moves: vec![(0, 0), (6, 0)],
+        },
+        Opening {
+          name: "centre".to_string(),
+          moves: vec![(0, 0), (1, 0)],
+        },
+      ],
+      sprt: None,
+    };
+    let (mut black, mut white) = (config.engine1.create(), config.engine2.create());
+    let (termination, _) = config.play_game(0, [black.as_mut(), white.as_mut()]);
+    assert_eq!(termination, Termination::InvalidOpening);
+    // Only the two games of the opening that fits are counted
+    assert_eq!(config.run().games(), 2);
+  }
+}
@@ -0,0 +1,333 @@
+use crate::ai::HIGH_VALUE;
+use crate::board::Board;
+use crate::engine::StopHandle;
+use crate::player::Role;
+use crate::time_manager::TimeManager;
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+/// Default number of playouts per move.
+pub const DEFAULT_PLAYOUTS: usize = 2_000;
+/// Exploration constant of UCT.
+const EXPLORATION: f64 = 1.4;
+/// Children per tree node: the best cells by the board heuristics.
+const MAX_CHILDREN: usize = 12;
+/// Rollout moves are drawn from this many of the best cells.
+const ROLLOUT_CANDIDATES: usize = 6;
+/// A rollout still undecided after this many moves counts as a draw.
+const ROLLOUT_DEPTH: usize = 30;
+
+#[derive(Debug)]
+struct Node {
+  mv: Option<(usize, usize)>,
+  parent: Option<usize>,
+  children: Vec<usize>,
+  /// Moves not expanded yet, best last so that `pop` takes it first.
+  untried: Vec<(usize, usize)>,
+  /// Role to move in this node.
+  to_move: Role,
+  /// The move into this node completed a five.
+  terminal: bool,
+  visits: u32,
+  /// Sum of results from the view of the player who made `mv`.
+  wins: f64,
+}
+
+/// Monte Carlo Tree Search player: UCT over the heuristically best cells, with rollouts
+/// that pick among the top cells of `Board::get_moves` weighted by their scores.
+///
+/// Deterministic for a given seed, position and playout count.
+#[derive(Debug)]
+pub struct MctsEngine {
+  pub playouts: usize,
+  /// Per-move time budget and game clock; unlimited by default.
+  pub time: TimeManager,
+  /// Raised to end the playouts early; the best move so far is returned.
+  pub(crate) stop: StopHandle,
+  rng: StdRng,
+  nodes: Vec<Node>,
+}
+
+impl MctsEngine {
+  pub fn new(playouts: usize, seed: u64) -> Self {
+    Self {
+      playouts,
+      time: TimeManager::default(),
+      stop: StopHandle::default(),
+      rng: StdRng::seed_from_u64(seed),
+      nodes: Vec::new(),
+    }
+  }
+
+  /// Choose a move for `role` after `playouts` playouts, when the time budget is used up
+  /// or when stopped.
+  #[allow(clippy::type_complexity)]
+  pub fn make_move(&mut self, board: &mut Board, role: Role) -> (i32, Option<(usize, usize)>, Vec<(usize, usize)>) {
+    if board.history.is_empty() {
+      let center = board.size / 2;
+      return (0, Some((center, center)), vec![]);
+    }
+
+    self.time.start();
+    let deadline = self.time.deadline(1.0);
+    let mut work = board.clone();
+    self.nodes.clear();
+    let untried = tree_moves(&mut work, role);
+    self.nodes.push(Node {
+      mv: None,
+      parent: None,
+      children: Vec::new(),
+      untried,
+      to_move: role,
+      terminal: false,
+      visits: 0,
+      wins: 0.0,
+    });
+
+    let mut playouts = 0;
+    while playouts < self.playouts && !self.stop.is_stopped() && deadline.is_none_or(|d| Instant::now() < d) {
+      self.playout(&mut work);
+      playouts += 1;
+    }
+    self.time.stop();
+
+    let line = self.principal_line();
+    let value = self.nodes[0]
+      .children
+      .iter()
+      .max_by_key(|&&c| self.nodes[c].visits)
+      .map(|&c| {
+        let child = &self.nodes[c];
+        let rate = child.wins / child.visits.max(1) as f64;
+        // Map the win rate onto the evaluation scale: 0.5 is level
+        ((rate - 0.5) * 2.0 * HIGH_VALUE as f64 * 0.3) as i32
+      })
+      .unwrap_or(0);
+    info!("MCTS: {} playouts, value {}, line {:?}", playouts, value, line);
+    (value, line.first().copied(), line)
+  }
+
+  /// One iteration: select, expand, roll out, back-propagate. `board` is restored.
+  fn playout(&mut self, board: &mut Board) {
+    let mut current = 0;
+    let mut depth = 0;
+
+    // Selection
+    while self.nodes[current].untried.is_empty() && !self.nodes[current].children.is_empty() {
+      current = self.select_child(current);
+      let (x, y) = self.nodes[current].mv.expect("children have moves");
+      let mover = self.nodes[current].to_move.opponent();
+      board.put(x, y, mover);
+      depth += 1;
+    }
+
+    // Expansion
+    if !self.nodes[current].terminal {
+      if let Some((x, y)) = self.nodes[current].untried.pop() {
+        let mover = self.nodes[current].to_move;
+        let terminal = board.makes_five(x, y, mover);
+        if board.put(x, y, mover) {
+          depth += 1;
+          let untried = if terminal {
+            vec![]
+          } else {
+            tree_moves(board, mover.opponent())
+          };
+          let child = self.nodes.len();
+          self.nodes.push(Node {
+            mv: Some((x, y)),
+            parent: Some(current),
+            children: Vec::new(),
+            untried,
+            to_move: mover.opponent(),
+            terminal,
+            visits: 0,
+            wins: 0.0,
+          });
+          self.nodes[current].children.push(child);
+          current = child;
+        }
+      }
+    }
+
+    // Rollout, scored for the player who moved into `current`
+    let (terminal, to_move) = (self.nodes[current].terminal, self.nodes[current].to_move);
+    let result = if terminal {
+      1.0
+    } else {
+      match self.rollout(board, to_move) {
+        Some(role) if role == to_move => 0.0,
+        Some(_) => 1.0,
+        None => 0.5,
+      }
+    };
+
+    // Back-propagation, flipping the point of view at every level
+    let mut result = result;
+    let mut index = Some(current);
+    while let Some(i) = index {
+      self.nodes[i].visits += 1;
+      self.nodes[i].wins += result;
+      result = 1.0 - result;
+      index = self.nodes[i].parent;
+    }
+
+    for _ in 0..depth {
+      board.undo();
+    }
+  }
+
+  fn select_child(&self, index: usize) -> usize {
+    let parent_visits = (self.nodes[index].visits.max(1) as f64).ln();
+    let uct = |c: usize| {
+      let child = &self.nodes[c];
+      let visits = child.visits.max(1) as f64;
+      child.wins / visits + EXPLORATION * (parent_visits / visits).sqrt()
+    };
+    *self.nodes[index]
+      .children
+      .iter()
+      .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+      .expect("selection only descends into expanded nodes")
+  }
+
+  /// Play biased random moves from the current position; returns the winner, if any.
+  /// The board is restored.
+  fn rollout(&mut self, board: &mut Board, mut role: Role) -> Option<Role> {
+    let mut played = 0;
+    let mut winner = None;
+    while played < ROLLOUT_DEPTH {
+      let Some((x, y)) = self.rollout_move(board, role) else {
+        break;
+      };
+      let wins = board.makes_five(x, y, role);
+      if !board.put(x, y, role) {
+        break;
+      }
+      played += 1;
+      if wins {
+        winner = Some(role);
+        break;
+      }
+      role = role.opponent();
+    }
+    for _ in 0..played {
+      board.undo();
+    }
+    winner
+  }
+
+  /// Win at once if possible, block a five if needed, otherwise draw from the best
+  /// cells with probability proportional to their heuristic score.
+  fn rollout_move(&mut self, board: &mut Board, role: Role) -> Option<(usize, usize)> {
+    if let Some(&five) = board.five_points(role).first() {
+      return Some(five);
+    }
+    if let Some(&block) = board.five_points(role.opponent()).first() {
+      return Some(block);
+    }
+    let candidates: Vec<((usize, usize), f64)> = board
+      .get_moves(role, 0, false, false)
+      .into_iter()
+      .take(ROLLOUT_CANDIDATES)
+      .map(|(x, y)| {
+        let score = board
+          .get_role_score(role, x, y)
+          .max(board.get_role_score(role.opponent(), x, y));
+        ((x, y), score.max(0) as f64 + 1.0)
+      })
+      .collect();
+    let total: f64 = candidates.iter().map(|(_, w)| w).sum();
+    let mut pick = self.rng.gen_range(0.0..total.max(f64::MIN_POSITIVE));
+    for &(mv, weight) in &candidates {
+      if pick < weight {
+        return Some(mv);
+      }
+      pick -= weight;
+    }
+    candidates.last().map(|&(mv, _)| mv)
+  }
+
+  /// Most visited path from the root.
+  fn principal_line(&self) -> Vec<(usize, usize)> {
+    let mut line = Vec::new();
+    let mut current = 0;
+    while let Some(&best) = self.nodes[current].children.iter().max_by_key(|&&c| self.nodes[c].visits) {
+      line.extend(self.nodes[best].mv);
+      current = best;
+    }
+    line
+  }
+}
+
+/// Candidate moves of a tree node, best last. A five, or the block of the opponent's
+/// five, is the only move worth trying.
+fn tree_moves(board: &mut Board, role: Role) -> Vec<(usize, usize)> {
+  if let Some(&five) = board.five_points(role).first() {
+    return vec![five];
+  }
+  let blocks = board.five_points(role.opponent());
+  if !blocks.is_empty() {
+    return blocks;
+  }
+  let mut moves: Vec<(usize, usize)> = board
+    .get_moves(role, 0, false, false)
+    .into_iter()
+    .take(MAX_CHILDREN)
+    .collect();
+  moves.reverse();
+  moves
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn position() -> Board {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(8, 8, Role::White);
+    board.put(6, 8, Role::Black);
+    board.put(8, 6, Role::White);
+    board
+  }
+
+  #[test]
+  fn test_same_seed_same_move() {
+    let mut a = MctsEngine::new(50, 7);
+    let mut b = MctsEngine::new(50, 7);
+    let (_, move_a, line_a) = a.make_move(&mut position(), Role::Black);
+    let (_, move_b, line_b) = b.make_move(&mut position(), Role::Black);
+    assert_eq!(move_a, move_b);
+    assert_eq!(line_a, line_b);
+  }
+
+  #[test]
+  fn test_takes_the_win_and_blocks() {
+    let mut board = Board::new(15);
+    for x in 3..7 {
+      board.put(x, 7, Role::White);
+    }
+    board.put(2, 7, Role::Black);
+    board.put(8, 8, Role::Black);
+    let history = board.history.len();
+
+    let mut mcts = MctsEngine::new(20, 1);
+    assert_eq!(mcts.make_move(&mut board, Role::White).1, Some((7, 7)));
+    assert_eq!(mcts.make_move(&mut board, Role::Black).1, Some((7, 7)));
+    assert_eq!(board.history.len(), history, "board must be restored");
+  }
+
+  #[test]
+  fn test_time_limit() {
+    use std::time::Duration;
+    let mut mcts = MctsEngine::new(usize::MAX, 3);
+    mcts.time = TimeManager::new(Some(Duration::from_millis(200)), None);
+    let started = Instant::now();
+    let (_, mv, _) = mcts.make_move(&mut position(), Role::Black);
+    assert!(mv.is_some());
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+  }
+}
@@ -0,0 +1,235 @@
+use crate::board::{Board, Rule};
+use crate::player::Role;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+
+/// A starting position: moves given as (dx, dy) offsets from the centre of the board,
+/// Black first, so the same opening fits every board size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+  pub name: String,
+  pub moves: Vec<(i32, i32)>,
+}
+
+impl Opening {
+  /// Absolute coordinates on a board of `size`, or `None` if a move falls outside it.
+  pub fn moves_on(&self, size: usize) -> Option<Vec<(usize, usize)>> {
+    let center = (size / 2) as i32;
+    self
+      .moves
+      .iter()
+      .map(|&(dx, dy)| {
+        let (x, y) = (center + dx, center + dy);
+        (x >= 0 && y >= 0 && x < size as i32 && y < size as i32).then_some((x as usize, y as usize))
+      })
+      .collect()
+  }
+
+  /// Play the opening on `board`, alternating from Black. Returns `false` if a move is
+  /// off the board, on an occupied cell or forbidden; the board is then left as it is.
+  pub fn place(&self, board: &mut Board) -> bool {
+    let Some(moves) = self.moves_on(board.size) else {
+      return false;
+    };
+    let mut role = Role::Black;
+    for (x, y) in moves {
+      if !board.put(x, y, role) {
+        return false;
+      }
+      role = role.opponent();
+    }
+    true
+  }
+}
+
+/// Third stones of the direct openings (White at (0, -1)), in the order of their
+/// standard numbers D1-D13. Offsets are from the centre, y growing downwards.
+const DIRECT_OPENINGS: [(&str, (i32, i32)); 13] = [
+  ("Kansei", (0, -2)),
+  ("Keigetsu", (1, -2)),
+  ("Sosei", (2, -2)),
+  ("Kagetsu", (1, -1)),
+  ("Zangetsu", (2, -1)),
+  ("Ugetsu", (1, 0)),
+  ("Kinsei", (2, 0)),
+  ("Shogetsu", (1, 1)),
+  ("Kyugetsu", (2, 1)),
+  ("Shingetsu", (1, 2)),
+  ("Zuisei", (2, 2)),
+  ("Sangetsu", (0, 1)),
+  ("Yusei", (0, 2)),
+];
+
+/// Third stones of the indirect openings (White at (1, -1)), in the order of their
+/// standard numbers I1-I13.
+const INDIRECT_OPENINGS: [(&str, (i32, i32)); 13] = [
+  ("Chosei", (2, -2)),
+  ("Kyogetsu", (2, -1)),
+  ("Kosei", (2, 0)),
+  ("Suigetsu", (2, 1)),
+  ("Ryusei", (2, 2)),
+  ("Ungetsu", (1, 0)),
+  ("Hogetsu", (1, 1)),
+  ("Gingetsu", (1, 2)),
+  ("Myojo", (0, 1)),
+  ("Shagetsu", (0, 2)),
+  ("Meigetsu", (-1, 1)),
+  ("Suisei", (-1, 2)),
+  ("Kisei", (-2, 2)),
+];
+
+/// The 26 canonical Renju openings: Black in the centre, White next to it either
+/// directly or diagonally, and the third stone anywhere in the central 5x5 square up to
+/// the mirror symmetry of the first two stones. Each carries its standard name, e.g.
+/// `D4 Kagetsu` or `I7 Hogetsu`.
+pub fn renju_openings() -> Vec<Opening> {
+  [("D", (0, -1), DIRECT_OPENINGS), ("I", (1, -1), INDIRECT_OPENINGS)]
+    .into_iter()
+    .flat_map(|(prefix, white, thirds)| {
+      thirds.into_iter().enumerate().map(move |(i, (name, third))| Opening {
+        name: format!("{}{} {}", prefix, i + 1, name),
+        moves: vec![(0, 0), white, third],
+      })
+    })
+    .collect()
+}
+
+/// Parse an opening file: one opening per line as comma-separated offsets from the
+/// centre, `dx1, dy1, dx2, dy2, ...` (the Gomocup tournament format). Blank lines and
+/// lines starting with `#` are skipped.
+pub fn parse_openings(text: &str) -> Result<Vec<Opening>, String> {
+  let mut openings = Vec::new();
+  for (number, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let values = line
+      .split(',')
+      .map(|v| v.trim().parse::<i32>())
+      .collect::<Result<Vec<i32>, _>>()
+      .map_err(|_| format!("line {}: expected comma-separated numbers", number + 1))?;
+    if values.len() % 2 != 0 {
+      return Err(format!("line {}: odd number of coordinates", number + 1));
+    }
+    openings.push(Opening {
+      name: format!("line {}", number + 1),
+      moves: values.chunks(2).map(|p| (p[0], p[1])).collect(),
+    });
+  }
+  Ok(openings)
+}
+
+/// Load the suite named by `spec`: `renju` for the built-in Renju openings, otherwise
+/// the path of an opening file.
+pub fn load_openings(spec: &str) -> io::Result<Vec<Opening>> {
+  if spec == "renju" {
+    return Ok(renju_openings());
+  }
+  let text = fs::read_to_string(spec)?;
+  parse_openings(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", spec, e)))
+}
+
+/// Check that every opening can be played on a board of `size` under `rule`.
+pub fn check_openings(openings: &[Opening], size: usize, rule: Rule) -> Result<(), String> {
+  match openings.iter().find(|o| !o.place(&mut Board::with_rule(size, rule))) {
+    Some(bad) => Err(format!(
+      "opening '{}' does not fit a {}x{} {:?} board",
+      bad.name, size, size, rule
+    )),
+    None => Ok(()),
+  }
+}
+
+/// `count` openings of `moves` stones each, placed at random within two cells of the
+/// centre. Deterministic for a seed.
+pub fn random_openings(count: usize, moves: usize, size: usize, rule: Rule, seed: u64) -> Vec<Opening> {
+  let mut rng = StdRng::seed_from_u64(seed);
+  let center = size / 2;
+  let radius = 2.min(center);
+  let area = (2 * radius + 1).pow(2);
+  (0..count)
+    .map(|i| {
+      let mut board = Board::with_rule(size, rule);
+      let mut role = Role::Black;
+      while board.history.len() < moves.min(area) {
+        let x = rng.gen_range(center - radius..=center + radius);
+        let y = rng.gen_range(center - radius..=center + radius);
+        // Occupied cells and Renju forbidden points are rejected by `put`
+        if board.put(x, y, role) {
+          role = role.opponent();
+        }
+      }
+      Opening {
+        name: format!("random {}", i + 1),
+        moves: board
+          .history
+          .iter()
+          .map(|&(x, y, _)| (x as i32 - center as i32, y as i32 - center as i32))
+          .collect(),
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_renju_openings() {
+    let openings = renju_openings();
+    assert_eq!(openings.len(), 26);
+    assert_eq!(openings[0].name, "D1 Kansei");
+    assert_eq!(openings[0].moves[2], (0, -2));
+    assert_eq!(openings[3].name, "D4 Kagetsu");
+    assert_eq!(openings[3].moves[2], (1, -1));
+    assert_eq!(openings[25].name, "I13 Kisei");
+    assert_eq!(openings[25].moves[2], (-2, 2));
+    for opening in &openings {
+      let mut board = Board::with_rule(15, Rule::Renju);
+      assert!(opening.place(&mut board), "{}", opening.name);
+      assert_eq!(board.history.len(), 3);
+    }
+    // No two openings are mirror images of each other
+    let mirrored = |o: &Opening| {
+      let (wx, _) = o.moves[1];
+      let (dx, dy) = o.moves[2];
+      if wx == 0 {
+        (-dx, dy)
+      } else {
+        (-dy, -dx)
+      }
+    };
+    for a in &openings {
+      assert!(openings
+        .iter()
+        .all(|b| b.moves[1] != a.moves[1] || b.moves[2] != mirrored(a) || b == a));
+    }
+  }
+
+  #[test]
+  fn test_parse_openings() {
+    let openings = parse_openings("# sample\n0, 0, 1, 0\n\n-1,-1, 0,1, 2,2\n").unwrap();
+    assert_eq!(openings.len(), 2);
+    assert_eq!(openings[0].moves, vec![(0, 0), (1, 0)]);
+    assert_eq!(openings[1].moves_on(15), Some(vec![(6, 6), (7, 8), (9, 9)]));
+    assert_eq!(openings[1].moves_on(3), None);
+    assert!(parse_openings("0, 0, 1").is_err());
+    assert!(parse_openings("a, b").is_err());
+    assert!(check_openings(&openings, 15, Rule::Freestyle).is_ok());
+    assert!(check_openings(&openings, 3, Rule::Freestyle).is_err());
+  }
+
+  #[test]
+  fn test_random_openings() {
+    let openings = random_openings(5, 3, 15, Rule::Freestyle, 1);
+    assert_eq!(openings, random_openings(5, 3, 15, Rule::Freestyle, 1));
+    for opening in &openings {
+      assert_eq!(opening.moves.len(), 3);
+      assert!(opening.moves.iter().all(|&(dx, dy)| dx.abs() <= 2 && dy.abs() <= 2));
+    }
+  }
+}
@@ -1,3 +1,6 @@
+use std::ops::Index;
+use std::sync::OnceLock;
+
 pub const GOMOKU_PATTERNS: &[(i32, &[i32], i32)] = &[
   (0, &[0, 1, 1, 1, 1], 4_000_000),    // FIVE
   (1, &[1, 0, 1, 1, 1], 4_000_000),    // FIVE
@@ -35,3 +38,210 @@ pub const GOMOKU_PATTERNS: &[(i32, &[i32], i32)] = &[
   (1, &[0, 0, 2], 2),                  // SEMIOPEN_ONE
   (1, &[2, 0, 0], 2),                  // SEMIOPEN_ONE
 ];
+
+/// Cells on each side of the activation point that a pattern can look at: a five
+/// activated at one of its ends reaches 4 cells away, and the overline check one more.
+pub const WINDOW_RADIUS: usize = 5;
+/// Number of line windows: every cell but the activation point is empty (0), own (1)
+/// or opponent/wall (2).
+const WINDOW_COUNT: usize = 3usize.pow(2 * WINDOW_RADIUS as u32);
+const NO_SHAPE: u8 = u8::MAX;
+
+/// Five patterns are the only ones with exactly four own stones in five cells.
+pub fn is_five_pattern(pattern_vec: &[i32]) -> bool {
+  pattern_vec.len() == 5 && pattern_vec.iter().filter(|&&c| c == 1).count() == 4
+}
+
+/// Scores of every line window around an empty cell, precomputed from a pattern list.
+///
+/// A window is indexed by its 10 cells in base 3, nearest-to-farthest on the negative
+/// side first, then on the positive side (see `window_index`). There is one table per
+/// combination of the two switches `Board::find_best_pattern_in_dir` applies: whether a
+/// five that joins more stones is an overline, and whether patterns cheaper than 200
+/// are skipped.
+#[derive(Debug)]
+pub struct PatternTable {
+  pub patterns: &'static [(i32, &'static [i32], i32)],
+  /// `entries[exact_five][skip_cheap][window]` = (index of the most expensive matched
+  /// pattern or `NO_SHAPE`, sum of the matched costs).
+  entries: [[Vec<(u8, i32)>; 2]; 2],
+}
+
+impl PatternTable {
+  pub fn new(patterns: &'static [(i32, &'static [i32], i32)]) -> Self {
+    assert!(patterns.len() < NO_SHAPE as usize, "too many patterns for the table");
+    for &(act_idx, pattern_vec, _) in patterns {
+      assert!(
+        act_idx >= 0 && (act_idx as usize) < pattern_vec.len() && pattern_vec[act_idx as usize] == 0,
+        "a pattern is activated on an empty cell"
+      );
+      assert!(
+        act_idx as usize <= WINDOW_RADIUS && pattern_vec.len() - act_idx as usize <= WINDOW_RADIUS,
+        "pattern longer than the window"
+      );
+    }
+
+    let mut entries: [[Vec<(u8, i32)>; 2]; 2] = Default::default();
+    for row in entries.iter_mut() {
+      for table in row.iter_mut() {
+        table.reserve_exact(WINDOW_COUNT);
+      }
+    }
+    for window in 0..WINDOW_COUNT {
+      let cells = window_cells(window);
+      for (exact_five, row) in entries.iter_mut().enumerate() {
+        for (skip_cheap, table) in row.iter_mut().enumerate() {
+          table.push(score_window(patterns, &cells, exact_five == 1, skip_cheap == 1));
+        }
+      }
+    }
+    Self { patterns, entries }
+  }
+
+  /// The table of `GOMOKU_PATTERNS`, built on first use.
+  pub fn gomoku() -> &'static Self {
+    static TABLE: OnceLock<PatternTable> = OnceLock::new();
+    TABLE.get_or_init(|| PatternTable::new(GOMOKU_PATTERNS))
+  }
+
+  /// `(best pattern index, total cost)` of the window with index `window`.
+  pub fn lookup(&self, window: usize, exact_five: bool, skip_cheap: bool) -> (Option<usize>, i32) {
+    let (best, cost) = self.entries[exact_five as usize][skip_cheap as usize][window];
+    ((best != NO_SHAPE).then_some(best as usize), cost)
+  }
+}
+
+impl Index<usize> for PatternTable {
+  type Output = (i32, &'static [i32], i32);
+
+  fn index(&self, index: usize) -> &Self::Output {
+    &self.patterns[index]
+  }
+}
+
+/// Index of a window from the pattern value (0 / 1 / 2) of the cell `offset` steps away
+/// from the activation point, for `offset` in `-5..=-1` and `1..=5`.
+#[cfg(test)]
+pub fn window_index(mut cell: impl FnMut(i32) -> i32) -> usize {
+  let radius = WINDOW_RADIUS as i32;
+  (1..=radius)
+    .map(|step| -step)
+    .chain(1..=radius)
+    .fold(0, |index, offset| index * 3 + cell(offset) as usize)
+}
+
+/// Weight of each bit of a window without its activation point in `window_index`:
+/// bits 0..5 are offsets -5..=-1, bits 5..10 offsets 1..=5.
+const BIT_WEIGHTS: [usize; 2 * WINDOW_RADIUS] = {
+  let mut weights = [0; 2 * WINDOW_RADIUS];
+  let mut bit = 0;
+  while bit < WINDOW_RADIUS {
+    weights[bit] = 3usize.pow((WINDOW_RADIUS + bit) as u32);
+    weights[WINDOW_RADIUS + bit] = 3usize.pow((WINDOW_RADIUS - 1 - bit) as u32);
+    bit += 1;
+  }
+  weights
+};
+
+/// `window_index` value of every combination of the 10 window bits.
+const MASK_WEIGHTS: [usize; 1 << (2 * WINDOW_RADIUS)] = {
+  let mut table = [0; 1 << (2 * WINDOW_RADIUS)];
+  let mut mask = 0;
+  while mask < table.len() {
+    let mut bit = 0;
+    while bit < 2 * WINDOW_RADIUS {
+      if mask & (1 << bit) != 0 {
+        table[mask] += BIT_WEIGHTS[bit];
+      }
+      bit += 1;
+    }
+    mask += 1;
+  }
+  table
+};
+
+/// `window_index` of a window read from line bitmasks (see `LineMasks::window`): bit
+/// `WINDOW_RADIUS + offset` of `own` / `blocked` is set for an own / opponent or wall
+/// cell `offset` steps away. The activation point's bit is ignored.
+pub fn window_index_from_masks(own: u32, blocked: u32) -> usize {
+  // Drop the activation point between the two sides
+  let squeeze = |mask: u32| {
+    let low = mask & ((1 << WINDOW_RADIUS) - 1);
+    let high = (mask >> (WINDOW_RADIUS + 1)) & ((1 << WINDOW_RADIUS) - 1);
+    (low | high << WINDOW_RADIUS) as usize
+  };
+  MASK_WEIGHTS[squeeze(own)] + 2 * MASK_WEIGHTS[squeeze(blocked)]
+}
+
+/// The 11 cells of window `window`, activation point (empty) in the middle.
+fn window_cells(mut window: usize) -> [i32; 2 * WINDOW_RADIUS + 1] {
+  let mut cells = [0; 2 * WINDOW_RADIUS + 1];
+  // Undo `window_index`: the last digit is the farthest cell on the positive side
+  let offsets = (1..=WINDOW_RADIUS)
+    .map(|step| WINDOW_RADIUS - step)
+    .chain(WINDOW_RADIUS + 1..=2 * WINDOW_RADIUS);
+  for position in offsets.rev() {
+    cells[position] = (window % 3) as i32;
+    window /= 3;
+  }
+  cells
+}
+
+/// Sum of the matching patterns' costs, the way `Board::find_best_pattern_in_dir` used
+/// to scan them: the most expensive pattern wins ties by coming first.
+fn score_window(patterns: &[(i32, &[i32], i32)], cells: &[i32], exact_five: bool, skip_cheap: bool) -> (u8, i32) {
+  let center = WINDOW_RADIUS as i32;
+  let mut best_cost = 0;
+  let mut best_shape = NO_SHAPE;
+  let mut sum_cost = 0;
+  for (i_pattern, &(act_idx, pattern_vec, cost)) in patterns.iter().enumerate() {
+    if skip_cheap && cost < 200 {
+      continue;
+    }
+    let start = center - act_idx;
+    let matches = pattern_vec
+      .iter()
+      .enumerate()
+      .all(|(i, &value)| cells[(start + i as i32) as usize] == value);
+    if !matches {
+      continue;
+    }
+    // Under exact-five rules a "five" that joins more stones is an overline, not a win
+    if is_five_pattern(pattern_vec) && exact_five {
+      let before = start - 1;
+      let after = start + pattern_vec.len() as i32;
+      let own = |position: i32| (0..cells.len() as i32).contains(&position) && cells[position as usize] == 1;
+      if own(before) || own(after) {
+        continue;
+      }
+    }
+    sum_cost += cost;
+    if cost > best_cost {
+      best_cost = cost;
+      best_shape = i_pattern as u8;
+    }
+  }
+  (best_shape, sum_cost)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_masks_index_every_window() {
+    for window in 0..WINDOW_COUNT {
+      let cells = window_cells(window);
+      let mask = |value: i32| {
+        cells
+          .iter()
+          .enumerate()
+          .filter(|&(_, &cell)| cell == value)
+          .fold(0, |mask, (bit, _)| mask | 1 << bit)
+      };
+      let center = WINDOW_RADIUS as i32;
+      assert_eq!(window_index(|offset| cells[(center + offset) as usize]), window);
+      assert_eq!(window_index_from_masks(mask(1), mask(2)), window);
+    }
+  }
+}
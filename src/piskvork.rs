@@ -0,0 +1,381 @@
+use crate::board::{Board, Rule};
+use crate::engine::{Engine, SearchLimits};
+use crate::player::Role;
+use crate::time_manager::TimeControl;
+use log::info;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+/// Limits and settings announced by the manager with `INFO` commands.
+/// Times are in milliseconds, memory in bytes, 0 means "no limit".
+#[derive(Debug, Clone, Default)]
+pub struct BrainInfo {
+  pub timeout_turn: u64,
+  pub timeout_match: u64,
+  pub max_memory: u64,
+  pub time_left: u64,
+  pub game_type: u64,
+}
+
+/// A Gomocup brain speaking the Piskvork protocol over any line-based reader/writer
+/// (stdin/stdout in production, in-memory buffers in tests).
+///
+/// Coordinates are `x,y` with x being the column, exactly as on `Board`.
+pub struct PiskvorkBrain<R: BufRead, W: Write> {
+  input: R,
+  output: W,
+  board: Board,
+  rule: Rule,
+  engine: Box<dyn Engine>,
+  /// Time limits and game clock, updated by `INFO` commands.
+  limits: SearchLimits,
+  /// Colour of our stones, known once the first move of the game is seen.
+  own_role: Option<Role>,
+  pub info: BrainInfo,
+}
+
+impl<R: BufRead, W: Write> PiskvorkBrain<R, W> {
+  pub fn new(input: R, output: W, engine: Box<dyn Engine>, rule: Rule) -> Self {
+    Self {
+      input,
+      output,
+      board: Board::with_rule(15, rule),
+      rule,
+      engine,
+      limits: SearchLimits::default(),
+      own_role: None,
+      info: BrainInfo::default(),
+    }
+  }
+
+  /// Default time limits, used until the manager announces its own with `INFO`.
+  pub fn set_time_limits(&mut self, per_move: Option<Duration>, control: Option<TimeControl>) {
+    self.limits = SearchLimits::new(per_move, control);
+  }
+
+  /// Process commands until `END` or end of input.
+  pub fn run(&mut self) -> std::io::Result<()> {
+    let mut line = String::new();
+    loop {
+      line.clear();
+      if self.input.read_line(&mut line)? == 0 {
+        return Ok(());
+      }
+      let command = line.trim();
+      if command.is_empty() {
+        continue;
+      }
+      info!("piskvork <- {}", command);
+      if !self.handle_command(command)? {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Handle one command line. Returns `false` when the brain should exit.
+  fn handle_command(&mut self, command: &str) -> std::io::Result<bool> {
+    let (name, args) = match command.split_once(char::is_whitespace) {
+      Some((name, args)) => (name.to_ascii_uppercase(), args.trim()),
+      None => (command.to_ascii_uppercase(), ""),
+    };
+
+    match name.as_str() {
+      "START" => match args.parse::<usize>() {
+        Ok(size) if (5..=32).contains(&size) => {
+          self.board = Board::with_rule(size, self.rule);
+          self.own_role = None;
+          self.engine.new_game(size, self.rule);
+          self.respond("OK")?;
+        }
+        _ => self.respond(&format!("ERROR unsupported size {}", args))?,
+      },
+      "RESTART" => {
+        self.board = Board::with_rule(self.board.size, self.rule);
+        self.own_role = None;
+        self.engine.new_game(self.board.size, self.rule);
+        self.respond("OK")?;
+      }
+      "BEGIN" => {
+        self.own_role = Some(Role::Black);
+        self.play()?;
+      }
+      "TURN" => match self.parse_point(args) {
+        Some((x, y)) => {
+          let own = *self.own_role.get_or_insert(Role::White);
+          if self.board.put(x, y, own.opponent()) {
+            self.play()?;
+          } else {
+            self.respond(&format!("ERROR invalid move {}", args))?;
+          }
+        }
+        None => self.respond(&format!("ERROR bad coordinates {}", args))?,
+      },
+      "BOARD" => self.read_board()?,
+      "INFO" => self.handle_info(args),
+      "TAKEBACK" => match self.parse_point(args) {
+        Some((x, y)) if matches!(self.board.history.last(), Some(&(lx, ly, _)) if (lx, ly) == (x, y)) => {
+          self.board.undo();
+          self.respond("OK")?;
+        }
+        _ => self.respond(&format!("ERROR cannot take back {}", args))?,
+      },
+      "ABOUT" => {
+        let about = format!(
+          "name=\"{}\", version=\"{}\"",
+          env!("CARGO_PKG_NAME"),
+          env!("CARGO_PKG_VERSION")
+        );
+        self.respond(&about)?;
+      }
+      "END" => return Ok(false),
+      _ => self.respond(&format!("UNKNOWN command {}", name))?,
+    }
+    Ok(true)
+  }
+
+  /// `BOARD` is followed by `x,y,field` lines and terminated with `DONE`.
+  /// Field 1 is our stone, 2 the opponent's, 3 a winning-line marker of continuous games.
+  /// The stones come in the order they were played and are replayed in that order, so
+  /// Renju checks see every Black stone at its real ply.
+  fn read_board(&mut self) -> std::io::Result<()> {
+    let mut stones = Vec::new();
+    let mut valid = true;
+    let mut line = String::new();
+    loop {
+      line.clear();
+      if self.input.read_line(&mut line)? == 0 {
+        return Ok(());
+      }
+      let entry = line.trim();
+      if entry.eq_ignore_ascii_case("DONE") {
+        break;
+      }
+      let fields: Vec<&str> = entry.split(',').map(str::trim).collect();
+      match fields.as_slice() {
+        [x, y, field] => match (x.parse::<usize>(), y.parse::<usize>(), *field) {
+          (Ok(x), Ok(y), "1") => stones.push((x, y, true)),
+          (Ok(x), Ok(y), "2") => stones.push((x, y, false)),
+          (Ok(_), Ok(_), "3") => {}
+          _ => valid = false,
+        },
+        _ => valid = false,
+      }
+    }
+
+    // The player to move now started the game iff both sides have the same stone count
+    let own_count = stones.iter().filter(|&&(_, _, own)| own).count();
+    let own_role = if 2 * own_count == stones.len() {
+      Role::Black
+    } else {
+      Role::White
+    };
+    self.board = Board::with_rule(self.board.size, self.rule);
+    self.own_role = Some(own_role);
+
+    for (x, y, own) in stones {
+      let role = if own { own_role } else { own_role.opponent() };
+      valid &= self.board.put(x, y, role);
+    }
+
+    if valid {
+      self.play()
+    } else {
+      self.respond("ERROR invalid BOARD position")
+    }
+  }
+
+  fn handle_info(&mut self, args: &str) {
+    let (key, value) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let value = value.trim();
+    let number = value.parse::<u64>().unwrap_or(0);
+    match key.to_ascii_lowercase().as_str() {
+      "timeout_turn" => {
+        // 0 means "play as fast as possible"
+        self.info.timeout_turn = number;
+        self.limits.per_move = Some(Duration::from_millis(number));
+      }
+      "timeout_match" => {
+        // 0 means no match time limit
+        self.info.timeout_match = number;
+        if number == 0 {
+          self.limits.remaining = None;
+        }
+      }
+      "max_memory" => {
+        // 0 means no limit. Otherwise give the table a quarter of it: every
+        // `Board` keeps its own caches as well.
+        self.info.max_memory = number;
+        if number > 0 {
+          self.engine.set_hash_size(((number / 4) >> 20).max(1) as usize);
+        }
+      }
+      "time_left" => {
+        self.info.time_left = number;
+        if self.info.timeout_match != 0 {
+          self.limits.remaining = Some(Duration::from_millis(number));
+        }
+      }
+      "game_type" => self.info.game_type = number,
+      "rule" => {
+        // Bit 1: exactly five, bit 4: renju (bit 2 "continuous game" is not supported)
+        self.rule = if number & 4 != 0 {
+          Rule::Renju
+        } else if number & 1 != 0 {
+          Rule::Standard
+        } else {
+          Rule::Freestyle
+        };
+        // The rule is fixed for a game; apply it right away only to an empty board
+        if self.board.history.is_empty() {
+          self.board = Board::with_rule(self.board.size, self.rule);
+        }
+      }
+      // Other keys (folder, evaluate, ...) are informational
+      _ => {}
+    }
+  }
+
+  /// Think, place our stone and report it.
+  fn play(&mut self) -> std::io::Result<()> {
+    let role = *self.own_role.get_or_insert(Role::Black);
+    let started = Instant::now();
+    let best = self.engine.think(&self.board, role, &self.limits).best_move;
+    self.limits.charge(started.elapsed());
+
+    let chosen = best
+      .filter(|&(x, y)| self.board.put(x, y, role))
+      .or_else(|| self.fallback_move(role));
+
+    match chosen {
+      Some((x, y)) => self.respond(&format!("{},{}", x, y)),
+      None => self.respond("ERROR no legal move"),
+    }
+  }
+
+  /// First legal cell in the move ordering, used if the search returns nothing usable.
+  fn fallback_move(&mut self, role: Role) -> Option<(usize, usize)> {
+    let moves = self.board.get_moves(role, 0, false, false);
+    moves.into_iter().find(|&(x, y)| self.board.put(x, y, role))
+  }
+
+  fn parse_point(&self, args: &str) -> Option<(usize, usize)> {
+    let (x, y) = args.split_once(',')?;
+    let x = x.trim().parse::<usize>().ok()?;
+    let y = y.trim().parse::<usize>().ok()?;
+    (x < self.board.size && y < self.board.size).then_some((x, y))
+  }
+
+  fn respond(&mut self, message: &str) -> std::io::Result<()> {
+    info!("piskvork -> {}", message);
+    writeln!(self.output, "{}", message)?;
+    self.output.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ai::AIEngine;
+  use std::io::Cursor;
+
+  /// Feed a script to a brain and return its non-empty output lines.
+  fn run_script(script: &str) -> Vec<String> {
+    run_script_with_rule(script, Rule::Freestyle)
+  }
+
+  fn run_script_with_rule(script: &str, rule: Rule) -> Vec<String> {
+    let mut output = Vec::new();
+    let mut brain = PiskvorkBrain::new(Cursor::new(script.to_string()), &mut output, Box::new(AIEngine::new(1)), rule);
+    brain.run().unwrap();
+    String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+  }
+
+  fn parse_move(line: &str) -> (usize, usize) {
+    let (x, y) = line.split_once(',').expect("expected a move");
+    (x.parse().unwrap(), y.parse().unwrap())
+  }
+
+  #[test]
+  fn test_start_begin_turn() {
+    let out = run_script("START 15\nBEGIN\nTURN 0,0\nEND\n");
+    assert_eq!(out[0], "OK");
+    assert_eq!(parse_move(&out[1]), (7, 7), "first move goes to the centre");
+    let (x, y) = parse_move(&out[2]);
+    assert!(x < 15 && y < 15 && (x, y) != (7, 7) && (x, y) != (0, 0));
+    assert_eq!(out.len(), 3);
+  }
+
+  #[test]
+  fn test_start_rejects_bad_size() {
+    let out = run_script("START 2\nEND\n");
+    assert!(out[0].starts_with("ERROR"));
+  }
+
+  #[test]
+  fn test_board_blocks_four() {
+    // Opponent (2) has four in a row with one end open; we must block at 5,3
+    let out = run_script("START 15\nBOARD\n1,3,2\n2,3,2\n3,3,2\n4,3,2\n0,3,1\n9,9,1\n6,6,1\nDONE\nEND\n");
+    assert_eq!(out[0], "OK");
+    assert_eq!(parse_move(&out[1]), (5, 3));
+  }
+
+  #[test]
+  fn test_board_completes_five() {
+    let out = run_script("START 15\nBOARD\n1,1,1\n2,2,1\n3,3,1\n4,4,1\n9,0,2\n9,1,2\n9,3,2\n12,12,2\nDONE\nEND\n");
+    let mv = parse_move(&out[1]);
+    assert!(mv == (5, 5) || mv == (0, 0), "expected a winning move, got {:?}", mv);
+  }
+
+  #[test]
+  fn test_board_keeps_the_order_of_play() {
+    // White's 4,7 came before Black's 7,7, which is then no double-three. Taking the
+    // colours in turns instead would put 4,7 after 7,7 and reject the position.
+    let stones = "5,7,1\n0,0,2\n6,7,1\n0,14,2\n7,5,1\n14,0,2\n7,6,1\n14,14,2\n4,7,2\n7,7,1\n";
+    let out = run_script_with_rule(&format!("START 15\nBOARD\n{}DONE\nEND\n", stones), Rule::Renju);
+    assert!(!out[1].starts_with("ERROR"), "{:?}", out);
+    parse_move(&out[1]);
+  }
+
+  #[test]
+  fn test_turn_on_occupied_cell_is_an_error() {
+    let out = run_script("START 15\nBEGIN\nTURN 7,7\nEND\n");
+    assert!(out[2].starts_with("ERROR"));
+  }
+
+  #[test]
+  fn test_takeback_and_restart() {
+    let out = run_script("START 15\nBEGIN\nTAKEBACK 7,7\nTAKEBACK 7,7\nRESTART\nBEGIN\nEND\n");
+    assert_eq!(out[1], "7,7");
+    assert_eq!(out[2], "OK");
+    assert!(out[3].starts_with("ERROR"), "nothing left to take back");
+    assert_eq!(out[4], "OK");
+    assert_eq!(out[5], "7,7", "board is empty after RESTART");
+  }
+
+  #[test]
+  fn test_info_about_and_unknown() {
+    let mut output = Vec::new();
+    let script = "INFO timeout_turn 5000\nINFO timeout_match 180000\nINFO max_memory 83886080\nINFO time_left 170000\nINFO rule 1\nINFO folder /tmp\nSTART 20\nABOUT\nFOO\nEND\n";
+    let mut brain = PiskvorkBrain::new(Cursor::new(script), &mut output, Box::new(AIEngine::new(1)), Rule::Freestyle);
+    brain.run().unwrap();
+    assert_eq!(brain.info.timeout_turn, 5000);
+    assert_eq!(brain.info.timeout_match, 180000);
+    assert_eq!(brain.info.max_memory, 83886080);
+    assert_eq!(brain.info.time_left, 170000);
+    assert_eq!(brain.rule, Rule::Standard);
+    assert_eq!(brain.board.rule, Rule::Standard);
+    assert_eq!(brain.board.size, 20);
+
+    let out = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "OK");
+    assert!(lines[1].starts_with("name=\"gomoku_rust\""));
+    assert!(lines[2].starts_with("UNKNOWN"));
+  }
+
+  #[test]
+  fn test_end_stops_processing() {
+    let out = run_script("END\nSTART 15\n");
+    assert!(out.is_empty());
+  }
+}
@@ -1,3 +1,5 @@
+use crate::engine::EngineConfig;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlayerType {
   Human, // Represents a human player
@@ -29,13 +31,11 @@ impl Role {
   }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Player {
   pub player_type: PlayerType, // Type of player (Human or AI)
   pub role: Role,              // Role of the player (Black or White)
 
-  // Parameters for AI, search depth
-  pub depth: i32, // Depth of search for AI
-                  // Other settings for AI can be stored
-                  // (e.g., heuristics, cache, ...)
+  // Engine for AI with its own settings (search depth, playouts, ...)
+  pub engine: EngineConfig,
 }
@@ -0,0 +1,514 @@
+use crate::board::{Board, Rule};
+use crate::engine::StopHandle;
+use crate::player::Role;
+use crate::vcf::four_moves;
+use std::time::Instant;
+
+const ALL_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+const INF: u32 = u32::MAX;
+
+/// Default budget of a proof-number search, in tree nodes.
+pub const DEFAULT_PN_NODES: usize = 3_000;
+/// Most attacker threats tried along one line.
+const MAX_PN_DEPTH: usize = 10;
+
+/// Outcome of a threat-space (VCT) proof-number search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PnResult {
+  /// The attacker wins by continuous threats. The principal line alternates attacker
+  /// threats and defender replies.
+  ProvenWin(Vec<(usize, usize)>),
+  /// Disproved: every threat sequence of the attacker is refuted.
+  ProvenLoss,
+  /// The budget ran out, or the disproof relied on the depth limit.
+  Unknown,
+}
+
+/// Kind of threat a move creates on one line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Threat {
+  /// Threatens five next move.
+  Four,
+  /// One move from a four that cannot be stopped (an open three, `_XXX_` and the like).
+  Three,
+}
+
+#[derive(Debug)]
+struct Node {
+  mv: Option<(usize, usize)>,
+  /// OR node: the attacker is to move. AND node: the defender is.
+  is_or: bool,
+  pn: u32,
+  dn: u32,
+  children: Vec<usize>,
+  expanded: bool,
+  /// Attacker threats played to reach this node.
+  depth: usize,
+}
+
+/// Proof-number search over threat moves: the attacker only plays fours and threes,
+/// the defender answers with the moves that stop them or with fours of its own.
+///
+/// The tree lives in an arena; the board follows the path to the node being expanded
+/// with `put`/`undo` and is restored after every iteration.
+pub struct ProofNumberSearch {
+  max_nodes: usize,
+  deadline: Option<Instant>,
+  stop: StopHandle,
+  nodes: Vec<Node>,
+  /// A leaf was disproved only because it hit `MAX_PN_DEPTH`.
+  depth_cut: bool,
+}
+
+impl ProofNumberSearch {
+  pub fn new(max_nodes: usize) -> Self {
+    Self {
+      max_nodes,
+      deadline: None,
+      stop: StopHandle::default(),
+      nodes: Vec::new(),
+      depth_cut: false,
+    }
+  }
+
+  /// Also stop at `deadline`, if any.
+  pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+    self.deadline = deadline;
+    self
+  }
+
+  /// Also stop when `stop` is raised from another thread.
+  pub fn with_stop(mut self, stop: StopHandle) -> Self {
+    self.stop = stop;
+    self
+  }
+
+  /// Prove or disprove a win by threats for `attacker`, who is to move.
+  pub fn solve(&mut self, board: &mut Board, attacker: Role) -> PnResult {
+    self.nodes.clear();
+    self.depth_cut = false;
+    if let Some(&five) = board.five_points(attacker).first() {
+      return PnResult::ProvenWin(vec![five]);
+    }
+    self.nodes.push(Node {
+      mv: None,
+      is_or: true,
+      pn: 1,
+      dn: 1,
+      children: Vec::new(),
+      expanded: false,
+      depth: 0,
+    });
+
+    while self.nodes[0].pn != 0 && self.nodes[0].dn != 0 {
+      if self.nodes.len() >= self.max_nodes || self.deadline.is_some_and(|d| Instant::now() >= d) || self.stop.is_stopped() {
+        return PnResult::Unknown;
+      }
+      // Walk down to the most-proving node
+      let mut path = vec![0];
+      let mut current = 0;
+      while self.nodes[current].expanded {
+        current = self.most_proving_child(current);
+        let (x, y) = self.nodes[current].mv.expect("only the root has no move");
+        board.put(x, y, self.mover(current, attacker));
+        path.push(current);
+      }
+
+      self.expand(board, attacker, current);
+
+      for &index in path.iter().rev() {
+        self.update(index);
+      }
+      for _ in 1..path.len() {
+        board.undo();
+      }
+    }
+
+    if self.nodes[0].pn == 0 {
+      PnResult::ProvenWin(self.principal_line(board, attacker))
+    } else if self.depth_cut {
+      PnResult::Unknown
+    } else {
+      PnResult::ProvenLoss
+    }
+  }
+
+  /// Role that played the move leading to `index`.
+  fn mover(&self, index: usize, attacker: Role) -> Role {
+    if self.nodes[index].is_or {
+      attacker.opponent()
+    } else {
+      attacker
+    }
+  }
+
+  fn most_proving_child(&self, index: usize) -> usize {
+    let node = &self.nodes[index];
+    let key = |&&c: &&usize| {
+      if node.is_or {
+        self.nodes[c].pn
+      } else {
+        self.nodes[c].dn
+      }
+    };
+    *node.children.iter().min_by_key(key).expect("expanded nodes have children")
+  }
+
+  fn update(&mut self, index: usize) {
+    let node = &self.nodes[index];
+    if !node.expanded {
+      return;
+    }
+    let pns = node.children.iter().map(|&c| self.nodes[c].pn);
+    let dns = node.children.iter().map(|&c| self.nodes[c].dn);
+    let (pn, dn) = if node.is_or {
+      (pns.min().unwrap_or(INF), dns.fold(0, u32::saturating_add))
+    } else {
+      (pns.fold(0, u32::saturating_add), dns.min().unwrap_or(INF))
+    };
+    self.nodes[index].pn = pn;
+    self.nodes[index].dn = dn;
+  }
+
+  /// Generate the children of `index` (the board is at its position) and score them.
+  fn expand(&mut self, board: &mut Board, attacker: Role, index: usize) {
+    let is_or = self.nodes[index].is_or;
+    let depth = self.nodes[index].depth;
+    let (mover, moves) = if is_or {
+      (attacker, attacker_moves(board, attacker))
+    } else {
+      (attacker.opponent(), defender_moves(board, attacker))
+    };
+
+    let mut children = Vec::new();
+    for (x, y) in moves {
+      if !board.put(x, y, mover) {
+        continue;
+      }
+      let child_depth = if is_or { depth + 1 } else { depth };
+      let (pn, dn) = self.evaluate_child(board, attacker, !is_or, child_depth);
+      board.undo();
+      children.push(self.nodes.len());
+      self.nodes.push(Node {
+        mv: Some((x, y)),
+        is_or: !is_or,
+        pn,
+        dn,
+        children: Vec::new(),
+        expanded: false,
+        depth: child_depth,
+      });
+    }
+
+    let node = &mut self.nodes[index];
+    node.expanded = true;
+    node.children = children;
+    if node.children.is_empty() {
+      // No threat left for the attacker, or no way for the defender to stop it
+      (node.pn, node.dn) = if is_or { (INF, 0) } else { (0, INF) };
+    }
+  }
+
+  /// Proof and disproof numbers of a freshly created node.
+  fn evaluate_child(&mut self, board: &mut Board, attacker: Role, is_or: bool, depth: usize) -> (u32, u32) {
+    let defender = attacker.opponent();
+    let winner = board.get_winner();
+    if winner == attacker.to_int() {
+      return (0, INF);
+    }
+    if winner == defender.to_int() {
+      return (INF, 0);
+    }
+    if is_or {
+      // The defender just moved
+      if !board.five_points(attacker).is_empty() {
+        return (0, INF);
+      }
+      if board.five_points(defender).len() > 1 {
+        return (INF, 0);
+      }
+    } else {
+      // The attacker just moved
+      if !board.five_points(defender).is_empty() {
+        return (INF, 0);
+      }
+      if board.five_points(attacker).len() > 1 {
+        return (0, INF);
+      }
+      if depth >= MAX_PN_DEPTH {
+        self.depth_cut = true;
+        return (INF, 0);
+      }
+    }
+    (1, 1)
+  }
+
+  /// Follow proven children from the root. The line ends with the attacker's five when
+  /// the proof ends on one, otherwise with the threat the defender cannot meet.
+  fn principal_line(&self, board: &mut Board, attacker: Role) -> Vec<(usize, usize)> {
+    let mut line = Vec::new();
+    let mut current = 0;
+    while let Some(&next) = self.nodes[current].children.iter().find(|&&c| self.nodes[c].pn == 0) {
+      let (x, y) = self.nodes[next].mv.expect("only the root has no move");
+      board.put(x, y, self.mover(next, attacker));
+      line.push((x, y));
+      current = next;
+    }
+    let played = line.len();
+    if self.nodes[current].is_or && board.get_winner() == 0 {
+      line.extend(board.five_points(attacker).first());
+    }
+    for _ in 0..played {
+      board.undo();
+    }
+    line
+  }
+}
+
+/// Threat moves of the attacker. Only the point stopping a defender's four is allowed
+/// when there is one, and it still has to be a threat itself.
+fn attacker_moves(board: &mut Board, attacker: Role) -> Vec<(usize, usize)> {
+  let blocks = board.five_points(attacker.opponent());
+  board
+    .get_moves(attacker, 0, true, false)
+    .into_iter()
+    .filter(|m| blocks.is_empty() || blocks.contains(m))
+    .filter(|&(x, y)| {
+      ALL_DIRECTIONS
+        .iter()
+        .any(|&dir| threat_at(board, x, y, dir, attacker).is_some())
+    })
+    .collect()
+}
+
+/// Defender replies to the attacker's last move: the block of a four, or every point
+/// that stops a three plus the defender's own fours.
+fn defender_moves(board: &mut Board, attacker: Role) -> Vec<(usize, usize)> {
+  let defender = attacker.opponent();
+  let fives = board.five_points(attacker);
+  if !fives.is_empty() {
+    return fives;
+  }
+  let Some(&(x, y, _)) = board.history.last() else {
+    return vec![];
+  };
+
+  let mut moves = Vec::new();
+  for dir in ALL_DIRECTIONS {
+    let mut line = Line::through(board, x, y, dir);
+    if line.threat(attacker, board) != Some(Threat::Three) {
+      continue;
+    }
+    for i in 0..line.cells.len() {
+      if line.cells[i] != 0 || i.abs_diff(line.origin) > 5 {
+        continue;
+      }
+      line.cells[i] = defender.to_int();
+      let stopped = line.winning_completion(attacker, board).is_none();
+      line.cells[i] = 0;
+      let point = line.point(i);
+      if stopped && !moves.contains(&point) {
+        moves.push(point);
+      }
+    }
+  }
+  for point in four_moves(board, defender) {
+    if !moves.contains(&point) {
+      moves.push(point);
+    }
+  }
+  moves
+}
+
+/// Threat `role` would make at the empty cell (x, y) along `dir`.
+fn threat_at(board: &Board, x: usize, y: usize, dir: (i32, i32), role: Role) -> Option<Threat> {
+  let mut line = Line::through(board, x, y, dir);
+  line.cells[line.origin] = role.to_int();
+  line.threat(role, board)
+}
+
+/// One full line of the board through a cell, for cheap "what if" checks.
+struct Line {
+  cells: Vec<i32>,
+  /// Board coordinates of `cells[0]` and the step between cells.
+  start: (i32, i32),
+  dir: (i32, i32),
+  /// Index of the cell the line was built through.
+  origin: usize,
+  rule: Rule,
+}
+
+impl Line {
+  fn through(board: &Board, x: usize, y: usize, dir: (i32, i32)) -> Self {
+    let inside = |px: i32, py: i32| px >= 0 && py >= 0 && px < board.size as i32 && py < board.size as i32;
+    let (mut sx, mut sy) = (x as i32, y as i32);
+    let mut origin = 0;
+    while inside(sx - dir.0, sy - dir.1) {
+      sx -= dir.0;
+      sy -= dir.1;
+      origin += 1;
+    }
+    let mut cells = Vec::new();
+    let (mut px, mut py) = (sx, sy);
+    while inside(px, py) {
+      cells.push(board.board[px as usize + 1][py as usize + 1]);
+      px += dir.0;
+      py += dir.1;
+    }
+    Self {
+      cells,
+      start: (sx, sy),
+      dir,
+      origin,
+      rule: board.rule,
+    }
+  }
+
+  fn point(&self, i: usize) -> (usize, usize) {
+    let i = i as i32;
+    (
+      (self.start.0 + i * self.dir.0) as usize,
+      (self.start.1 + i * self.dir.1) as usize,
+    )
+  }
+
+  /// Empty cells where `role` would complete a winning run.
+  fn five_points(&mut self, role: Role) -> Vec<usize> {
+    let role_val = role.to_int();
+    let mut points = Vec::new();
+    for i in 0..self.cells.len() {
+      if self.cells[i] != 0 {
+        continue;
+      }
+      let lo = self.cells[..i].iter().rev().take_while(|&&c| c == role_val).count();
+      let hi = self.cells[i + 1..].iter().take_while(|&&c| c == role_val).count();
+      if self.rule.is_win(lo + hi + 1, role_val) {
+        points.push(i);
+      }
+    }
+    points
+  }
+
+  /// A cell near the origin after which `role` has two ways to five on this line.
+  fn winning_completion(&mut self, role: Role, board: &Board) -> Option<usize> {
+    let renju_black = self.rule == Rule::Renju && role == Role::Black;
+    (0..self.cells.len()).find(|&i| {
+      if self.cells[i] != 0 || i.abs_diff(self.origin) > 4 {
+        return false;
+      }
+      self.cells[i] = role.to_int();
+      let wins = self.five_points(role).len() > 1;
+      self.cells[i] = 0;
+      // Renju: a three whose four point is forbidden for Black is no three
+      wins
+        && !(renju_black && {
+          let (px, py) = self.point(i);
+          board.is_forbidden(px, py)
+        })
+    })
+  }
+
+  /// Threat made by the `role` stone at the origin.
+  fn threat(&mut self, role: Role, board: &Board) -> Option<Threat> {
+    if self.five_points(role).iter().any(|&i| i.abs_diff(self.origin) <= 4) {
+      Some(Threat::Four)
+    } else if self.winning_completion(role, board).is_some() {
+      Some(Threat::Three)
+    } else {
+      None
+    }
+  }
+}
+
+impl Board {
+  /// Proof-number search for a win by continuous threats (VCT) for `role`, who is to move,
+  /// giving up early once `stop`, if any, is raised.
+  pub fn prove_vct(&mut self, role: Role, max_nodes: usize, deadline: Option<Instant>, stop: Option<&StopHandle>) -> PnResult {
+    let mut search = ProofNumberSearch::new(max_nodes).with_deadline(deadline);
+    if let Some(stop) = stop {
+      search = search.with_stop(stop.clone());
+    }
+    search.solve(self, role)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Replay a principal line: attacker and defender alternate from `attacker`.
+  fn replay(board: &mut Board, attacker: Role, line: &[(usize, usize)]) {
+    let mut role = attacker;
+    for &(x, y) in line {
+      assert!(board.put(x, y, role), "illegal move {:?} in {:?}", (x, y), line);
+      role = role.opponent();
+    }
+  }
+
+  #[test]
+  fn test_open_four_is_proven() {
+    let mut b = Board::with_stones(Rule::Freestyle, &[(5, 7), (6, 7), (7, 7)], &[(0, 0), (14, 14), (0, 14)]);
+    match b.prove_vct(Role::Black, 1_000, None, None) {
+      PnResult::ProvenWin(line) => {
+        assert!(line[0] == (4, 7) || line[0] == (8, 7), "{:?}", line);
+        assert_eq!(b.history.len(), 6, "board must be restored");
+      }
+      other => panic!("expected a proof, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_double_three_vct() {
+    // Two open twos crossing at (7,7): the double three there wins by threats
+    let black = [(5, 7), (6, 7), (7, 5), (7, 6)];
+    let white = [(0, 0), (14, 14), (0, 14), (14, 0)];
+    let mut b = Board::with_stones(Rule::Freestyle, &black, &white);
+    match b.prove_vct(Role::Black, 20_000, None, None) {
+      PnResult::ProvenWin(line) => {
+        replay(&mut b, Role::Black, &line);
+        let last = *line.last().unwrap();
+        assert!(
+          b.get_winner() == -1 || b.five_points(Role::Black).len() > 1,
+          "line {:?} ends at {:?}",
+          line,
+          last
+        );
+      }
+      other => panic!("expected a proof, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_no_threats_is_disproved() {
+    let mut b = Board::with_stones(Rule::Freestyle, &[(7, 7)], &[(8, 8)]);
+    assert_eq!(b.prove_vct(Role::Black, 1_000, None, None), PnResult::ProvenLoss);
+  }
+
+  #[test]
+  fn test_opponent_four_comes_first() {
+    // Black has an open three, but White's open four wins first
+    let black = [(5, 7), (6, 7), (7, 7)];
+    let white = [(3, 3), (4, 3), (5, 3), (6, 3)];
+    let mut b = Board::with_stones(Rule::Freestyle, &black, &white);
+    assert!(!matches!(b.prove_vct(Role::Black, 1_000, None, None), PnResult::ProvenWin(_)));
+  }
+
+  #[test]
+  fn test_budget_exhausted() {
+    let black = [(5, 7), (6, 7), (7, 5), (7, 6)];
+    let mut b = Board::with_stones(Rule::Freestyle, &black, &[(0, 0)]);
+    assert_eq!(b.prove_vct(Role::Black, 2, None, None), PnResult::Unknown);
+  }
+
+  #[test]
+  fn test_threat_detection() {
+    let b = Board::with_stones(Rule::Freestyle, &[(5, 7), (6, 7)], &[(3, 7)]);
+    assert_eq!(threat_at(&b, 7, 7, (1, 0), Role::Black), Some(Threat::Three));
+    assert_eq!(threat_at(&b, 7, 7, (0, 1), Role::Black), None);
+    // Closed on the left by White: X X X at 4..6 would only be a closed three
+    let b = Board::with_stones(Rule::Freestyle, &[(5, 7), (6, 7)], &[(4, 7)]);
+    assert_eq!(threat_at(&b, 7, 7, (1, 0), Role::Black), None);
+    let b = Board::with_stones(Rule::Freestyle, &[(5, 7), (6, 7), (7, 7)], &[(4, 7)]);
+    assert_eq!(threat_at(&b, 8, 7, (1, 0), Role::Black), Some(Threat::Four));
+  }
+}
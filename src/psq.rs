@@ -0,0 +1,253 @@
+use crate::bitboard::MAX_SIZE;
+use crate::board::{Board, Rule};
+use crate::game::Game;
+use crate::player::{PlayerType, Role};
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Outcome stored on the last line of a `.psq` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsqResult {
+  Winner(Role),
+  Draw,
+  Unfinished,
+}
+
+impl PsqResult {
+  fn code(&self) -> i32 {
+    match self {
+      PsqResult::Winner(Role::Black) => 1,
+      PsqResult::Winner(Role::White) => 2,
+      PsqResult::Draw => 0,
+      PsqResult::Unfinished => -1,
+    }
+  }
+
+  fn from_code(code: i32) -> Option<Self> {
+    match code {
+      1 => Some(PsqResult::Winner(Role::Black)),
+      2 => Some(PsqResult::Winner(Role::White)),
+      0 => Some(PsqResult::Draw),
+      -1 => Some(PsqResult::Unfinished),
+      _ => None,
+    }
+  }
+}
+
+/// A game in the Piskvork `.psq` format used by the Gomocup archives:
+///
+/// ```text
+/// Piskvork 15x15, 11:11, 0
+/// 8,8,0
+/// 9,9,1250
+/// pbrain-alpha.exe
+/// pbrain-beta.exe
+/// 1
+/// ```
+///
+/// A header with the board size, one `x,y,milliseconds` line per move with 1-based
+/// coordinates, Black first, then optionally the names of the black and the white
+/// player and the result: 1 / 2 for a black / white win, 0 for a draw, -1 while the
+/// game is still on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsqGame {
+  pub size: usize,
+  /// 0-based moves, alternating from Black, with the time spent on each.
+  pub moves: Vec<(usize, usize, Duration)>,
+  /// Black and white player names.
+  pub players: Option<(String, String)>,
+  pub result: Option<PsqResult>,
+}
+
+impl PsqGame {
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let header = lines.next().ok_or("empty file")?;
+    let size = parse_header(header).ok_or_else(|| format!("invalid header '{}'", header))?;
+
+    let mut moves = Vec::new();
+    let mut rest = Vec::new();
+    for line in lines {
+      match parse_move(line) {
+        Some((x, y, ms)) if rest.is_empty() => {
+          if x == 0 || y == 0 || x > size || y > size {
+            return Err(format!("move {},{} is off the board", x, y));
+          }
+          moves.push((x - 1, y - 1, Duration::from_millis(ms)));
+        }
+        _ => rest.push(line),
+      }
+    }
+
+    // Trailer: optional player names, then an optional result code
+    let result = match rest.last().map(|line| line.parse::<i32>()) {
+      Some(Ok(code)) => {
+        rest.pop();
+        Some(PsqResult::from_code(code).ok_or_else(|| format!("unknown result {}", code))?)
+      }
+      _ => None,
+    };
+    let players = match rest.as_slice() {
+      [] => None,
+      [black, white] => Some((black.to_string(), white.to_string())),
+      _ => return Err(format!("unexpected line '{}'", rest[0])),
+    };
+    Ok(Self {
+      size,
+      moves,
+      players,
+      result,
+    })
+  }
+
+  pub fn to_text(&self) -> String {
+    let mut text = format!("Piskvork {}x{}, 11:11, 0\n", self.size, self.size);
+    for &(x, y, time) in &self.moves {
+      text += &format!("{},{},{}\n", x + 1, y + 1, time.as_millis());
+    }
+    if let Some((black, white)) = &self.players {
+      text += &format!("{}\n{}\n", black, white);
+    }
+    if let Some(result) = self.result {
+      text += &format!("{}\n", result.code());
+    }
+    text
+  }
+
+  pub fn load(path: &str) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+  }
+
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  /// Replay the moves with `Board::put` under `rule`. Fails on the first move that
+  /// is taken or forbidden.
+  pub fn to_board(&self, rule: Rule) -> Result<Board, String> {
+    let mut board = Board::with_rule(self.size, rule);
+    let mut role = Role::Black;
+    for (number, &(x, y, _)) in self.moves.iter().enumerate() {
+      if !board.put(x, y, role) {
+        return Err(format!("move {} ({},{}) is illegal", number + 1, x + 1, y + 1));
+      }
+      role = role.opponent();
+    }
+    Ok(board)
+  }
+
+  /// Snapshot of `game`, finished or not. Moves without a recorded time (e.g. opening
+  /// stones) get zero.
+  pub fn of(game: &Game) -> Self {
+    let mut times = game.move_times.clone();
+    times.resize(game.board.history.len(), Duration::ZERO);
+    let name = |role: Role| {
+      let (player, engine) = if game.player1.role == role {
+        (&game.player1, &game.ai1)
+      } else {
+        (&game.player2, &game.ai2)
+      };
+      match player.player_type {
+        PlayerType::Human => "human".to_string(),
+        PlayerType::AI => engine.name(),
+      }
+    };
+
+    let result = match game.outcome() {
+      Some(Some(winner)) => PsqResult::Winner(winner),
+      Some(None) => PsqResult::Draw,
+      None => PsqResult::Unfinished,
+    };
+    Self {
+      size: game.board.size,
+      moves: game
+        .board
+        .history
+        .iter()
+        .zip(times)
+        .map(|(&(x, y, _), time)| (x, y, time))
+        .collect(),
+      players: Some((name(Role::Black), name(Role::White))),
+      result: Some(result),
+    }
+  }
+}
+
+/// Board size from `Piskvork 15x15, 11:11, 0`. Only square boards are supported.
+fn parse_header(line: &str) -> Option<usize> {
+  let dimensions = line.strip_prefix("Piskvork ")?.split(',').next()?;
+  let (width, height) = dimensions.trim().split_once('x')?;
+  let (width, height) = (width.parse::<usize>().ok()?, height.parse::<usize>().ok()?);
+  (width == height && (1..=MAX_SIZE).contains(&width)).then_some(width)
+}
+
+/// `x,y,milliseconds`.
+fn parse_move(line: &str) -> Option<(usize, usize, u64)> {
+  let mut parts = line.split(',').map(|part| part.trim().parse::<u64>());
+  let (x, y, ms) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+  parts.next().is_none().then_some((x as usize, y as usize, ms))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample(name: &str) -> String {
+    let path = format!("{}/tests/data/{}", env!("CARGO_MANIFEST_DIR"), name);
+    fs::read_to_string(path).unwrap()
+  }
+
+  #[test]
+  fn test_round_trip_samples() {
+    for name in ["black_wins.psq", "unfinished.psq"] {
+      let text = sample(name);
+      let game = PsqGame::parse(&text).unwrap();
+      assert_eq!(game.to_text(), text, "{}", name);
+    }
+  }
+
+  #[test]
+  fn test_replay_finished_game() {
+    let game = PsqGame::parse(&sample("black_wins.psq")).unwrap();
+    assert_eq!(game.size, 15);
+    assert_eq!(game.moves[1], (8, 8, Duration::from_millis(1250)));
+    assert_eq!(
+      game.players,
+      Some(("pbrain-alpha.exe".to_string(), "pbrain-beta.exe".to_string()))
+    );
+    assert_eq!(game.result, Some(PsqResult::Winner(Role::Black)));
+
+    let board = game.to_board(Rule::Freestyle).unwrap();
+    assert_eq!(board.history.len(), 9);
+    assert_eq!(board.get_winner(), -1);
+  }
+
+  #[test]
+  fn test_export_game() {
+    let mut game = Game::for_test(20, Rule::Freestyle, PlayerType::Human, PlayerType::AI);
+    let psq = PsqGame::parse(&sample("unfinished.psq")).unwrap();
+    game.board = psq.to_board(Rule::Freestyle).unwrap();
+    game.move_times = vec![Duration::from_millis(5); 2];
+
+    let exported = PsqGame::of(&game);
+    assert_eq!(exported.moves.len(), 5);
+    assert_eq!(exported.moves[1].2, Duration::from_millis(5));
+    assert_eq!(exported.moves[4], (11, 7, Duration::ZERO));
+    assert_eq!(exported.result, Some(PsqResult::Unfinished));
+    assert_eq!(exported.players.as_ref().map(|(black, _)| black.as_str()), Some("human"));
+    assert_eq!(PsqGame::parse(&exported.to_text()), Ok(exported));
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert!(PsqGame::parse("").is_err());
+    assert!(PsqGame::parse("Piskvork 15x20, 11:11, 0\n").is_err());
+    assert!(PsqGame::parse("Piskvork 15x15, 11:11, 0\n16,1,0\n").is_err());
+    assert!(PsqGame::parse("Piskvork 15x15, 11:11, 0\n1,1,0\nfoo\n7\n").is_err());
+    // The second move lands on the first one
+    let game = PsqGame::parse("Piskvork 15x15, 11:11, 0\n1,1,0\n1,1,0\n").unwrap();
+    assert!(game.to_board(Rule::Freestyle).is_err());
+  }
+}
@@ -0,0 +1,335 @@
+use crate::board::{Board, Rule};
+use crate::player::Role;
+
+const BLACK: i32 = -1;
+const EMPTY: i32 = 0;
+const WALL: i32 = 2;
+
+const ALL_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+
+/// Detects Renju forbidden points for Black on a live `Board`.
+///
+/// Checks place hypothetical black stones (the recursive "is this three really open?"
+/// test needs several of them at once). They are kept in a small stack on top of the
+/// board instead of a copy of it, so the real `Board` and its caches are never touched
+/// and no check costs more than the cells it looks at.
+pub struct ForbiddenChecker<'a> {
+  board: &'a Board,
+  /// Hypothetical black stones, last placed on top.
+  placed: Vec<(i32, i32)>,
+}
+
+impl<'a> ForbiddenChecker<'a> {
+  pub fn new(board: &'a Board) -> Self {
+    Self {
+      board,
+      placed: Vec::new(),
+    }
+  }
+
+  fn get(&self, x: i32, y: i32) -> i32 {
+    let size = self.board.size as i32;
+    if x < 0 || y < 0 || x >= size || y >= size {
+      WALL
+    } else if self.placed.contains(&(x, y)) {
+      BLACK
+    } else {
+      self.board.board[x as usize + 1][y as usize + 1]
+    }
+  }
+
+  /// Put a hypothetical black stone on the empty cell (x, y).
+  fn place(&mut self, x: i32, y: i32) {
+    self.placed.push((x, y));
+  }
+
+  /// Take back the hypothetical stone placed last.
+  fn take_back(&mut self) {
+    self.placed.pop();
+  }
+
+  /// Bounds (as step offsets from (x, y)) of the black run through (x, y) in direction (dx, dy).
+  fn run_bounds(&self, x: i32, y: i32, dx: i32, dy: i32) -> (i32, i32) {
+    let mut lo = 0;
+    while self.get(x + (lo - 1) * dx, y + (lo - 1) * dy) == BLACK {
+      lo -= 1;
+    }
+    let mut hi = 0;
+    while self.get(x + (hi + 1) * dx, y + (hi + 1) * dy) == BLACK {
+      hi += 1;
+    }
+    (lo, hi)
+  }
+
+  /// Whether Black at the empty cell (x, y) makes exactly five in direction (dx, dy).
+  fn makes_exact_five(&mut self, x: i32, y: i32, dx: i32, dy: i32) -> bool {
+    if self.get(x, y) != EMPTY {
+      return false;
+    }
+    self.place(x, y);
+    let (lo, hi) = self.run_bounds(x, y, dx, dy);
+    self.take_back();
+    hi - lo + 1 == 5
+  }
+
+  /// Number of fours through the black stone (x, y) in direction (dx, dy).
+  /// A straight four (`_XXXX_`) counts once, while two separate fours on the
+  /// same line (e.g. `X_XXX_X`) count twice.
+  fn count_fours(&mut self, x: i32, y: i32, dx: i32, dy: i32) -> usize {
+    let mut five_points = Vec::new();
+    for step in -4..=4 {
+      if step == 0 {
+        continue;
+      }
+      let (qx, qy) = (x + step * dx, y + step * dy);
+      if self.get(qx, qy) != EMPTY {
+        continue;
+      }
+      self.place(qx, qy);
+      let (lo, hi) = self.run_bounds(qx, qy, dx, dy);
+      self.take_back();
+      // The five has to go through (x, y), otherwise the four was not made by it
+      let contains_origin = (lo..=hi).contains(&-step);
+      if hi - lo + 1 == 5 && contains_origin {
+        five_points.push(step);
+      }
+    }
+    match five_points.as_slice() {
+      [a, b] if (a - b).abs() == 5 => 1,
+      points => points.len(),
+    }
+  }
+
+  /// Whether the black stone (x, y) forms a real (open) three in direction (dx, dy):
+  /// there is a point that turns it into a straight four and that point is not itself
+  /// forbidden.
+  fn is_open_three(&mut self, x: i32, y: i32, dx: i32, dy: i32, depth: usize) -> bool {
+    for step in -4..=4 {
+      if step == 0 {
+        continue;
+      }
+      let (qx, qy) = (x + step * dx, y + step * dy);
+      if self.get(qx, qy) != EMPTY {
+        continue;
+      }
+      self.place(qx, qy);
+      let (lo, hi) = self.run_bounds(qx, qy, dx, dy);
+      let straight_four = hi - lo + 1 == 4
+        && (lo..=hi).contains(&-step)
+        && self.makes_exact_five(qx + (lo - 1) * dx, qy + (lo - 1) * dy, dx, dy)
+        && self.makes_exact_five(qx + (hi + 1) * dx, qy + (hi + 1) * dy, dx, dy);
+      self.take_back();
+
+      if straight_four && !self.is_forbidden_at(qx, qy, depth + 1) {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// Whether Black playing at the empty cell (x, y) is forbidden.
+  pub fn is_forbidden(&mut self, x: usize, y: usize) -> bool {
+    self.is_forbidden_at(x as i32, y as i32, 0)
+  }
+
+  fn is_forbidden_at(&mut self, x: i32, y: i32, depth: usize) -> bool {
+    if self.get(x, y) != EMPTY || !self.has_enough_neighbours(x, y) {
+      return false;
+    }
+
+    self.place(x, y);
+    let forbidden = self.classify(x, y, depth);
+    self.take_back();
+    forbidden
+  }
+
+  /// Decide whether the just-placed black stone at (x, y) is a forbidden move.
+  fn classify(&mut self, x: i32, y: i32, depth: usize) -> bool {
+    let mut overline = false;
+    for &(dx, dy) in &ALL_DIRECTIONS {
+      let (lo, hi) = self.run_bounds(x, y, dx, dy);
+      match hi - lo + 1 {
+        // Five wins even if it creates a forbidden shape elsewhere
+        5 => return false,
+        len if len > 5 => overline = true,
+        _ => {}
+      }
+    }
+    if overline {
+      return true;
+    }
+
+    let mut fours = 0;
+    let mut threes = 0;
+    for &(dx, dy) in &ALL_DIRECTIONS {
+      let f = self.count_fours(x, y, dx, dy);
+      if f > 0 {
+        fours += f;
+      } else if self.is_open_three(x, y, dx, dy, depth) {
+        threes += 1;
+      }
+    }
+    fours >= 2 || threes >= 2
+  }
+
+  /// Cheap pre-filter: a forbidden shape needs at least four other black stones
+  /// on the lines through (x, y).
+  fn has_enough_neighbours(&self, x: i32, y: i32) -> bool {
+    let mut count = 0;
+    for &(dx, dy) in &ALL_DIRECTIONS {
+      for step in -5..=5 {
+        if step != 0 && self.get(x + step * dx, y + step * dy) == BLACK {
+          count += 1;
+        }
+      }
+    }
+    count >= 4
+  }
+}
+
+impl Board {
+  /// Whether Black playing at the empty cell (x, y) is forbidden under the current rule.
+  /// Always `false` outside of Renju.
+  pub fn is_forbidden(&self, x: usize, y: usize) -> bool {
+    if self.rule != Rule::Renju || x >= self.size || y >= self.size {
+      return false;
+    }
+    ForbiddenChecker::new(self).is_forbidden(x, y)
+  }
+
+  /// Renju: Black is to move, White threatens five and every point that would stop it
+  /// is forbidden for Black (and Black cannot win first). Such a position is lost for Black.
+  pub fn is_forced_onto_forbidden(&self) -> bool {
+    if self.rule != Rule::Renju {
+      return false;
+    }
+    let threats = self.five_points(Role::White);
+    if threats.is_empty() || !self.five_points(Role::Black).is_empty() {
+      return false;
+    }
+    let mut checker = ForbiddenChecker::new(self);
+    threats.iter().all(|&(x, y)| checker.is_forbidden(x, y))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::board::{Board, Rule};
+  use crate::player::Role;
+
+  #[test]
+  fn test_double_three_is_forbidden() {
+    let mut b = Board::with_stones(Rule::Renju, &[(5, 7), (6, 7), (7, 5), (7, 6)], &[]);
+    assert!(b.is_forbidden(7, 7));
+    assert!(!b.put(7, 7, Role::Black), "put must reject a forbidden point");
+    assert!(b.put(7, 7, Role::White), "White is never restricted");
+  }
+
+  #[test]
+  fn test_double_four_is_forbidden() {
+    let b = Board::with_stones(
+      Rule::Renju,
+      &[(4, 7), (5, 7), (6, 7), (7, 4), (7, 5), (7, 6)],
+      &[(3, 7), (7, 3)],
+    );
+    assert!(b.is_forbidden(7, 7));
+  }
+
+  #[test]
+  fn test_double_four_on_one_line_is_forbidden() {
+    // X_XXX_X: the middle stone makes two fours on the same line
+    let b = Board::with_stones(Rule::Renju, &[(3, 7), (5, 7), (7, 7), (9, 7)], &[]);
+    assert!(b.is_forbidden(6, 7));
+  }
+
+  #[test]
+  fn test_overline_is_forbidden_and_does_not_win() {
+    let b = Board::with_stones(Rule::Renju, &[(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)], &[]);
+    assert!(b.is_forbidden(5, 7));
+
+    let mut free = Board::new(15);
+    for x in 2..8 {
+      free.put(x, 7, Role::Black);
+    }
+    assert_eq!(free.get_winner(), -1, "overline wins in freestyle");
+  }
+
+  #[test]
+  fn test_five_beats_forbidden_shape() {
+    // Horizontal five at (7,7) also makes a vertical overline: the five still wins
+    let mut b = Board::with_stones(
+      Rule::Renju,
+      &[
+        (3, 7),
+        (4, 7),
+        (5, 7),
+        (6, 7),
+        (7, 4),
+        (7, 5),
+        (7, 6),
+        (7, 8),
+        (7, 9),
+        (7, 10),
+      ],
+      &[],
+    );
+    assert!(!b.is_forbidden(7, 7));
+    assert!(b.put(7, 7, Role::Black));
+    assert_eq!(b.get_winner(), -1);
+  }
+
+  #[test]
+  fn test_four_three_is_allowed() {
+    let b = Board::with_stones(Rule::Renju, &[(4, 7), (5, 7), (6, 7), (7, 5), (7, 6)], &[(3, 7)]);
+    assert!(!b.is_forbidden(7, 7));
+  }
+
+  #[test]
+  fn test_fake_three_is_not_counted() {
+    // Horizontal three B B [p] can only become a straight four at (3,7).
+    let black = [(4, 7), (5, 7), (6, 5), (6, 6)];
+    let b = Board::with_stones(Rule::Renju, &black, &[(8, 7)]);
+    assert!(b.is_forbidden(6, 7), "two open threes");
+
+    // Now (3,7) would be a double-four for Black, so the horizontal three is fake.
+    let mut with_column = black.to_vec();
+    with_column.extend_from_slice(&[(3, 4), (3, 5), (3, 6)]);
+    let b = Board::with_stones(Rule::Renju, &with_column, &[(8, 7)]);
+    assert!(!b.is_forbidden(6, 7), "only one of the threes is real");
+  }
+
+  #[test]
+  fn test_white_overline_wins() {
+    let mut b = Board::with_rule(15, Rule::Renju);
+    for x in 2..8 {
+      b.put(x, 7, Role::White);
+    }
+    assert_eq!(b.get_winner(), 1);
+  }
+
+  #[test]
+  fn test_moves_skip_forbidden_points() {
+    let mut b = Board::with_stones(
+      Rule::Renju,
+      &[(5, 7), (6, 7), (7, 5), (7, 6)],
+      &[(0, 0), (14, 14), (0, 14), (14, 0)],
+    );
+    assert!(!b.get_moves(Role::Black, 0, false, false).contains(&(7, 7)));
+    assert!(!b.get_valuable_moves(Role::Black, 0, false, false).contains(&(7, 7)));
+    assert!(b.get_moves(Role::White, 0, false, false).contains(&(7, 7)));
+  }
+
+  #[test]
+  fn test_forced_onto_forbidden() {
+    // White four on the diagonal can only be stopped at (7,7), a double-three for Black
+    let b = Board::with_stones(
+      Rule::Renju,
+      &[(5, 7), (6, 7), (7, 5), (7, 6), (12, 12)],
+      &[(8, 8), (9, 9), (10, 10), (11, 11)],
+    );
+    assert!(b.is_forced_onto_forbidden());
+
+    let free = Board::new(15);
+    assert!(!free.is_forced_onto_forbidden());
+  }
+}
@@ -0,0 +1,289 @@
+use crate::board::{Board, Rule, Symmetry};
+use crate::player::Role;
+use crate::swap2::{Swap2Ai, BALANCED_MARGIN};
+
+/// Most fifth-move alternatives White may ask for under Soosõrv-8.
+pub const MAX_SOOSORV_ALTERNATIVES: usize = 8;
+/// Candidate cells searched when the AI places an opening move before a swap.
+const BALANCED_CANDIDATES: usize = 4;
+
+/// Standard Renju opening rule. Every rule restricts the first moves to central squares
+/// (checked by `Board::put`), lets a player swap colours at fixed points and ends with
+/// Black offering several fifth moves, of which White picks one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpeningRule {
+  /// RIF (1996): the first player places moves 1-3, White may swap, White plays
+  /// move 4, Black offers two fifth moves.
+  Rif,
+  /// Soosõrv-8: like RIF, but with White declaring 1 to 8 fifth-move alternatives
+  /// along with move 4, after which Black may swap.
+  Soosorv8,
+  /// Taraguchi-10: moves 1-4 in growing central squares, with a swap offer after each,
+  /// then ten fifth moves anywhere on the board.
+  Taraguchi10,
+}
+
+impl OpeningRule {
+  /// Half-width of the central square that move `number` (1-based) must be played in.
+  pub fn square(&self, number: usize) -> Option<usize> {
+    match (self, number) {
+      (_, 1) => Some(0),
+      (_, 2) => Some(1),
+      (_, 3) => Some(2),
+      (OpeningRule::Taraguchi10, 4) => Some(3),
+      _ => None,
+    }
+  }
+
+  /// Whether move `number` may be played at (x, y) on a board of `size`.
+  pub fn allows(&self, number: usize, x: usize, y: usize, size: usize) -> bool {
+    let center = size / 2;
+    self
+      .square(number)
+      .is_none_or(|half| x.abs_diff(center) <= half && y.abs_diff(center) <= half)
+  }
+
+  /// The first player places both colours of moves 1-3 (RIF, Soosõrv); otherwise
+  /// every move is played by the player holding its colour.
+  pub fn first_player_places_three(&self) -> bool {
+    !matches!(self, OpeningRule::Taraguchi10)
+  }
+
+  /// Whether the holder of the other colour may swap after move `number`.
+  pub fn swap_after(&self, number: usize) -> bool {
+    match self {
+      OpeningRule::Rif => number == 3,
+      OpeningRule::Soosorv8 => number == 3 || number == 4,
+      OpeningRule::Taraguchi10 => number <= 4,
+    }
+  }
+
+  /// Number of fifth-move alternatives, `None` when White declares it (Soosõrv-8).
+  pub fn fifth_alternatives(&self) -> Option<usize> {
+    match self {
+      OpeningRule::Rif => Some(2),
+      OpeningRule::Soosorv8 => None,
+      OpeningRule::Taraguchi10 => Some(10),
+    }
+  }
+}
+
+/// Whether moves `a` and `b` lead to the same position up to a symmetry of the board
+/// that leaves the current stones in place.
+pub fn equivalent_moves(board: &Board, a: (usize, usize), b: (usize, usize)) -> bool {
+  Symmetry::ALL
+    .iter()
+    .any(|&symmetry| symmetry.apply(a.0, a.1, board.size) == b && board.symmetric_hash(symmetry) == board.hash())
+}
+
+/// Check fifth-move alternatives: legal for Black, all different, none a mirror image of another.
+pub fn check_offers(board: &Board, offers: &[(usize, usize)]) -> Result<(), String> {
+  let mut positions: Vec<(u64, (usize, usize))> = Vec::new();
+  for &(x, y) in offers {
+    let mut next = board.clone();
+    if !next.put(x, y, Role::Black) {
+      return Err(format!("{},{} is not a legal move", x, y));
+    }
+    let (key, _) = next.canonical_hash();
+    if let Some(&(_, (ox, oy))) = positions.iter().find(|&&(other, _)| other == key) {
+      return Err(format!("{},{} is symmetric to {},{}", x, y, ox, oy));
+    }
+    positions.push((key, (x, y)));
+  }
+  Ok(())
+}
+
+/// Opening decisions of an AI player, valued like the Swap2 ones.
+pub struct RenjuOpeningAi {
+  ai: Swap2Ai,
+}
+
+impl Default for RenjuOpeningAi {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl RenjuOpeningAi {
+  pub fn new() -> Self {
+    Self { ai: Swap2Ai::new() }
+  }
+
+  /// Moves 1-3 placed by the first player: the most balanced standard opening, as the
+  /// opponent may swap.
+  pub fn propose(&mut self, size: usize, rule: Rule) -> Vec<(usize, usize)> {
+    self.ai.propose(size, rule)
+  }
+
+  /// A move of `role` that leaves the position balanced, as the opponent may swap after
+  /// it. Only cells the opening rule of `board` allows are considered.
+  pub fn balanced_move(&mut self, board: &Board, role: Role) -> Option<(usize, usize)> {
+    let mut board = board.clone();
+    let mut candidates: Vec<(usize, usize)> = board
+      .get_moves(role, 0, false, false)
+      .into_iter()
+      .filter(|&(x, y)| board.clone().put(x, y, role))
+      .take(BALANCED_CANDIDATES)
+      .collect();
+    if candidates.is_empty() {
+      // Nothing near the stones (e.g. the first move): any cell the rule allows
+      candidates = (0..board.size)
+        .flat_map(|x| (0..board.size).map(move |y| (x, y)))
+        .filter(|&(x, y)| board.clone().put(x, y, role))
+        .take(BALANCED_CANDIDATES)
+        .collect();
+    }
+    let mut best: Option<(i32, (usize, usize))> = None;
+    for (x, y) in candidates {
+      board.put(x, y, role);
+      let imbalance = self.ai.value(&board, role.opponent()).0.abs();
+      board.undo();
+      if best.is_none_or(|(b, _)| imbalance < b) {
+        best = Some((imbalance, (x, y)));
+      }
+    }
+    best.map(|(_, mv)| mv)
+  }
+
+  /// Best move of `role`, when no swap follows.
+  pub fn free_move(&mut self, board: &Board, role: Role) -> Option<(usize, usize)> {
+    match self.ai.value(board, role).1 {
+      Some((x, y)) if board.clone().put(x, y, role) => Some((x, y)),
+      _ => self.balanced_move(board, role),
+    }
+  }
+
+  /// Whether the player holding `to_move`, offered a swap, should take the other colour.
+  pub fn wants_swap(&mut self, board: &Board, to_move: Role) -> bool {
+    self.ai.value(board, to_move).0 < -BALANCED_MARGIN
+  }
+
+  /// Soosõrv-8, White after move 4: more alternatives favour White, so ask for many
+  /// when Black stands well and for few when Black stands badly (Black may still swap).
+  pub fn declare_alternatives(&mut self, board: &Board) -> usize {
+    let value = self.ai.value(board, Role::Black).0.clamp(-BALANCED_MARGIN, BALANCED_MARGIN);
+    let steps = (MAX_SOOSORV_ALTERNATIVES - 1) as i64;
+    1 + ((value + BALANCED_MARGIN) as i64 * steps / (2 * BALANCED_MARGIN) as i64) as usize
+  }
+
+  /// Black's `count` fifth moves. White will take the best one for White, so offer
+  /// the moves that leave White worst off, skipping mirror images.
+  pub fn offer_fifth(&mut self, board: &Board, count: usize) -> Vec<(usize, usize)> {
+    let mut board = board.clone();
+    let mut scored: Vec<(i32, (usize, usize))> = Vec::new();
+    for (x, y) in board.get_moves(Role::Black, 0, false, false).into_iter().take(count * 2 + 2) {
+      if board.put(x, y, Role::Black) {
+        let value = self.ai.value(&board, Role::White).0;
+        board.undo();
+        scored.push((value, (x, y)));
+      }
+    }
+    scored.sort_by_key(|&(value, _)| value);
+
+    let mut offers: Vec<(usize, usize)> = Vec::new();
+    for (_, mv) in scored {
+      if offers.len() < count && !offers.iter().any(|&o| equivalent_moves(&board, o, mv)) {
+        offers.push(mv);
+      }
+    }
+    // Not enough good candidates: fill up with any legal cells
+    for x in 0..board.size {
+      for y in 0..board.size {
+        if offers.len() < count && check_offers(&board, &[offers.as_slice(), &[(x, y)]].concat()).is_ok() {
+          offers.push((x, y));
+        }
+      }
+    }
+    offers
+  }
+
+  /// White's pick among the offered fifth moves.
+  pub fn choose_fifth(&mut self, board: &Board, offers: &[(usize, usize)]) -> (usize, usize) {
+    let mut board = board.clone();
+    *offers
+      .iter()
+      .max_by_key(|&&(x, y)| {
+        board.put(x, y, Role::Black);
+        let value = self.ai.value(&board, Role::White).0;
+        board.undo();
+        value
+      })
+      .expect("at least one fifth move is offered")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn board_with(rule: OpeningRule) -> Board {
+    let mut board = Board::with_rule(15, Rule::Renju);
+    board.opening_rule = Some(rule);
+    board
+  }
+
+  #[test]
+  fn test_put_checks_central_squares() {
+    let mut board = board_with(OpeningRule::Rif);
+    assert!(!board.put(6, 6, Role::Black), "move 1 goes to the centre");
+    assert!(board.put(7, 7, Role::Black));
+    assert!(!board.put(9, 7, Role::White), "move 2 within 3x3");
+    assert!(board.put(8, 6, Role::White));
+    assert!(!board.put(4, 7, Role::Black), "move 3 within 5x5");
+    assert!(board.put(9, 9, Role::Black));
+    assert!(board.put(0, 0, Role::White), "move 4 is free under RIF");
+
+    let mut board = board_with(OpeningRule::Taraguchi10);
+    for &(x, y, role) in &[(7, 7, Role::Black), (7, 8, Role::White), (9, 9, Role::Black)] {
+      assert!(board.put(x, y, role));
+    }
+    assert!(!board.put(11, 7, Role::White), "move 4 within 7x7");
+    assert!(board.put(10, 7, Role::White));
+  }
+
+  #[test]
+  fn test_equivalent_moves() {
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(7, 6, Role::White);
+    // The position is symmetric about the vertical axis x = 7
+    assert!(equivalent_moves(&board, (5, 9), (9, 9)));
+    assert!(!equivalent_moves(&board, (5, 9), (9, 5)));
+    assert!(check_offers(&board, &[(5, 9), (9, 9)]).is_err());
+    assert!(check_offers(&board, &[(5, 9), (9, 5)]).is_ok());
+    assert!(check_offers(&board, &[(7, 7)]).is_err());
+  }
+
+  #[test]
+  fn test_ai_offers_distinct_fifth_moves() {
+    let mut board = board_with(OpeningRule::Rif);
+    for &(x, y, role) in &[
+      (7, 7, Role::Black),
+      (7, 6, Role::White),
+      (8, 8, Role::Black),
+      (5, 5, Role::White),
+    ] {
+      assert!(board.put(x, y, role));
+    }
+    let mut ai = RenjuOpeningAi::new();
+    let offers = ai.offer_fifth(&board, 2);
+    assert_eq!(offers.len(), 2);
+    assert!(check_offers(&board, &offers).is_ok());
+    assert!(offers.contains(&ai.choose_fifth(&board, &offers)));
+  }
+
+  #[test]
+  fn test_declared_alternatives_in_range() {
+    let mut board = board_with(OpeningRule::Soosorv8);
+    for &(x, y, role) in &[
+      (7, 7, Role::Black),
+      (7, 6, Role::White),
+      (8, 8, Role::Black),
+      (5, 5, Role::White),
+    ] {
+      board.put(x, y, role);
+    }
+    let n = RenjuOpeningAi::new().declare_alternatives(&board);
+    assert!((1..=MAX_SOOSORV_ALTERNATIVES).contains(&n));
+  }
+}
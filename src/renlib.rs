@@ -0,0 +1,336 @@
+use crate::board::{Board, Symmetry};
+use std::fs;
+use std::io;
+
+/// Renju libraries are recorded on the standard 15x15 board.
+pub const LIBRARY_SIZE: usize = 15;
+
+/// Renlib header: `0xFF "RenLib" 0xFF`, version bytes, padding.
+const RENLIB_HEADER_LEN: usize = 20;
+const RENLIB_MAGIC: &[u8] = b"\xffRenLib\xff";
+
+// Flags of a Renlib node. `MARK` (0x10) and `START` (0x04) only tell the RenLib
+// editor to highlight a node or open the library at it, so they are ignored here.
+const DOWN: u8 = 0x80;
+const RIGHT: u8 = 0x40;
+const OLD_COMMENT: u8 = 0x20;
+const COMMENT: u8 = 0x08;
+const NO_MOVE: u8 = 0x02;
+const EXTENSION: u8 = 0x01;
+
+/// A position of an opening library and its known continuations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryNode {
+  /// Move leading here, `None` for the root.
+  pub mv: Option<(usize, usize)>,
+  pub comment: String,
+  /// Indices of the continuations in `OpeningLibrary::nodes`.
+  pub children: Vec<usize>,
+}
+
+/// A known continuation of a looked-up position, in the coordinates of that position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryMove {
+  pub mv: (usize, usize),
+  pub comment: String,
+}
+
+/// Opening tree read from a Renlib `.lib` file or a RIF (renju.net) XML game database.
+/// Node 0 is the empty board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningLibrary {
+  pub nodes: Vec<LibraryNode>,
+}
+
+impl Default for OpeningLibrary {
+  fn default() -> Self {
+    Self {
+      nodes: vec![LibraryNode::default()],
+    }
+  }
+}
+
+impl OpeningLibrary {
+  /// Read a library file: Renlib if it has the Renlib header, RIF XML otherwise.
+  pub fn load(path: &str) -> io::Result<Self> {
+    let bytes = fs::read(path)?;
+    let library = if bytes.starts_with(RENLIB_MAGIC) {
+      Self::from_renlib(&bytes)
+    } else {
+      Self::from_rif_xml(&String::from_utf8_lossy(&bytes))
+    };
+    library.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+  }
+
+  /// Parse a Renlib library. After the header, every node is a move byte and a flag
+  /// byte, in pre-order: `DOWN` means the next node is its first child, `RIGHT` that a
+  /// sibling follows once its subtree is done. A move byte `0` or the `NO_MOVE` flag is
+  /// no move (a pass), otherwise column `(m - 1) % 16` and row `(m - 1) / 16` from the
+  /// top. Comments follow their
+  /// node as zero-terminated Latin-1 text, padded to an even length.
+  pub fn from_renlib(bytes: &[u8]) -> Result<Self, String> {
+    if bytes.len() < RENLIB_HEADER_LEN || !bytes.starts_with(RENLIB_MAGIC) {
+      return Err("not a Renlib file".to_string());
+    }
+    let mut library = Self::default();
+    let mut parent = 0;
+    // Parents of the nodes whose right sibling is still to come
+    let mut pending = Vec::new();
+    let mut pos = RENLIB_HEADER_LEN;
+    while pos + 2 <= bytes.len() {
+      let (m, flags) = (bytes[pos], bytes[pos + 1]);
+      pos += 2;
+      if flags & EXTENSION != 0 {
+        pos += 2;
+      }
+      let mv = match m {
+        _ if flags & NO_MOVE != 0 => None,
+        0 => None,
+        m => {
+          let (x, y) = ((m as usize - 1) % 16, (m as usize - 1) / 16);
+          if x >= LIBRARY_SIZE || y >= LIBRARY_SIZE {
+            return Err(format!("move byte {:#04x} at offset {} is off the board", m, pos - 2));
+          }
+          Some((x, y))
+        }
+      };
+      let mut comment = String::new();
+      if flags & (COMMENT | OLD_COMMENT) != 0 {
+        let end = (pos..bytes.len())
+          .step_by(2)
+          .find(|&i| bytes[i] == 0 || bytes.get(i + 1) == Some(&0))
+          .ok_or("unterminated comment")?;
+        comment = bytes[pos..(end + 2).min(bytes.len())]
+          .iter()
+          .take_while(|&&b| b != 0)
+          .map(|&b| b as char)
+          .collect();
+        pos = end + 2;
+      }
+
+      // A leading node without a move stands for the empty board itself
+      let index = if mv.is_none() && library.nodes.len() == 1 && parent == 0 {
+        library.nodes[0].comment = comment;
+        0
+      } else {
+        library.nodes.push(LibraryNode {
+          mv,
+          comment,
+          children: Vec::new(),
+        });
+        let index = library.nodes.len() - 1;
+        library.nodes[parent].children.push(index);
+        index
+      };
+
+      if flags & RIGHT != 0 {
+        pending.push(parent);
+      }
+      if flags & DOWN != 0 {
+        parent = index;
+      } else if let Some(next) = pending.pop() {
+        parent = next;
+      } else {
+        break;
+      }
+    }
+    Ok(library)
+  }
+
+  /// Parse a RIF (renju.net) XML database: every `<game>` element with its moves in a
+  /// `move` attribute or child element, as `h8 i9 ...`. Games sharing a beginning share
+  /// a branch; each node's comment counts the games through it.
+  pub fn from_rif_xml(text: &str) -> Result<Self, String> {
+    let mut library = Self::default();
+    let mut counts = vec![0usize];
+    let mut games = 0;
+    for element in text.split("<game").skip(1) {
+      // `<games>` opens the list, it is not a game
+      if !element.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+        continue;
+      }
+      let moves = attribute(element, "move")
+        .or_else(|| {
+          element
+            .split_once("<move>")
+            .and_then(|(_, rest)| rest.split_once("</move>"))
+            .map(|(m, _)| m)
+        })
+        .unwrap_or("");
+      let mut node = 0;
+      for name in moves.split_whitespace() {
+        let mv = parse_rif_move(name).ok_or_else(|| format!("game {}: invalid move '{}'", games + 1, name))?;
+        node = library.child(node, mv);
+        counts.resize(library.nodes.len(), 0);
+        counts[node] += 1;
+      }
+      counts[0] += 1;
+      games += 1;
+    }
+    if games == 0 {
+      return Err("no games found".to_string());
+    }
+    for (node, count) in library.nodes.iter_mut().zip(counts) {
+      node.comment = format!("{} game{}", count, if count == 1 { "" } else { "s" });
+    }
+    Ok(library)
+  }
+
+  /// Child of `node` reached by `mv`, created if missing.
+  fn child(&mut self, node: usize, mv: (usize, usize)) -> usize {
+    if let Some(&child) = self.nodes[node].children.iter().find(|&&c| self.nodes[c].mv == Some(mv)) {
+      return child;
+    }
+    self.nodes.push(LibraryNode {
+      mv: Some(mv),
+      ..LibraryNode::default()
+    });
+    let child = self.nodes.len() - 1;
+    self.nodes[node].children.push(child);
+    child
+  }
+
+  /// Node reached by playing `moves` from the empty board.
+  fn find(&self, moves: impl Iterator<Item = (usize, usize)>) -> Option<usize> {
+    let mut node = 0;
+    for mv in moves {
+      node = *self.nodes[node].children.iter().find(|&&c| self.nodes[c].mv == Some(mv))?;
+    }
+    Some(node)
+  }
+
+  /// Comment of the position on `board` and its known continuations. The move sequence
+  /// is looked up under all eight symmetries of the board, the first match wins; the
+  /// continuations are mapped back onto `board`. Other move orders are not found.
+  pub fn lookup(&self, board: &Board) -> Option<(String, Vec<LibraryMove>)> {
+    if board.size != LIBRARY_SIZE {
+      return None;
+    }
+    Symmetry::ALL.iter().find_map(|&symmetry| {
+      let moves = symmetry.apply_moves(&board.history, LIBRARY_SIZE);
+      let node = self.find(moves.iter().map(|&(x, y, _)| (x, y)))?;
+      let back = symmetry.inverse();
+      let continuations = self.nodes[node]
+        .children
+        .iter()
+        .filter_map(|&c| {
+          let (x, y) = self.nodes[c].mv?;
+          Some(LibraryMove {
+            mv: back.apply(x, y, LIBRARY_SIZE),
+            comment: self.nodes[c].comment.clone(),
+          })
+        })
+        .collect();
+      Some((self.nodes[node].comment.clone(), continuations))
+    })
+  }
+}
+
+/// Value of `name="..."` in the attributes of an element.
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+  let tag = &element[..element.find('>').unwrap_or(element.len())];
+  let pattern = format!(" {}=\"", name);
+  let start = tag.find(&pattern)? + pattern.len();
+  let value = &tag[start..];
+  Some(&value[..value.find('"')?])
+}
+
+/// `h8` -> (7, 7): column letter from `a`, row number from the bottom of a 15x15 board.
+pub fn parse_rif_move(name: &str) -> Option<(usize, usize)> {
+  let mut chars = name.chars();
+  let column = chars.next()?;
+  let row: usize = chars.as_str().parse().ok()?;
+  let x = (column.to_ascii_lowercase() as usize).checked_sub('a' as usize)?;
+  (x < LIBRARY_SIZE && (1..=LIBRARY_SIZE).contains(&row)).then(|| (x, LIBRARY_SIZE - row))
+}
+
+/// (7, 7) -> `h8`.
+pub fn rif_move_name(x: usize, y: usize) -> String {
+  format!("{}{}", (b'a' + x as u8) as char, LIBRARY_SIZE - y)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::player::Role;
+
+  /// A small RenLib 3.x library, assembled byte by byte:
+  ///
+  /// ```text
+  /// 00 80              root, DOWN
+  /// 78 88 "centre"     h8, DOWN | COMMENT
+  /// 68 c0              h9, RIGHT | DOWN
+  /// 5a 10              j10, MARK
+  /// 69 84              i9, DOWN | START
+  /// 5a 48 "sure win"   j10, RIGHT | COMMENT
+  /// 00 02              pass, NO_MOVE
+  /// ```
+  fn sample_library() -> OpeningLibrary {
+    OpeningLibrary::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sample.lib")).unwrap()
+  }
+
+  #[test]
+  fn test_read_renlib() {
+    let library = sample_library();
+    assert_eq!(library.nodes.len(), 7);
+    let centre = library.nodes[0].children[0];
+    assert_eq!(library.nodes[centre].mv, parse_rif_move("h8"));
+    assert_eq!(library.nodes[centre].comment, "centre");
+    let replies: Vec<_> = library.nodes[centre].children.iter().map(|&c| library.nodes[c].mv).collect();
+    assert_eq!(replies, vec![parse_rif_move("h9"), parse_rif_move("i9")]);
+    // The marked j10 closes the h9 branch, the pass follows j10 under i9
+    let h9 = library.nodes[centre].children[0];
+    let j10 = library.nodes[h9].children[0];
+    assert_eq!(
+      (library.nodes[j10].mv, library.nodes[j10].children.len()),
+      (parse_rif_move("j10"), 0)
+    );
+    let i9 = library.nodes[centre].children[1];
+    let under_i9: Vec<_> = library.nodes[i9].children.iter().map(|&c| library.nodes[c].mv).collect();
+    assert_eq!(under_i9, vec![parse_rif_move("j10"), None]);
+    assert!(OpeningLibrary::from_renlib(b"not a library").is_err());
+  }
+
+  #[test]
+  fn test_lookup_under_symmetry() {
+    let library = sample_library();
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    let (comment, moves) = library.lookup(&board).unwrap();
+    assert_eq!(comment, "centre");
+    assert_eq!(moves.len(), 2);
+
+    // i9 mirrored left to right is g9, and its continuation j10 becomes f10
+    board.put(6, 6, Role::White);
+    let (_, moves) = library.lookup(&board).unwrap();
+    assert_eq!(
+      moves,
+      vec![LibraryMove {
+        mv: parse_rif_move("f10").unwrap(),
+        comment: "sure win".to_string()
+      }]
+    );
+    board.put(0, 0, Role::Black);
+    assert_eq!(library.lookup(&board), None);
+  }
+
+  #[test]
+  fn test_read_rif_xml() {
+    let xml = r#"<database><games>
+      <game id="1" move="h8 i9 j10"/>
+      <game id="2" move="h8 i9 g7"/>
+      <game id="3"><move>h8 h9</move></game>
+    </games></database>"#;
+    let library = OpeningLibrary::from_rif_xml(xml).unwrap();
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    let (comment, moves) = library.lookup(&board).unwrap();
+    assert_eq!(comment, "3 games");
+    let names: Vec<_> = moves
+      .iter()
+      .map(|m| (rif_move_name(m.mv.0, m.mv.1), m.comment.as_str()))
+      .collect();
+    assert_eq!(names, vec![("i9".to_string(), "2 games"), ("h9".to_string(), "1 game")]);
+    assert!(OpeningLibrary::from_rif_xml(r#"<game move="z99"/>"#).is_err());
+  }
+}
@@ -0,0 +1,435 @@
+use crate::board::{Board, Rule};
+use crate::engine::SearchResult;
+use crate::game::Game;
+use crate::game_record::{parse_rule, rule_name};
+use crate::player::{PlayerType, Role};
+use std::fs;
+use std::io;
+
+/// Board size when a record has no `SZ`.
+const DEFAULT_SGF_SIZE: usize = 15;
+/// Coordinates are single letters `a`..`z`.
+const MAX_SGF_SIZE: usize = 26;
+
+/// A node of an SGF game tree. The first child continues the main line, the others
+/// are variations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SgfNode {
+  pub properties: Vec<(String, Vec<String>)>,
+  pub children: Vec<SgfNode>,
+}
+
+impl SgfNode {
+  /// First value of property `key`.
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self
+      .properties
+      .iter()
+      .find(|(k, _)| k == key)
+      .and_then(|(_, values)| values.first())
+      .map(String::as_str)
+  }
+
+  /// Set property `key` to the single value `value`.
+  pub fn set(&mut self, key: &str, value: &str) {
+    match self.properties.iter_mut().find(|(k, _)| k == key) {
+      Some((_, values)) => *values = vec![value.to_string()],
+      None => self.properties.push((key.to_string(), vec![value.to_string()])),
+    }
+  }
+
+  /// Stone played in this node (`B[..]` or `W[..]`), if any.
+  pub fn stone(&self) -> Result<Option<(usize, usize, Role)>, String> {
+    for (key, role) in [("B", Role::Black), ("W", Role::White)] {
+      if let Some(point) = self.get(key) {
+        let (x, y) = parse_point(point).ok_or_else(|| format!("invalid point {}[{}]", key, point))?;
+        return Ok(Some((x, y, role)));
+      }
+    }
+    Ok(None)
+  }
+
+  /// The nodes of the main line, this one first.
+  pub fn main_line(&self) -> Vec<&SgfNode> {
+    let mut line = vec![self];
+    while let Some(next) = line[line.len() - 1].children.first() {
+      line.push(next);
+    }
+    line
+  }
+
+  /// The record as SGF text.
+  pub fn to_text(&self) -> String {
+    let mut text = String::from("(");
+    self.write(&mut text);
+    text.push_str(")\n");
+    text
+  }
+
+  fn write(&self, text: &mut String) {
+    text.push(';');
+    for (key, values) in &self.properties {
+      text.push_str(key);
+      for value in values {
+        text.push('[');
+        text.push_str(&value.replace('\\', "\\\\").replace(']', "\\]"));
+        text.push(']');
+      }
+    }
+    match self.children.as_slice() {
+      [] => {}
+      [next] => next.write(text),
+      variations => {
+        for variation in variations {
+          text.push('(');
+          variation.write(text);
+          text.push(')');
+        }
+      }
+    }
+  }
+
+  /// Parse the first game tree of an SGF collection and return its root node.
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut parser = Parser {
+      chars: text.chars().collect(),
+      pos: 0,
+    };
+    parser.skip_whitespace();
+    parser.expect('(')?;
+    parser.tree()
+  }
+
+  pub fn load(path: &str) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+  }
+
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    fs::write(path, self.to_text())
+  }
+
+  /// Rebuild the main line on a `Board` with `Board::put`, under the rule named by the
+  /// record's `RU` property, or `rule` if it has none. Every variation is replayed as
+  /// well, so a record with any taken or forbidden move is rejected.
+  pub fn to_board(&self, rule: Rule) -> Result<Board, String> {
+    if let Some(game) = self.get("GM").filter(|&gm| gm.trim() != "4") {
+      return Err(format!("not a gomoku record (GM[{}])", game));
+    }
+    if self.get("AB").is_some() || self.get("AW").is_some() {
+      return Err("setup stones (AB/AW) are not supported".to_string());
+    }
+    let size = match self.get("SZ") {
+      Some(size) => size
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("unsupported board size SZ[{}]", size))?,
+      None => DEFAULT_SGF_SIZE,
+    };
+    if size == 0 || size > MAX_SGF_SIZE {
+      return Err(format!("unsupported board size SZ[{}]", size));
+    }
+    let rule = match self.get("RU") {
+      Some(name) => parse_rule(name.trim()).ok_or_else(|| format!("unknown rule RU[{}]", name))?,
+      None => rule,
+    };
+
+    let mut board = Board::with_rule(size, rule);
+    replay_tree(self, &mut board)?;
+    for node in self.main_line() {
+      if let Some((x, y, role)) = node.stone()? {
+        board.put(x, y, role);
+      }
+    }
+    Ok(board)
+  }
+
+  /// Record of `game`: rule, players, result and one node per move, with the engine's
+  /// score and principal variation as the comment of the moves it played. Fails for
+  /// boards too large for letter coordinates.
+  pub fn of(game: &Game) -> Result<Self, String> {
+    if game.board.size > MAX_SGF_SIZE {
+      return Err(format!(
+        "a {0}x{0} board is too large for SGF, at most {1}x{1}",
+        game.board.size, MAX_SGF_SIZE
+      ));
+    }
+    let name = |role: Role| {
+      let (player, engine) = if game.player1.role == role {
+        (&game.player1, &game.ai1)
+      } else {
+        (&game.player2, &game.ai2)
+      };
+      match player.player_type {
+        PlayerType::Human => "human".to_string(),
+        PlayerType::AI => engine.name(),
+      }
+    };
+    let mut root = SgfNode::default();
+    root.set("GM", "4");
+    root.set("FF", "4");
+    root.set("SZ", &game.board.size.to_string());
+    root.set("RU", rule_name(game.board.rule));
+    root.set("PB", &name(Role::Black));
+    root.set("PW", &name(Role::White));
+    match game.outcome() {
+      Some(Some(Role::Black)) => root.set("RE", "B+"),
+      Some(Some(Role::White)) => root.set("RE", "W+"),
+      Some(None) => root.set("RE", "0"),
+      None => {}
+    }
+
+    // Build the line from its end so every node can own the next one
+    let mut next: Option<SgfNode> = None;
+    for (index, &(x, y, role)) in game.board.history.iter().enumerate().rev() {
+      let mut node = SgfNode::default();
+      node.set(if role == Role::Black { "B" } else { "W" }, &point_name(x, y));
+      if let Some(Some(result)) = game.analysis.get(index) {
+        node.set("C", &analysis_comment(result));
+      }
+      node.children.extend(next.take());
+      next = Some(node);
+    }
+    root.children.extend(next);
+    Ok(root)
+  }
+}
+
+/// Score and principal variation of a search, e.g. `score 1200, pv hh ig jf`.
+pub fn analysis_comment(result: &SearchResult) -> String {
+  let line: Vec<String> = result.line.iter().map(|&(x, y)| point_name(x, y)).collect();
+  format!("score {}, pv {}", result.value, line.join(" "))
+}
+
+/// Play every node of the tree below `node` on `board`, restoring it afterwards.
+fn replay_tree(node: &SgfNode, board: &mut Board) -> Result<(), String> {
+  let placed = match node.stone()? {
+    Some((x, y, role)) => {
+      if !board.put(x, y, role) {
+        return Err(format!(
+          "move {} {}[{}] is illegal: off the board, taken or forbidden",
+          board.history.len() + 1,
+          if role == Role::Black { "B" } else { "W" },
+          point_name(x, y)
+        ));
+      }
+      true
+    }
+    None => false,
+  };
+  let result = node.children.iter().try_for_each(|child| replay_tree(child, board));
+  if placed {
+    board.undo();
+  }
+  result
+}
+
+/// `hh` -> (7, 7).
+fn parse_point(point: &str) -> Option<(usize, usize)> {
+  let mut letters = point.trim().chars();
+  let (x, y) = (letters.next()?, letters.next()?);
+  let coordinate = |c: char| c.is_ascii_lowercase().then(|| c as usize - 'a' as usize);
+  match letters.next() {
+    None => Some((coordinate(x)?, coordinate(y)?)),
+    Some(_) => None,
+  }
+}
+
+fn point_name(x: usize, y: usize) -> String {
+  [x, y].iter().map(|&c| (b'a' + c as u8) as char).collect()
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl Parser {
+  fn skip_whitespace(&mut self) {
+    while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+      self.pos += 1;
+    }
+  }
+
+  fn peek(&mut self) -> Option<char> {
+    self.skip_whitespace();
+    self.chars.get(self.pos).copied()
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), String> {
+    match self.peek() {
+      Some(c) if c == expected => {
+        self.pos += 1;
+        Ok(())
+      }
+      Some(c) => Err(format!("expected '{}' at offset {}, found '{}'", expected, self.pos, c)),
+      None => Err(format!("expected '{}', found the end of the file", expected)),
+    }
+  }
+
+  /// A game tree after its opening `(`: a sequence of nodes, then variations.
+  fn tree(&mut self) -> Result<SgfNode, String> {
+    let mut sequence = vec![self.node()?];
+    while self.peek() == Some(';') {
+      sequence.push(self.node()?);
+    }
+    let mut last = sequence.pop().expect("a sequence has a node");
+    while self.peek() == Some('(') {
+      self.pos += 1;
+      last.children.push(self.tree()?);
+    }
+    self.expect(')')?;
+    while let Some(mut parent) = sequence.pop() {
+      parent.children.push(last);
+      last = parent;
+    }
+    Ok(last)
+  }
+
+  fn node(&mut self) -> Result<SgfNode, String> {
+    self.expect(';')?;
+    let mut node = SgfNode::default();
+    while self.peek().is_some_and(|c| c.is_ascii_uppercase()) {
+      let start = self.pos;
+      while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_uppercase()) {
+        self.pos += 1;
+      }
+      let key: String = self.chars[start..self.pos].iter().collect();
+      let mut values = Vec::new();
+      while self.peek() == Some('[') {
+        values.push(self.value()?);
+      }
+      if values.is_empty() {
+        return Err(format!("property {} has no value", key));
+      }
+      node.properties.push((key, values));
+    }
+    Ok(node)
+  }
+
+  /// A bracketed value, with `\` escaping the next character.
+  fn value(&mut self) -> Result<String, String> {
+    self.expect('[')?;
+    let mut value = String::new();
+    loop {
+      match self.chars.get(self.pos) {
+        None => return Err("unterminated property value".to_string()),
+        Some(']') => break,
+        Some('\\') => {
+          self.pos += 1;
+          // An escaped line break is a soft break and disappears
+          match self.chars.get(self.pos) {
+            Some('\n') => {}
+            Some(&c) => value.push(c),
+            None => return Err("unterminated property value".to_string()),
+          }
+        }
+        Some(&c) => value.push(c),
+      }
+      self.pos += 1;
+    }
+    self.pos += 1;
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RECORD: &str = "(;GM[4]FF[4]SZ[15]PB[alpha]PW[beta]RE[B+]\
+    ;B[hh]C[centre];W[ii]C[score -12, pv ii hg]\
+    (;B[hg];W[ig](;B[hf]C[a \\] bracket])(;B[he]))\
+    (;B[gg]))";
+
+  #[test]
+  fn test_parse_and_write() {
+    let root = SgfNode::parse(RECORD).unwrap();
+    assert_eq!(root.get("SZ"), Some("15"));
+    assert_eq!(root.get("PB"), Some("alpha"));
+    let line = root.main_line();
+    assert_eq!(line.len(), 6);
+    assert_eq!(line[1].stone(), Ok(Some((7, 7, Role::Black))));
+    assert_eq!(line[2].get("C"), Some("score -12, pv ii hg"));
+    assert_eq!(line[5].get("C"), Some("a ] bracket"));
+    assert_eq!(line[2].children.len(), 2);
+    assert_eq!(line[4].children.len(), 2);
+
+    // Writing and reading back gives the same tree
+    assert_eq!(SgfNode::parse(&root.to_text()), Ok(root));
+  }
+
+  #[test]
+  fn test_to_board() {
+    let root = SgfNode::parse(RECORD).unwrap();
+    let board = root.to_board(Rule::Freestyle).unwrap();
+    assert_eq!(
+      board.history,
+      vec![
+        (7, 7, Role::Black),
+        (8, 8, Role::White),
+        (7, 6, Role::Black),
+        (8, 6, Role::White),
+        (7, 5, Role::Black)
+      ]
+    );
+  }
+
+  #[test]
+  fn test_invalid_records() {
+    // A variation plays on a taken cell
+    let err = SgfNode::parse("(;GM[4];B[hh](;W[ii])(;W[hh]))")
+      .unwrap()
+      .to_board(Rule::Freestyle)
+      .unwrap_err();
+    assert!(err.contains("move 2 W[hh]"), "{}", err);
+    assert!(SgfNode::parse("(;GM[1];B[hh])").unwrap().to_board(Rule::Freestyle).is_err());
+    assert!(SgfNode::parse("(;SZ[9];B[kk])").unwrap().to_board(Rule::Freestyle).is_err());
+    assert!(SgfNode::parse("(;RU[japanese];B[hh])")
+      .unwrap()
+      .to_board(Rule::Freestyle)
+      .is_err());
+    assert!(SgfNode::parse("(;B[hh]").is_err());
+    assert!(SgfNode::parse("(;C[open)").is_err());
+  }
+
+  #[test]
+  fn test_record_of_game() {
+    let mut game = Game::for_test(15, Rule::Freestyle, PlayerType::Human, PlayerType::AI);
+    game.board.put(7, 7, Role::Black);
+    game.board.put(8, 8, Role::White);
+    game.analysis = vec![
+      None,
+      Some(SearchResult {
+        value: 42,
+        best_move: Some((8, 8)),
+        line: vec![(8, 8), (6, 6)],
+      }),
+    ];
+
+    let root = SgfNode::of(&game).unwrap();
+    assert_eq!(root.get("PB"), Some("human"));
+    assert_eq!(root.get("RU"), Some("freestyle"));
+    assert_eq!(root.get("RE"), None);
+    let line = root.main_line();
+    assert_eq!(line[1].get("C"), None);
+    assert_eq!(line[2].get("C"), Some("score 42, pv ii gg"));
+    let board = SgfNode::parse(&root.to_text()).unwrap().to_board(Rule::Freestyle).unwrap();
+    assert_eq!(board.history, game.board.history);
+
+    // Larger boards have no letter coordinates
+    game.board = Board::new(MAX_SGF_SIZE + 1);
+    assert!(SgfNode::of(&game).is_err());
+  }
+
+  #[test]
+  fn test_rule_round_trip() {
+    let mut game = Game::for_test(15, Rule::Renju, PlayerType::Human, PlayerType::Human);
+    game.board.put(7, 7, Role::Black);
+    let text = SgfNode::of(&game).unwrap().to_text();
+    assert!(text.contains("RU[renju]"), "{}", text);
+    // The record's rule wins over the one asked for
+    let board = SgfNode::parse(&text).unwrap().to_board(Rule::Freestyle).unwrap();
+    assert_eq!(board.rule, Rule::Renju);
+  }
+}
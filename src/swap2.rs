@@ -0,0 +1,196 @@
+use crate::ai::AIEngine;
+use crate::board::{Board, Rule};
+use crate::openings::renju_openings;
+use crate::player::Role;
+
+/// Positions whose value for White stays within this margin count as balanced.
+pub const BALANCED_MARGIN: i32 = 100_000;
+/// Openings, by static evaluation, that the proposer searches.
+const PROPOSAL_CANDIDATES: usize = 4;
+/// Black stones considered to rebalance the position after the best White move.
+const REBALANCE_CANDIDATES: usize = 4;
+
+/// Decision of the second player after the first three stones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Swap2Choice {
+  /// Play Black; the first player takes White and plays the fourth move.
+  TakeBlack,
+  /// Play White and play the fourth move.
+  TakeWhite,
+  /// Place a white and a black stone, then let the first player choose a colour.
+  PlaceTwo((usize, usize), (usize, usize)),
+}
+
+/// Swap2 decisions of an AI player, judged by shallow `AIEngine` searches:
+/// the proposer aims at balanced positions, the chooser takes the better colour.
+///
+/// A search favours the side that moved last, so positions are valued by the mean of
+/// a one- and a two-ply search.
+pub struct Swap2Ai {
+  engine: AIEngine,
+}
+
+impl Default for Swap2Ai {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Swap2Ai {
+  pub fn new() -> Self {
+    let mut engine = AIEngine::new(1);
+    engine.set_hash_size(1);
+    Self { engine }
+  }
+
+  /// Value of `board` for `role`, who is to move, and its best move.
+  pub fn value(&mut self, board: &Board, role: Role) -> (i32, Option<(usize, usize)>) {
+    let mut total = 0;
+    let mut best = None;
+    for depth in [1, 2] {
+      self.engine.depth = depth;
+      self.engine.clear_hash();
+      let (value, mv, _) = self.engine.make_move(&mut board.clone(), role);
+      total += value / 2;
+      best = mv;
+    }
+    (total, best)
+  }
+
+  /// Value for White, who is to move after three or five stones.
+  fn white_value(&mut self, board: &Board) -> (i32, Option<(usize, usize)>) {
+    self.value(board, Role::White)
+  }
+
+  /// First player: the three opening stones (black, white, black), picked among the
+  /// Renju openings as the one closest to balance, so either choice of the opponent is fair.
+  pub fn propose(&mut self, size: usize, rule: Rule) -> Vec<(usize, usize)> {
+    let mut candidates: Vec<(i32, Board)> = renju_openings()
+      .iter()
+      .filter_map(|opening| {
+        let mut board = Board::with_rule(size, rule);
+        opening.place(&mut board).then(|| (board.evaluate(Role::White).abs(), board))
+      })
+      .collect();
+    candidates.sort_by_key(|&(imbalance, _)| imbalance);
+
+    let mut best: Option<(i32, Vec<(usize, usize)>)> = None;
+    for (_, board) in candidates.into_iter().take(PROPOSAL_CANDIDATES) {
+      let imbalance = self.white_value(&board).0.abs();
+      if best.as_ref().is_none_or(|(b, _)| imbalance < *b) {
+        best = Some((imbalance, board.history.iter().map(|&(x, y, _)| (x, y)).collect()));
+      }
+    }
+    best.map(|(_, stones)| stones).unwrap_or_default()
+  }
+
+  /// Second player, after three stones: take the colour that is clearly better, or
+  /// add a white and a black stone that keep the game balanced.
+  pub fn second_choice(&mut self, board: &Board) -> Swap2Choice {
+    let (value, white) = self.white_value(board);
+    if value > BALANCED_MARGIN {
+      return Swap2Choice::TakeWhite;
+    }
+    if value < -BALANCED_MARGIN {
+      return Swap2Choice::TakeBlack;
+    }
+
+    let mut board = board.clone();
+    let white = match white {
+      Some(white) if board.put(white.0, white.1, Role::White) => white,
+      _ => return Swap2Choice::TakeWhite,
+    };
+    let mut best: Option<(i32, (usize, usize))> = None;
+    for (x, y) in board
+      .get_moves(Role::Black, 0, false, false)
+      .into_iter()
+      .take(REBALANCE_CANDIDATES)
+    {
+      if !board.put(x, y, Role::Black) {
+        continue;
+      }
+      let imbalance = self.white_value(&board).0.abs();
+      board.undo();
+      if best.is_none_or(|(b, _)| imbalance < b) {
+        best = Some((imbalance, (x, y)));
+      }
+    }
+    match best {
+      Some((_, black)) => Swap2Choice::PlaceTwo(white, black),
+      None => Swap2Choice::TakeWhite,
+    }
+  }
+
+  /// First player, after five stones: the colour with the better prospects.
+  pub fn choose_colour(&mut self, board: &Board) -> Role {
+    if self.white_value(board).0 >= 0 {
+      Role::White
+    } else {
+      Role::Black
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_proposal_is_an_opening() {
+    let stones = Swap2Ai::new().propose(15, Rule::Freestyle);
+    assert_eq!(stones.len(), 3);
+    let mut board = Board::new(15);
+    for (&(x, y), role) in stones.iter().zip([Role::Black, Role::White, Role::Black]) {
+      assert!(board.put(x, y, role));
+    }
+  }
+
+  #[test]
+  fn test_second_player_takes_the_winning_colour() {
+    // Two black stones side by side against a white stone in the corner: take Black
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(0, 0, Role::White);
+    board.put(8, 7, Role::Black);
+    assert_eq!(Swap2Ai::new().second_choice(&board), Swap2Choice::TakeBlack);
+  }
+
+  #[test]
+  fn test_first_player_takes_the_winning_colour() {
+    // Black already has an open three: White is lost, so take Black
+    let mut board = Board::new(15);
+    board.put(6, 7, Role::Black);
+    board.put(0, 0, Role::White);
+    board.put(7, 7, Role::Black);
+    board.put(0, 14, Role::White);
+    board.put(8, 7, Role::Black);
+    let mut ai = Swap2Ai::new();
+    assert_eq!(ai.choose_colour(&board), Role::Black);
+  }
+
+  #[test]
+  fn test_second_player_takes_white_against_scattered_stones() {
+    // Both black stones in corners, White in the centre: take White
+    let mut board = Board::new(15);
+    board.put(0, 0, Role::Black);
+    board.put(7, 7, Role::White);
+    board.put(14, 14, Role::Black);
+    assert_eq!(Swap2Ai::new().second_choice(&board), Swap2Choice::TakeWhite);
+  }
+
+  #[test]
+  fn test_place_two_adds_legal_stones() {
+    // A balanced opening (direct, the second black stone two below the first)
+    let mut board = Board::new(15);
+    board.put(7, 7, Role::Black);
+    board.put(7, 6, Role::White);
+    board.put(7, 5, Role::Black);
+    match Swap2Ai::new().second_choice(&board) {
+      Swap2Choice::PlaceTwo(white, black) => {
+        assert!(board.put(white.0, white.1, Role::White));
+        assert!(board.put(black.0, black.1, Role::Black));
+      }
+      choice => panic!("expected PlaceTwo on a balanced opening, got {:?}", choice),
+    }
+  }
+}
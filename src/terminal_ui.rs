@@ -1,6 +1,6 @@
 use crossterm::{
   cursor::{Hide, MoveTo, Show},
-  event::{read, Event, KeyCode, KeyEvent},
+  event::{poll, read, Event, KeyCode, KeyEvent},
   execute,
   style::{Color, Print, ResetColor, SetForegroundColor},
   terminal::{disable_raw_mode, enable_raw_mode, size, EnterAlternateScreen, LeaveAlternateScreen},
@@ -8,6 +8,7 @@ use crossterm::{
 
 use crate::{board::Board, player::PlayerType};
 use std::io::{stdout, Result as IoResult}; // Note, we take Result as IoResult
+use std::time::Duration;
 
 // Definition of the GameAction enum for various actions in the game
 #[derive(Debug)]
@@ -22,6 +23,7 @@ pub enum GameAction {
   MoveUp,      // Move up
   MoveDown,    // Move down
   PlaceStone,  // Place stone
+  Save,        // Save the game to a file
 }
 
 // Structure for the terminal user interface
@@ -77,6 +79,9 @@ impl TerminalUI {
             KeyCode::Tab => {
               return GameAction::Redo; // Redo action
             }
+            KeyCode::Char('s') => {
+              return GameAction::Save; // Save the game
+            }
             KeyCode::Left => return GameAction::MoveLeft,   // Move left
             KeyCode::Right => return GameAction::MoveRight, // Move right
             KeyCode::Up => return GameAction::MoveUp,       // Move up
@@ -93,6 +98,33 @@ impl TerminalUI {
     GameAction::None // No action
   }
 
+  /// Wait for one of `keys`; `None` if the user quits (Esc / Q) instead
+  pub fn read_choice(&mut self, keys: &[char]) -> Option<char> {
+    loop {
+      if let Ok(Event::Key(KeyEvent { code, .. })) = read() {
+        match code {
+          KeyCode::Esc | KeyCode::Char('q') => return None,
+          KeyCode::Char(c) if keys.contains(&c) => return Some(c),
+          _ => {}
+        }
+      }
+    }
+  }
+
+  /// Wait up to `timeout` for a key press; true if it asks the AI to move now (Enter / Space)
+  pub fn poll_move_now(&mut self, timeout: Duration) -> bool {
+    if !matches!(poll(timeout), Ok(true)) {
+      return false;
+    }
+    matches!(
+      read(),
+      Ok(Event::Key(KeyEvent {
+        code: KeyCode::Enter | KeyCode::Char(' '),
+        ..
+      }))
+    )
+  }
+
   /// Set (and immediately draw) a new message
   pub fn show_message(&mut self, msg: &str) {
     // Save to the field
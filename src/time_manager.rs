@@ -0,0 +1,166 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Part of the per-move limit kept in reserve for the work done after the search stops.
+const SAFETY_MARGIN: Duration = Duration::from_millis(50);
+/// Expected number of our remaining moves when budgeting from the game clock.
+const MOVES_TO_GO: u32 = 25;
+
+/// Game time control: `base` time per player plus `increment` added after each move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+  pub base: Duration,
+  pub increment: Duration,
+}
+
+impl FromStr for TimeControl {
+  type Err = String;
+
+  /// Parse `"<base>+<increment>"` or just `"<base>"`, both in seconds (fractions allowed),
+  /// e.g. `300+5` or `60`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (base, increment) = s.split_once('+').unwrap_or((s, "0"));
+    let seconds = |part: &str| {
+      part
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("invalid time control '{}', expected e.g. 300+5", s))
+    };
+    Ok(TimeControl {
+      base: seconds(base)?,
+      increment: seconds(increment)?,
+    })
+  }
+}
+
+/// Decides how long one move may take and keeps the game clock of one engine.
+///
+/// The budget is the smaller of the fixed per-move limit and a share of the remaining
+/// game time plus most of the increment. Without any limit the search is depth-bound only.
+#[derive(Debug, Clone, Default)]
+pub struct TimeManager {
+  per_move: Option<Duration>,
+  /// Remaining game time on our clock, if playing with a time control.
+  remaining: Option<Duration>,
+  increment: Duration,
+  /// Start of the current move and the budget it was given.
+  started: Option<Instant>,
+  budget: Option<Duration>,
+}
+
+impl TimeManager {
+  pub fn new(per_move: Option<Duration>, control: Option<TimeControl>) -> Self {
+    Self {
+      per_move,
+      remaining: control.map(|c| c.base),
+      increment: control.map(|c| c.increment).unwrap_or_default(),
+      started: None,
+      budget: None,
+    }
+  }
+
+  /// Whether any time limit is set at all.
+  pub fn is_limited(&self) -> bool {
+    self.per_move.is_some() || self.remaining.is_some()
+  }
+
+  /// Time available for the next move.
+  pub fn budget(&self) -> Option<Duration> {
+    let from_clock = self.remaining.map(|remaining| {
+      let share = remaining / MOVES_TO_GO + self.increment * 3 / 4;
+      // Never plan to use more than half of what is left on the clock
+      share.min(remaining / 2)
+    });
+    let per_move = self.per_move.map(|limit| limit.saturating_sub(SAFETY_MARGIN.min(limit / 10)));
+
+    match (per_move, from_clock) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b),
+    }
+  }
+
+  /// Start thinking on a move.
+  pub fn start(&mut self) {
+    self.started = Some(Instant::now());
+    self.budget = self.budget();
+  }
+
+  /// Deadline after `fraction` of the current move's budget, `None` if unlimited.
+  pub fn deadline(&self, fraction: f64) -> Option<Instant> {
+    let started = self.started?;
+    let budget = self.budget?;
+    Some(started + budget.mul_f64(fraction))
+  }
+
+  /// Time spent on the current move so far.
+  pub fn elapsed(&self) -> Duration {
+    self.started.map(|s| s.elapsed()).unwrap_or_default()
+  }
+
+  /// Finish the current move: charge the time used to the clock and add the increment.
+  pub fn stop(&mut self) {
+    let used = self.elapsed();
+    if let Some(remaining) = self.remaining.as_mut() {
+      *remaining = remaining.saturating_sub(used) + self.increment;
+    }
+    self.started = None;
+    self.budget = None;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_time_control() {
+    let tc: TimeControl = "300+5".parse().unwrap();
+    assert_eq!(tc.base, Duration::from_secs(300));
+    assert_eq!(tc.increment, Duration::from_secs(5));
+
+    let tc: TimeControl = "1.5".parse().unwrap();
+    assert_eq!(tc.base, Duration::from_millis(1500));
+    assert_eq!(tc.increment, Duration::ZERO);
+
+    assert!("abc".parse::<TimeControl>().is_err());
+    assert!("10+-1".parse::<TimeControl>().is_err());
+  }
+
+  #[test]
+  fn test_budget() {
+    assert_eq!(TimeManager::default().budget(), None);
+    assert!(!TimeManager::default().is_limited());
+
+    let tm = TimeManager::new(Some(Duration::from_secs(1)), None);
+    assert_eq!(tm.budget(), Some(Duration::from_millis(950)));
+
+    let tc = TimeControl {
+      base: Duration::from_secs(100),
+      increment: Duration::from_secs(4),
+    };
+    let tm = TimeManager::new(None, Some(tc));
+    assert_eq!(tm.budget(), Some(Duration::from_secs(4) + Duration::from_secs(3)));
+
+    // The per-move limit caps the clock-based budget
+    let tm = TimeManager::new(Some(Duration::from_secs(2)), Some(tc));
+    assert_eq!(tm.budget(), Some(Duration::from_millis(1950)));
+  }
+
+  #[test]
+  fn test_clock_is_charged_and_incremented() {
+    let tc = TimeControl {
+      base: Duration::from_secs(10),
+      increment: Duration::from_secs(1),
+    };
+    let mut tm = TimeManager::new(None, Some(tc));
+    tm.start();
+    assert!(tm.deadline(1.0).is_some());
+    tm.stop();
+    let remaining = tm.remaining.unwrap();
+    assert!(remaining > Duration::from_secs(10) && remaining <= Duration::from_secs(11));
+    assert!(tm.deadline(1.0).is_none());
+  }
+}
@@ -0,0 +1,288 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Default transposition table size in megabytes.
+pub const DEFAULT_TT_MB: usize = 32;
+
+/// Entries per bucket: 4 entries of 16 bytes fill one 64-byte cache line.
+const BUCKET_SIZE: usize = 4;
+const ENTRY_BYTES: usize = 16;
+/// Ages wrap around in 6 bits.
+const AGE_MASK: u8 = 0x3F;
+const NO_MOVE: u64 = 0xFFFF;
+
+/// How the stored value relates to the true value of the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+  /// The search finished inside the window: the value is exact.
+  Exact,
+  /// A beta cutoff happened: the true value is at least `value`.
+  Lower,
+  /// No move raised alpha: the true value is at most `value`.
+  Upper,
+}
+
+impl Bound {
+  fn to_bits(self) -> u64 {
+    match self {
+      Bound::Exact => 1,
+      Bound::Lower => 2,
+      Bound::Upper => 3,
+    }
+  }
+
+  fn from_bits(bits: u64) -> Option<Bound> {
+    match bits {
+      1 => Some(Bound::Exact),
+      2 => Some(Bound::Lower),
+      3 => Some(Bound::Upper),
+      // 0 marks an empty slot
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TTEntry {
+  pub value: i32,
+  /// Remaining depth the value was searched with.
+  pub depth: i32,
+  pub bound: Bound,
+  pub best_move: Option<(usize, usize)>,
+  /// Search generation that wrote the entry.
+  pub age: u8,
+}
+
+impl TTEntry {
+  /// Pack into one word: value (32 bits) | move (16) | depth (8) | bound (2) | age (6).
+  fn pack(&self) -> u64 {
+    let mv = match self.best_move {
+      Some((x, y)) => ((x as u64 & 0xFF) << 8) | (y as u64 & 0xFF),
+      None => NO_MOVE,
+    };
+    (self.value as u32 as u64)
+      | (mv << 32)
+      | ((self.depth.clamp(0, 255) as u64) << 48)
+      | (self.bound.to_bits() << 56)
+      | (((self.age & AGE_MASK) as u64) << 58)
+  }
+
+  fn unpack(data: u64) -> Option<TTEntry> {
+    let bound = Bound::from_bits((data >> 56) & 0x3)?;
+    let mv = (data >> 32) & 0xFFFF;
+    Some(TTEntry {
+      value: data as u32 as i32,
+      depth: ((data >> 48) & 0xFF) as i32,
+      bound,
+      best_move: (mv != NO_MOVE).then_some(((mv >> 8) as usize, (mv & 0xFF) as usize)),
+      age: ((data >> 58) as u8) & AGE_MASK,
+    })
+  }
+}
+
+/// Fixed-size transposition table keyed by Zobrist hash.
+///
+/// Every slot is two atomic words, `key ^ data` and `data`, so readers and writers
+/// never lock: a slot torn by a concurrent write fails the key check and reads as a
+/// miss. Replacement prefers keeping deep entries from the current search.
+pub struct TranspositionTable {
+  slots: Vec<[AtomicU64; 2]>,
+  bucket_mask: usize,
+  age: AtomicU8,
+}
+
+impl std::fmt::Debug for TranspositionTable {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("TranspositionTable")
+      .field("capacity", &self.capacity())
+      .field("age", &self.age.load(Ordering::Relaxed))
+      .finish()
+  }
+}
+
+impl TranspositionTable {
+  /// Create a table using about `mb` megabytes (rounded down to a power of two buckets).
+  pub fn new(mb: usize) -> Self {
+    let bytes = mb.max(1) * 1024 * 1024;
+    let buckets = (bytes / (ENTRY_BYTES * BUCKET_SIZE)).max(1);
+    // Round down to a power of two so the bucket index is a mask
+    let buckets = 1usize << (usize::BITS - 1 - buckets.leading_zeros());
+    let slots = (0..buckets * BUCKET_SIZE)
+      .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+      .collect();
+    Self {
+      slots,
+      bucket_mask: buckets - 1,
+      age: AtomicU8::new(0),
+    }
+  }
+
+  /// Number of entries the table can hold.
+  pub fn capacity(&self) -> usize {
+    self.slots.len()
+  }
+
+  /// Forget everything.
+  pub fn clear(&self) {
+    for slot in &self.slots {
+      slot[0].store(0, Ordering::Relaxed);
+      slot[1].store(0, Ordering::Relaxed);
+    }
+    self.age.store(0, Ordering::Relaxed);
+  }
+
+  /// Start a new search generation; entries of older generations become cheap to replace.
+  pub fn new_search(&self) {
+    let age = (self.age.load(Ordering::Relaxed) + 1) & AGE_MASK;
+    self.age.store(age, Ordering::Relaxed);
+  }
+
+  fn bucket(&self, key: u64) -> std::ops::Range<usize> {
+    let start = (key as usize & self.bucket_mask) * BUCKET_SIZE;
+    start..start + BUCKET_SIZE
+  }
+
+  fn read(&self, index: usize) -> (u64, u64) {
+    let slot = &self.slots[index];
+    let data = slot[1].load(Ordering::Relaxed);
+    (slot[0].load(Ordering::Relaxed) ^ data, data)
+  }
+
+  pub fn probe(&self, key: u64) -> Option<TTEntry> {
+    self.bucket(key).find_map(|i| {
+      let (stored_key, data) = self.read(i);
+      if stored_key == key {
+        TTEntry::unpack(data)
+      } else {
+        None
+      }
+    })
+  }
+
+  pub fn store(&self, key: u64, value: i32, depth: i32, bound: Bound, best_move: Option<(usize, usize)>) {
+    let age = self.age.load(Ordering::Relaxed);
+    let mut target = None;
+    let mut worst_score = i32::MAX;
+
+    for i in self.bucket(key) {
+      let (stored_key, data) = self.read(i);
+      let existing = TTEntry::unpack(data);
+      if let Some(old) = existing.filter(|_| stored_key == key) {
+        // Keep a deeper result of this search unless the new one is exact
+        if bound != Bound::Exact && old.age == age && old.depth > depth {
+          return;
+        }
+        // Keep the old best move if this search did not find one
+        let best_move = best_move.or(old.best_move);
+        self.write(i, key, value, depth, bound, best_move, age);
+        return;
+      }
+      // Empty slots go first, then shallow entries, then stale ones
+      let score = match existing {
+        None => i32::MIN,
+        Some(e) => e.depth - 8 * ((age.wrapping_sub(e.age) & AGE_MASK) as i32),
+      };
+      if score < worst_score {
+        worst_score = score;
+        target = Some(i);
+      }
+    }
+
+    if let Some(i) = target {
+      self.write(i, key, value, depth, bound, best_move, age);
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn write(&self, index: usize, key: u64, value: i32, depth: i32, bound: Bound, best_move: Option<(usize, usize)>, age: u8) {
+    let data = TTEntry {
+      value,
+      depth,
+      bound,
+      best_move,
+      age,
+    }
+    .pack();
+    let slot = &self.slots[index];
+    slot[0].store(key ^ data, Ordering::Relaxed);
+    slot[1].store(data, Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_store_and_probe() {
+    let tt = TranspositionTable::new(1);
+    assert_eq!(tt.probe(42), None);
+
+    tt.store(42, -1234, 5, Bound::Lower, Some((7, 14)));
+    let e = tt.probe(42).unwrap();
+    assert_eq!(e.value, -1234);
+    assert_eq!(e.depth, 5);
+    assert_eq!(e.bound, Bound::Lower);
+    assert_eq!(e.best_move, Some((7, 14)));
+
+    tt.store(43, 10_000_000, 0, Bound::Exact, None);
+    let e = tt.probe(43).unwrap();
+    assert_eq!(e.value, 10_000_000);
+    assert_eq!(e.best_move, None);
+  }
+
+  #[test]
+  fn test_verification_rejects_other_keys() {
+    let tt = TranspositionTable::new(1);
+    let buckets = tt.bucket_mask as u64 + 1;
+    tt.store(5, 1, 1, Bound::Exact, None);
+    // Same bucket, different key
+    assert_eq!(tt.probe(5 + buckets), None);
+  }
+
+  #[test]
+  fn test_same_key_keeps_deeper_bound_and_move() {
+    let tt = TranspositionTable::new(1);
+    tt.store(7, 100, 6, Bound::Exact, Some((1, 2)));
+    tt.store(7, 50, 2, Bound::Upper, None);
+    assert_eq!(tt.probe(7).unwrap().depth, 6, "shallower bound must not overwrite");
+
+    tt.store(7, 60, 2, Bound::Exact, None);
+    let e = tt.probe(7).unwrap();
+    assert_eq!((e.value, e.depth), (60, 2));
+    assert_eq!(e.best_move, Some((1, 2)), "best move is kept");
+  }
+
+  #[test]
+  fn test_replacement_prefers_shallow_and_stale() {
+    let tt = TranspositionTable::new(1);
+    let buckets = tt.bucket_mask as u64 + 1;
+    let keys: Vec<u64> = (0..6).map(|i| 3 + i * buckets).collect();
+
+    // Fill the bucket with depths 9, 2, 7, 8; the depth-2 entry is the victim
+    for (&key, depth) in keys.iter().zip([9, 2, 7, 8]) {
+      tt.store(key, 0, depth, Bound::Exact, None);
+    }
+    tt.store(keys[4], 0, 1, Bound::Exact, None);
+    assert!(tt.probe(keys[1]).is_none());
+    assert!(tt.probe(keys[4]).is_some());
+    assert!(tt.probe(keys[0]).is_some());
+
+    // Two generations later a fresh shallow entry outlives stale deep ones
+    tt.new_search();
+    tt.new_search();
+    tt.store(keys[1], 0, 1, Bound::Exact, None);
+    tt.store(keys[5], 0, 1, Bound::Exact, None);
+    assert!(tt.probe(keys[1]).is_some());
+    assert!(tt.probe(keys[5]).is_some());
+    assert!(tt.probe(keys[2]).is_none(), "the stale depth-7 entry is evicted");
+  }
+
+  #[test]
+  fn test_size_and_clear() {
+    let tt = TranspositionTable::new(2);
+    assert_eq!(tt.capacity(), 2 * 1024 * 1024 / ENTRY_BYTES);
+    tt.store(1, 1, 1, Bound::Exact, None);
+    tt.clear();
+    assert_eq!(tt.probe(1), None);
+  }
+}
@@ -0,0 +1,377 @@
+use crate::board::Board;
+use crate::engine::StopHandle;
+use crate::player::Role;
+use std::collections::HashSet;
+use std::time::Instant;
+
+const ALL_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+
+/// Default node budget of a VCF search.
+pub const DEFAULT_VCF_NODES: usize = 5_000;
+/// Longest sequence of fours tried.
+const MAX_VCF_DEPTH: usize = 30;
+
+/// Outcome of a VCF (victory by continuous fours) search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VcfResult {
+  /// Proven win. The sequence alternates attacker fours and forced defender replies
+  /// and ends with the attacker's five.
+  Win(Vec<(usize, usize)>),
+  /// Every sequence of fours fails.
+  NoWin,
+  /// The node or time budget ran out before the search finished.
+  Unknown,
+}
+
+/// Threat solver that only plays fours for the attacker, so every defender reply is forced.
+pub struct VcfSolver {
+  max_nodes: usize,
+  deadline: Option<Instant>,
+  stop: StopHandle,
+  pub nodes: usize,
+  out_of_budget: bool,
+  /// Longest sequence of fours tried, `MAX_VCF_DEPTH` outside tests.
+  max_depth: usize,
+  /// Set when a line was cut off at `max_depth`, so its failure proves nothing.
+  depth_cut: bool,
+  /// Positions (attacker to move) already shown to have no VCF.
+  refuted: HashSet<u64>,
+}
+
+impl VcfSolver {
+  pub fn new(max_nodes: usize) -> Self {
+    Self {
+      max_nodes,
+      deadline: None,
+      stop: StopHandle::default(),
+      nodes: 0,
+      out_of_budget: false,
+      max_depth: MAX_VCF_DEPTH,
+      depth_cut: false,
+      refuted: HashSet::new(),
+    }
+  }
+
+  /// Also stop at `deadline`, if any.
+  pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+    self.deadline = deadline;
+    self
+  }
+
+  /// Also stop when `stop` is raised from another thread.
+  pub fn with_stop(mut self, stop: StopHandle) -> Self {
+    self.stop = stop;
+    self
+  }
+
+  #[cfg(test)]
+  fn with_max_depth(mut self, max_depth: usize) -> Self {
+    self.max_depth = max_depth;
+    self
+  }
+
+  /// Look for a VCF for `attacker`, who is to move. The board is restored afterwards.
+  pub fn solve(&mut self, board: &mut Board, attacker: Role) -> VcfResult {
+    self.nodes = 0;
+    self.out_of_budget = false;
+    self.depth_cut = false;
+    // Refutations only hold for the same attacker
+    self.refuted.clear();
+    let mut sequence = Vec::new();
+    if self.search(board, attacker, 0, &mut sequence) {
+      VcfResult::Win(sequence)
+    } else if self.out_of_budget || self.depth_cut {
+      VcfResult::Unknown
+    } else {
+      VcfResult::NoWin
+    }
+  }
+
+  fn search(&mut self, board: &mut Board, attacker: Role, depth: usize, sequence: &mut Vec<(usize, usize)>) -> bool {
+    self.nodes += 1;
+    if self.nodes > self.max_nodes || self.deadline.is_some_and(|d| Instant::now() >= d) || self.stop.is_stopped() {
+      self.out_of_budget = true;
+    }
+    if self.out_of_budget {
+      return false;
+    }
+
+    // 1) A five on the board ends it
+    if let Some(&five) = board.five_points(attacker).first() {
+      sequence.push(five);
+      return true;
+    }
+    if self.refuted.contains(&board.hash()) {
+      return false;
+    }
+    if depth >= self.max_depth {
+      self.depth_cut = true;
+      return false;
+    }
+
+    // 2) If the defender threatens five, our four has to be played on that very point
+    let defender = attacker.opponent();
+    let threats = board.five_points(defender);
+    if threats.len() > 1 {
+      return false;
+    }
+    let candidates: Vec<(usize, usize)> = four_moves(board, attacker)
+      .into_iter()
+      .filter(|m| threats.is_empty() || threats.contains(m))
+      .collect();
+
+    // Only a subtree searched to the end is a refutation
+    let cut_before = std::mem::take(&mut self.depth_cut);
+    for (mx, my) in candidates {
+      if !board.put(mx, my, attacker) {
+        continue;
+      }
+      let fives = fives_through(board, mx, my, attacker);
+      let won = match fives.as_slice() {
+        // Not a four after all (e.g. it only makes an overline under exact-five rules)
+        [] => false,
+        // Two ways to five: the defender can stop only one
+        [first, second, ..] => {
+          sequence.extend([(mx, my), *first, *second]);
+          true
+        }
+        [(bx, by)] => self.defend_and_continue(board, attacker, (mx, my), (*bx, *by), depth, sequence),
+      };
+      board.undo();
+      if won || self.out_of_budget {
+        self.depth_cut |= cut_before;
+        return won;
+      }
+    }
+
+    if !self.depth_cut {
+      self.refuted.insert(board.hash());
+    }
+    self.depth_cut |= cut_before;
+    false
+  }
+
+  /// The defender blocks the single five point `block`; then the attacker keeps going.
+  fn defend_and_continue(
+    &mut self,
+    board: &mut Board,
+    attacker: Role,
+    four: (usize, usize),
+    block: (usize, usize),
+    depth: usize,
+    sequence: &mut Vec<(usize, usize)>,
+  ) -> bool {
+    let defender = attacker.opponent();
+    // Renju: Black cannot block on a forbidden point
+    if !board.put(block.0, block.1, defender) {
+      sequence.extend([four, block]);
+      return true;
+    }
+    // The block itself may complete a five for the defender
+    let won = if board.get_winner() == defender.to_int() {
+      false
+    } else {
+      sequence.extend([four, block]);
+      let won = self.search(board, attacker, depth + 1, sequence);
+      if !won {
+        sequence.truncate(sequence.len() - 2);
+      }
+      won
+    };
+    board.undo();
+    won
+  }
+}
+
+/// Empty cells where `role` would make a four (a move after which it threatens five).
+/// Candidates are pre-filtered to cells with three own stones on one of their lines.
+pub fn four_moves(board: &mut Board, role: Role) -> Vec<(usize, usize)> {
+  let role_val = role.to_int();
+  let mut moves = Vec::new();
+  for x in 0..board.size {
+    for y in 0..board.size {
+      if board.board[x + 1][y + 1] != 0 || !has_three_on_a_line(board, x, y, role_val) {
+        continue;
+      }
+      if board.put(x, y, role) {
+        let is_four = !fives_through(board, x, y, role).is_empty();
+        board.undo();
+        if is_four {
+          moves.push((x, y, board.get_role_score(role, x, y)));
+        }
+      }
+    }
+  }
+  // Strongest shapes (open fours, four-threes) first
+  moves.sort_by_key(|&(_, _, score)| std::cmp::Reverse(score));
+  moves.into_iter().map(|(x, y, _)| (x, y)).collect()
+}
+
+/// Five points of `role` on the four lines through (x, y), within reach of that stone.
+fn fives_through(board: &Board, x: usize, y: usize, role: Role) -> Vec<(usize, usize)> {
+  let mut points = Vec::new();
+  for &(dx, dy) in &ALL_DIRECTIONS {
+    for step in -4..=4 {
+      let nx = x as i32 + step * dx;
+      let ny = y as i32 + step * dy;
+      if step == 0 || nx < 0 || ny < 0 || nx >= board.size as i32 || ny >= board.size as i32 {
+        continue;
+      }
+      let point = (nx as usize, ny as usize);
+      if board.makes_five(point.0, point.1, role) && !points.contains(&point) {
+        points.push(point);
+      }
+    }
+  }
+  points
+}
+
+fn has_three_on_a_line(board: &Board, x: usize, y: usize, role_val: i32) -> bool {
+  ALL_DIRECTIONS.iter().any(|&(dx, dy)| {
+    (-4..=4)
+      .filter(|&step| {
+        let nx = x as i32 + 1 + step * dx;
+        let ny = y as i32 + 1 + step * dy;
+        step != 0
+          && nx >= 0
+          && ny >= 0
+          && nx < board.board.len() as i32
+          && ny < board.board.len() as i32
+          && board.board[nx as usize][ny as usize] == role_val
+      })
+      .count()
+      >= 3
+  })
+}
+
+impl Board {
+  /// Search for a VCF for `role` (to move) within `max_nodes` nodes and before `deadline`,
+  /// giving up early once `stop`, if any, is raised. Handy on its own for checking puzzles.
+  pub fn find_vcf(&mut self, role: Role, max_nodes: usize, deadline: Option<Instant>, stop: Option<&StopHandle>) -> VcfResult {
+    let mut solver = VcfSolver::new(max_nodes).with_deadline(deadline);
+    if let Some(stop) = stop {
+      solver = solver.with_stop(stop.clone());
+    }
+    solver.solve(self, role)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::board::Rule;
+
+  /// Replay a VCF sequence and check that it ends in a five for `attacker`.
+  fn assert_sequence_wins(board: &mut Board, attacker: Role, sequence: &[(usize, usize)]) {
+    let mut role = attacker;
+    for &(x, y) in sequence {
+      assert!(board.put(x, y, role), "illegal move {:?} in {:?}", (x, y), sequence);
+      role = role.opponent();
+    }
+    assert_eq!(board.get_winner(), attacker.to_int());
+  }
+
+  #[test]
+  fn test_immediate_five() {
+    let mut b = Board::with_stones(Rule::Freestyle, &[(3, 3), (4, 3), (5, 3), (6, 3)], &[(2, 3)]);
+    assert_eq!(b.find_vcf(Role::Black, 100, None, None), VcfResult::Win(vec![(7, 3)]));
+  }
+
+  /// Black needs at least two fours: (4,4) fours on the diagonal and adds to column 4,
+  /// then (4,7) fours on row 7 and column 4 at once.
+  fn two_step_position() -> Board {
+    let black = [(1, 1), (2, 2), (3, 3), (4, 5), (4, 6), (1, 7), (2, 7), (3, 7)];
+    let white = [(0, 0), (0, 7), (14, 14), (14, 0), (0, 14), (13, 13), (13, 0), (0, 13)];
+    Board::with_stones(Rule::Freestyle, &black, &white)
+  }
+
+  #[test]
+  fn test_two_step_vcf() {
+    let mut b = two_step_position();
+    let history = b.history.len();
+    match b.find_vcf(Role::Black, 10_000, None, None) {
+      VcfResult::Win(seq) => {
+        assert_eq!(b.history.len(), history, "board must be restored");
+        assert_sequence_wins(&mut b, Role::Black, &seq);
+      }
+      other => panic!("expected a VCF, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_no_vcf() {
+    let mut b = Board::with_stones(Rule::Freestyle, &[(7, 7), (8, 8)], &[(7, 8)]);
+    assert_eq!(b.find_vcf(Role::Black, 10_000, None, None), VcfResult::NoWin);
+  }
+
+  #[test]
+  fn test_defender_five_comes_first() {
+    // Black could make fours, but White already threatens five at (9,10)
+    let black = [(4, 3), (5, 3), (6, 3), (7, 4), (7, 5), (7, 6)];
+    let white = [(3, 3), (7, 7), (9, 6), (9, 7), (9, 8), (9, 9)];
+    let mut b = Board::with_stones(Rule::Freestyle, &black, &white);
+    assert!(!matches!(b.find_vcf(Role::Black, 10_000, None, None), VcfResult::Win(_)));
+  }
+
+  #[test]
+  fn test_depth_cutoff_is_unknown() {
+    let mut b = two_step_position();
+    let mut solver = VcfSolver::new(10_000).with_max_depth(1);
+    assert_eq!(solver.solve(&mut b, Role::Black), VcfResult::Unknown);
+    assert!(matches!(VcfSolver::new(10_000).solve(&mut b, Role::Black), VcfResult::Win(_)));
+  }
+
+  #[test]
+  fn test_transposed_cutoff_is_not_refuted() {
+    // Three dead-end threes: the fours on rows 1 and 6 reach the same position in
+    // either order, and only a third four on row 11 shows it has no VCF.
+    let black = [(1, 1), (2, 1), (3, 1), (1, 6), (2, 6), (3, 6), (1, 11), (2, 11), (3, 11)];
+    let white = [(0, 1), (0, 6), (0, 11), (14, 14)];
+    let mut b = Board::with_stones(Rule::Freestyle, &black, &white);
+    let mut solver = VcfSolver::new(100_000).with_max_depth(2);
+    assert_eq!(solver.solve(&mut b, Role::Black), VcfResult::Unknown);
+    // Positions whose lines were cut off at the limit must not be cached as refuted
+    assert!(b.put(4, 1, Role::Black) && b.put(5, 1, Role::White));
+    assert!(!solver.refuted.contains(&b.hash()));
+    b.undo();
+    b.undo();
+    assert_eq!(VcfSolver::new(100_000).solve(&mut b, Role::Black), VcfResult::NoWin);
+  }
+
+  #[test]
+  fn test_budget_exhausted() {
+    let mut b = two_step_position();
+    assert_eq!(b.find_vcf(Role::Black, 1, None, None), VcfResult::Unknown);
+    let deadline = Some(Instant::now());
+    assert_eq!(b.find_vcf(Role::Black, 10_000, deadline, None), VcfResult::Unknown);
+    let stop = StopHandle::default();
+    stop.stop();
+    assert_eq!(b.find_vcf(Role::Black, 10_000, None, Some(&stop)), VcfResult::Unknown);
+  }
+
+  #[test]
+  fn test_renju_block_on_forbidden_point() {
+    // White's four on the diagonal can only be blocked at (7,7), a double-three for Black
+    let mut b = Board::with_rule(15, Rule::Renju);
+    for &(x, y) in &[(5, 7), (6, 7), (7, 5), (7, 6), (12, 12)] {
+      b.put(x, y, Role::Black);
+    }
+    for &(x, y) in &[(9, 9), (10, 10), (11, 11)] {
+      b.put(x, y, Role::White);
+    }
+    match b.find_vcf(Role::White, 1_000, None, None) {
+      VcfResult::Win(seq) => assert_eq!(seq[..2], [(8, 8), (7, 7)]),
+      other => panic!("expected a VCF, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_engine_plays_vcf() {
+    let mut b = two_step_position();
+    let mut ai = crate::ai::AIEngine::new(1);
+    let (value, mv, _) = ai.make_move(&mut b, Role::Black);
+    assert!(value >= crate::ai::HIGH_VALUE);
+    let (x, y) = mv.unwrap();
+    assert!(b.put(x, y, Role::Black));
+  }
+}
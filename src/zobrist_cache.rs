@@ -1,4 +1,20 @@
-use rand::Rng;
+/// Seed of the keys every board uses, so that `Board::hash` is the same in every
+/// process and on every platform and can be stored in files.
+pub const DEFAULT_ZOBRIST_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// SplitMix64: a tiny generator whose output is fully specified by its seed, unlike
+/// `rand`'s generators which may change between versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+}
 
 /// Structure for storing the Zobrist table and the current hash.
 #[derive(Clone, Debug)]
@@ -16,7 +32,12 @@ pub struct ZobristCache {
 impl ZobristCache {
   /// Create a new Zobrist table for a board of size `size x size`
   pub fn new(size: usize) -> Self {
-    let zobrist_table = Self::initialize_zobrist_table(size);
+    Self::with_seed(size, DEFAULT_ZOBRIST_SEED)
+  }
+
+  /// Create a Zobrist table whose keys are generated from `seed`.
+  pub fn with_seed(size: usize, seed: u64) -> Self {
+    let zobrist_table = Self::initialize_zobrist_table(size, seed);
     ZobristCache {
       zobrist_table,
       hash: 0,
@@ -25,17 +46,16 @@ impl ZobristCache {
   }
 
   /// Initialize the Zobrist table for each cell [x][y] and for each role (1 / -1).
-  fn initialize_zobrist_table(size: usize) -> Vec<Vec<[u64; 2]>> {
+  fn initialize_zobrist_table(size: usize, seed: u64) -> Vec<Vec<[u64; 2]>> {
     let mut table = vec![vec![[0u64; 2]; size]; size];
-    let mut rng = rand::thread_rng();
-
-    for x in 0..size {
-      for y in 0..size {
-        // We have two "roles": role=1 (black) and role=-1 (white).
-        // To simplify, we place them in indices 0 and 1 respectively.
-        table[x][y][0] = rng.gen::<u64>(); // for role=1
-        table[x][y][1] = rng.gen::<u64>(); // for role=-1
-      }
+    let mut rng = SplitMix64(seed);
+
+    // Column by column, so the keys of a cell depend only on the seed and the size
+    for keys in table.iter_mut().flatten() {
+      // We have two "roles": role=1 (black) and role=-1 (white).
+      // To simplify, we place them in indices 0 and 1 respectively.
+      keys[0] = rng.next_u64(); // for role=1
+      keys[1] = rng.next_u64(); // for role=-1
     }
     table
   }
@@ -43,9 +63,14 @@ impl ZobristCache {
   /// Toggle (XOR) the hash value for the stone `role` at cell (x,y).
   /// The `role` parameter is expected to be `1` (black) or `-1` (white).
   pub fn toggle_piece(&mut self, x: usize, y: usize, role: i32) {
+    self.hash ^= self.key(x, y, role);
+  }
+
+  /// Key of the stone `role` (`1` / `-1`) at cell (x,y).
+  pub fn key(&self, x: usize, y: usize, role: i32) -> u64 {
     // Convert role (1/-1) to index 0/1.
     let role_index = if role == 1 { 0 } else { 1 };
-    self.hash ^= self.zobrist_table[x][y][role_index];
+    self.zobrist_table[x][y][role_index]
   }
 
   /// Returns the current Zobrist hash value.
@@ -56,7 +81,26 @@ impl ZobristCache {
 
 #[cfg(test)]
 mod tests {
-  use super::ZobristCache;
+  use super::{SplitMix64, ZobristCache};
+
+  #[test]
+  fn test_splitmix64_reference_values() {
+    // First outputs of the reference implementation for seed 0
+    let mut rng = SplitMix64(0);
+    assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+    assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+  }
+
+  #[test]
+  fn test_keys_depend_only_on_the_seed() {
+    let (a, b) = (ZobristCache::new(15), ZobristCache::new(15));
+    assert_eq!(a.key(3, 4, 1), b.key(3, 4, 1));
+    assert_eq!(a.key(14, 0, -1), b.key(14, 0, -1));
+    // Pinned, so that hashes saved by one build keep working in the next
+    assert_eq!(a.key(0, 0, 1), 0xC0E1_6B16_3A85_A4DC);
+    let other = ZobristCache::with_seed(15, 1);
+    assert_ne!(a.key(3, 4, 1), other.key(3, 4, 1));
+  }
 
   #[test]
   fn test_zobrist_toggle() {
@@ -0,0 +1,39 @@
+#!/bin/sh
+# Fake Piskvork brain for the external engine tests.
+#
+# Modes (first argument):
+#   play   BEGIN -> 7,7; TURN x,y -> x+1,y; BOARD -> 0,0
+#   crash  exits as soon as it is asked for a move
+#   hang   never answers a move request
+mode="$1"
+
+while IFS= read -r line; do
+  line=$(printf '%s' "$line" | tr -d '\r')
+  case "$line" in
+    START*) echo "OK" ;;
+    INFO*) ;;
+    END) exit 0 ;;
+    BEGIN | TURN* | BOARD)
+      case "$mode" in
+        crash) exit 1 ;;
+        hang) exec sleep 30 ;;
+      esac
+      case "$line" in
+        BEGIN) echo "7,7" ;;
+        TURN*)
+          point=${line#TURN }
+          x=${point%,*}
+          y=${point#*,}
+          echo "$((x + 1)),$y"
+          ;;
+        BOARD)
+          while IFS= read -r entry; do
+            [ "$(printf '%s' "$entry" | tr -d '\r')" = "DONE" ] && break
+          done
+          echo "0,0"
+          ;;
+      esac
+      ;;
+    *) echo "UNKNOWN $line" ;;
+  esac
+done